@@ -0,0 +1,642 @@
+//! A set of helpers to handle the official Digital Revocation List (DRL).
+//!
+//! The revocation list is a set of SHA-256 hashes of the Unique Vaccination
+//! Certificate/Assertion Identifiers (UVCIs) of revoked certificates. It is versioned and it is
+//! published in chunks: a client first retrieves the [`DrlStatus`] from [`STATUS_URL`], then it
+//! downloads all the [`DrlChunk`]s from [`URL`] and it applies them to a [`RevocationStore`].
+//! When the store is already at a previous version, the chunks contain a delta instead of the
+//! whole list.
+
+use std::{collections::HashSet, fmt};
+
+use dgc::Dgc;
+use serde::{Deserialize, Serialize};
+
+/// The URL from which the status of the revocation list can be retrieved in JSON format.
+///
+/// The `version` query parameter must be set to the version of the local [`RevocationStore`].
+pub const STATUS_URL: &str = "https://get.dgc.gov.it/v1/dgc/drl/check";
+
+/// The URL from which the chunks of the revocation list can be retrieved in JSON format.
+///
+/// The `version` query parameter must be set to the version of the local [`RevocationStore`],
+/// and the `chunk` query parameter to the number of the chunk, starting from 1.
+pub const URL: &str = "https://get.dgc.gov.it/v1/dgc/drl";
+
+/// The status of the revocation list exposed by [official APIs](STATUS_URL).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DrlStatus {
+    /// The identifier of the revocation list.
+    pub id: String,
+
+    /// The version from which the delta is computed, if the update is not a snapshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_version: Option<u64>,
+
+    /// The latest version of the revocation list.
+    pub version: u64,
+
+    /// The total number of chunks of the update.
+    pub total_chunk: u32,
+
+    /// The total number of revoked UVCIs at the latest version.
+    #[serde(rename = "totalNumberUCVI")]
+    pub total_number_ucvi: u64,
+
+    /// The total size of the update.
+    #[serde(default)]
+    pub total_size_in_byte: u64,
+
+    /// The size of a single chunk.
+    #[serde(default)]
+    pub size_single_chunk_in_byte: u64,
+}
+
+/// A chunk of the revocation list exposed by [official APIs](URL).
+///
+/// A chunk contains either a part of the whole list (a _snapshot_) or a part of the changes
+/// from a previous version (a _delta_).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DrlChunk {
+    /// The identifier of the revocation list.
+    pub id: String,
+
+    /// The version of the revocation list.
+    pub version: u64,
+
+    /// The version from which the delta is computed, if the chunk contains a delta.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_version: Option<u64>,
+
+    /// The number of the chunk, starting from 1.
+    pub chunk: u32,
+
+    /// The number of the last chunk of the update.
+    pub last_chunk: u32,
+
+    /// The hashes of revoked UVCIs, when the chunk is part of a snapshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_ucvi: Option<Vec<String>>,
+
+    /// The changes from [`from_version`](Self::from_version), when the chunk is part of a delta.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<DrlDelta>,
+
+    /// The size of a single chunk.
+    #[serde(default)]
+    pub size_single_chunk_in_byte: u64,
+
+    /// The total number of revoked UVCIs at the version of the chunk.
+    #[serde(rename = "totalNumberUCVI")]
+    pub total_number_ucvi: u64,
+}
+
+/// The changes of the revocation list between two versions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DrlDelta {
+    /// The hashes of the UVCIs that have been revoked.
+    #[serde(default)]
+    pub insertions: Vec<String>,
+
+    /// The hashes of the UVCIs that are not revoked anymore.
+    #[serde(default)]
+    pub deletions: Vec<String>,
+}
+
+/// Hashes a UVCI the same way the revocation list does: the SHA-256 digest of the UVCI,
+/// encoded using base64.
+pub fn hash_uvci(uvci: &str) -> String {
+    base64::encode(ring::digest::digest(&ring::digest::SHA256, uvci.as_bytes()))
+}
+
+/// The local copy of the revocation list.
+///
+/// The store is updated applying in order all the chunks of an update. Changes are
+/// committed only when the last chunk is applied, therefore an interrupted or failed update
+/// leaves the store at the previous version.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RevocationStore {
+    id: Option<String>,
+    version: u64,
+    hashes: HashSet<String>,
+    pending: Option<PendingUpdate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PendingUpdate {
+    id: String,
+    version: u64,
+    total_chunks: u32,
+    next_chunk: u32,
+    data: PendingData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PendingData {
+    Snapshot(HashSet<String>),
+    Delta {
+        insertions: Vec<String>,
+        deletions: Vec<String>,
+    },
+}
+
+impl RevocationStore {
+    /// Creates an empty store, at version 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The identifier of the revocation list, if any update has been applied.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The version of the revocation list.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The number of revoked UVCIs.
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Checks if there are no revoked UVCIs.
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Checks if the store must be updated to reach the given status.
+    pub fn needs_update(&self, status: &DrlStatus) -> bool {
+        status.version != self.version || self.id.as_deref() != Some(status.id.as_str())
+    }
+
+    /// The number of the next chunk to apply, if an update is in progress.
+    pub fn next_chunk(&self) -> Option<u32> {
+        self.pending.as_ref().map(|pending| pending.next_chunk)
+    }
+
+    /// Discards the changes of an update in progress.
+    pub fn abort_update(&mut self) {
+        self.pending = None;
+    }
+
+    /// Applies a chunk of the update described by the given status.
+    ///
+    /// Chunks must be applied in order. When an error occurs, the update in progress is
+    /// discarded and it must be restarted from the first chunk.
+    pub fn apply(&mut self, status: &DrlStatus, chunk: DrlChunk) -> Result<(), DrlError> {
+        let result = self.try_apply(status, chunk);
+        if result.is_err() {
+            self.pending = None;
+        }
+        result
+    }
+
+    fn try_apply(&mut self, status: &DrlStatus, chunk: DrlChunk) -> Result<(), DrlError> {
+        if chunk.id != status.id {
+            return Err(DrlError::IdMismatch {
+                expected: status.id.clone(),
+                found: chunk.id,
+            });
+        }
+        if chunk.version != status.version {
+            return Err(DrlError::VersionMismatch {
+                expected: status.version,
+                found: chunk.version,
+            });
+        }
+        if chunk.last_chunk != status.total_chunk {
+            return Err(DrlError::ChunkCountMismatch {
+                expected: status.total_chunk,
+                found: chunk.last_chunk,
+            });
+        }
+
+        let expected_chunk = self.next_chunk().unwrap_or(1);
+        if chunk.chunk != expected_chunk {
+            return Err(DrlError::UnexpectedChunk {
+                expected: expected_chunk,
+                found: chunk.chunk,
+            });
+        }
+
+        let mut pending = match self.pending.take() {
+            Some(pending) if pending.version == chunk.version => pending,
+            Some(pending) => {
+                return Err(DrlError::VersionMismatch {
+                    expected: pending.version,
+                    found: chunk.version,
+                })
+            }
+            None => PendingUpdate {
+                id: chunk.id.clone(),
+                version: chunk.version,
+                total_chunks: chunk.last_chunk,
+                next_chunk: 1,
+                data: match (&chunk.revoked_ucvi, &chunk.delta) {
+                    (Some(_), None) => PendingData::Snapshot(HashSet::new()),
+                    (None, Some(_)) => PendingData::Delta {
+                        insertions: Vec::new(),
+                        deletions: Vec::new(),
+                    },
+                    _ => return Err(DrlError::InvalidChunk(chunk.chunk)),
+                },
+            },
+        };
+
+        match (&mut pending.data, chunk.revoked_ucvi, chunk.delta) {
+            (PendingData::Snapshot(hashes), Some(revoked), None) => hashes.extend(revoked),
+            (
+                PendingData::Delta {
+                    insertions,
+                    deletions,
+                },
+                None,
+                Some(delta),
+            ) => {
+                let from_version = chunk.from_version.unwrap_or_default();
+                if from_version != self.version {
+                    return Err(DrlError::FromVersionMismatch {
+                        expected: self.version,
+                        found: from_version,
+                    });
+                }
+                insertions.extend(delta.insertions);
+                deletions.extend(delta.deletions);
+            }
+            _ => return Err(DrlError::InvalidChunk(chunk.chunk)),
+        }
+
+        if pending.next_chunk < pending.total_chunks {
+            pending.next_chunk += 1;
+            self.pending = Some(pending);
+            return Ok(());
+        }
+
+        let hashes = match pending.data {
+            PendingData::Snapshot(hashes) => hashes,
+            PendingData::Delta {
+                insertions,
+                deletions,
+            } => {
+                let mut hashes = self.hashes.clone();
+                for deletion in &deletions {
+                    hashes.remove(deletion);
+                }
+                hashes.extend(insertions);
+                hashes
+            }
+        };
+        if hashes.len() as u64 != status.total_number_ucvi {
+            return Err(DrlError::CountMismatch {
+                expected: status.total_number_ucvi,
+                found: hashes.len() as u64,
+            });
+        }
+
+        self.id = Some(pending.id);
+        self.version = pending.version;
+        self.hashes = hashes;
+        Ok(())
+    }
+
+    /// Checks if the given UVCI has been revoked.
+    pub fn contains_uvci(&self, uvci: &str) -> bool {
+        self.hashes.contains(&hash_uvci(uvci))
+    }
+
+    /// Checks if any vaccination, test or recovery entry of the certificate has a UVCI that
+    /// has been revoked.
+    pub fn contains(&self, dgc: &Dgc) -> bool {
+        dgc.vaccines
+            .iter()
+            .map(|vaccination| &vaccination.id)
+            .chain(dgc.tests.iter().map(|test| &test.id))
+            .chain(dgc.recoveries.iter().map(|recovery| &recovery.id))
+            .any(|uvci| self.contains_uvci(uvci))
+    }
+}
+
+/// An error that can occur when applying a chunk to a [`RevocationStore`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DrlError {
+    /// The chunk belongs to a different revocation list.
+    IdMismatch {
+        /// The expected identifier.
+        expected: String,
+
+        /// The identifier of the chunk.
+        found: String,
+    },
+
+    /// The chunk has a different version than the update.
+    VersionMismatch {
+        /// The expected version.
+        expected: u64,
+
+        /// The version of the chunk.
+        found: u64,
+    },
+
+    /// The delta contained in the chunk does not start from the version of the store.
+    FromVersionMismatch {
+        /// The version of the store.
+        expected: u64,
+
+        /// The version from which the delta is computed.
+        found: u64,
+    },
+
+    /// The chunk has a different number of total chunks than the update.
+    ChunkCountMismatch {
+        /// The expected number of chunks.
+        expected: u32,
+
+        /// The number of chunks declared by the chunk.
+        found: u32,
+    },
+
+    /// The chunk has not been applied in order.
+    UnexpectedChunk {
+        /// The number of the expected chunk.
+        expected: u32,
+
+        /// The number of the chunk.
+        found: u32,
+    },
+
+    /// The chunk with the given number contains neither a snapshot nor a delta, or it does
+    /// not match the kind of the previous chunks.
+    InvalidChunk(u32),
+
+    /// The number of revoked UVCIs after the update does not match the status.
+    CountMismatch {
+        /// The number of revoked UVCIs declared by the status.
+        expected: u64,
+
+        /// The number of revoked UVCIs after the update.
+        found: u64,
+    },
+}
+
+impl fmt::Display for DrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DrlError::*;
+
+        match self {
+            IdMismatch { expected, found } => {
+                write!(f, r#"expected DRL id "{}", found "{}""#, expected, found)
+            }
+            VersionMismatch { expected, found } => {
+                write!(f, "expected DRL version {}, found {}", expected, found)
+            }
+            FromVersionMismatch { expected, found } => write!(
+                f,
+                "expected DRL delta from version {}, found delta from version {}",
+                expected, found
+            ),
+            ChunkCountMismatch { expected, found } => {
+                write!(f, "expected {} DRL chunks, found {}", expected, found)
+            }
+            UnexpectedChunk { expected, found } => {
+                write!(f, "expected DRL chunk {}, found chunk {}", expected, found)
+            }
+            InvalidChunk(chunk) => write!(f, "DRL chunk {} is invalid", chunk),
+            CountMismatch { expected, found } => write!(
+                f,
+                "expected {} revoked UVCIs after the update, found {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DrlError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(version: u64, total_chunk: u32, total_number_ucvi: u64) -> DrlStatus {
+        DrlStatus {
+            id: "drl".to_string(),
+            from_version: None,
+            version,
+            total_chunk,
+            total_number_ucvi,
+            total_size_in_byte: 0,
+            size_single_chunk_in_byte: 0,
+        }
+    }
+
+    fn snapshot(version: u64, chunk: u32, last_chunk: u32, uvcis: &[&str]) -> DrlChunk {
+        DrlChunk {
+            id: "drl".to_string(),
+            version,
+            from_version: None,
+            chunk,
+            last_chunk,
+            revoked_ucvi: Some(uvcis.iter().map(|uvci| hash_uvci(uvci)).collect()),
+            delta: None,
+            size_single_chunk_in_byte: 0,
+            total_number_ucvi: 0,
+        }
+    }
+
+    fn delta(
+        from_version: u64,
+        version: u64,
+        chunk: u32,
+        last_chunk: u32,
+        insertions: &[&str],
+        deletions: &[&str],
+    ) -> DrlChunk {
+        DrlChunk {
+            id: "drl".to_string(),
+            version,
+            from_version: Some(from_version),
+            chunk,
+            last_chunk,
+            revoked_ucvi: None,
+            delta: Some(DrlDelta {
+                insertions: insertions.iter().map(|uvci| hash_uvci(uvci)).collect(),
+                deletions: deletions.iter().map(|uvci| hash_uvci(uvci)).collect(),
+            }),
+            size_single_chunk_in_byte: 0,
+            total_number_ucvi: 0,
+        }
+    }
+
+    #[test]
+    fn it_hashes_uvcis() {
+        assert_eq!(
+            hash_uvci("01ITE7300E1AB2A84C719004F103DCB1F70A#6"),
+            "DyJmCv7CNbMRx3IA0WIvtERL+QWlBpPDt96ehUXBnso="
+        );
+    }
+
+    #[test]
+    fn it_deserializes_status_and_chunks() {
+        let status: DrlStatus = serde_json::from_str(
+            r#"{
+                "fromVersion": 1,
+                "id": "61f0a4df8ed0d96d0f8bcfed",
+                "totalNumberUCVI": 3,
+                "version": 2,
+                "chunk": 1,
+                "totalChunk": 1,
+                "totalSizeInByte": 312,
+                "sizeSingleChunkInByte": 1000
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(status.from_version, Some(1));
+        assert_eq!(status.version, 2);
+        assert_eq!(status.total_chunk, 1);
+        assert_eq!(status.total_number_ucvi, 3);
+
+        let chunk: DrlChunk = serde_json::from_str(
+            r#"{
+                "id": "61f0a4df8ed0d96d0f8bcfed",
+                "version": 2,
+                "fromVersion": 1,
+                "chunk": 1,
+                "lastChunk": 1,
+                "delta": {
+                    "insertions": ["DyJmCv7CNbMRx3IA0WIvtERL+QWlBpPDt96ehUXBnso="],
+                    "deletions": []
+                },
+                "sizeSingleChunkInByte": 1000,
+                "totalNumberUCVI": 3
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(chunk.revoked_ucvi, None);
+        assert_eq!(chunk.delta.unwrap().insertions.len(), 1);
+    }
+
+    #[test]
+    fn it_applies_snapshots_and_deltas() {
+        let mut store = RevocationStore::new();
+        let status = status(1, 2, 3);
+        assert!(store.needs_update(&status));
+
+        store
+            .apply(&status, snapshot(1, 1, 2, &["A#1", "B#1"]))
+            .unwrap();
+        assert_eq!(store.next_chunk(), Some(2));
+        // nothing is committed until the last chunk is applied
+        assert!(store.is_empty());
+        assert_eq!(store.version(), 0);
+
+        store.apply(&status, snapshot(1, 2, 2, &["C#1"])).unwrap();
+        assert_eq!(store.next_chunk(), None);
+        assert_eq!(store.version(), 1);
+        assert_eq!(store.id(), Some("drl"));
+        assert_eq!(store.len(), 3);
+        assert!(store.contains_uvci("A#1"));
+        assert!(!store.needs_update(&status));
+
+        let status = super::tests::status(2, 1, 3);
+        store
+            .apply(&status, delta(1, 2, 1, 1, &["D#1"], &["A#1"]))
+            .unwrap();
+        assert_eq!(store.version(), 2);
+        assert!(!store.contains_uvci("A#1"));
+        assert!(store.contains_uvci("D#1"));
+    }
+
+    #[test]
+    fn it_rejects_inconsistent_chunks() {
+        let mut store = RevocationStore::new();
+        let status = status(1, 2, 2);
+
+        assert_eq!(
+            store.apply(&status, snapshot(2, 1, 2, &["A#1"])),
+            Err(DrlError::VersionMismatch {
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            store.apply(&status, snapshot(1, 1, 3, &["A#1"])),
+            Err(DrlError::ChunkCountMismatch {
+                expected: 2,
+                found: 3
+            })
+        );
+        assert_eq!(
+            store.apply(&status, snapshot(1, 2, 2, &["A#1"])),
+            Err(DrlError::UnexpectedChunk {
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            store.apply(&status, delta(3, 1, 1, 2, &["A#1"], &[])),
+            Err(DrlError::FromVersionMismatch {
+                expected: 0,
+                found: 3
+            })
+        );
+
+        store.apply(&status, snapshot(1, 1, 2, &["A#1"])).unwrap();
+        assert_eq!(
+            store.apply(&status, delta(0, 1, 2, 2, &["B#1"], &[])),
+            Err(DrlError::InvalidChunk(2))
+        );
+        // the failed update has been discarded
+        assert_eq!(store.next_chunk(), None);
+
+        store.apply(&status, snapshot(1, 1, 2, &["A#1"])).unwrap();
+        assert_eq!(
+            store.apply(&status, snapshot(1, 2, 2, &[])),
+            Err(DrlError::CountMismatch {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert!(store.is_empty());
+        assert_eq!(store.version(), 0);
+    }
+
+    #[test]
+    fn it_checks_certificates() {
+        let mut store = RevocationStore::new();
+        let status = status(1, 1, 1);
+        store
+            .apply(
+                &status,
+                snapshot(1, 1, 1, &["01ITE7300E1AB2A84C719004F103DCB1F70A#6"]),
+            )
+            .unwrap();
+
+        let mut dgc: Dgc = serde_json::from_str(
+            r#"{
+                "ver": "1.3.0",
+                "nam": { "fnt": "ROSSI", "gnt": "MARIO" },
+                "dob": "1980-01-01",
+                "v": [{
+                    "tg": "840539006",
+                    "vp": "1119349007",
+                    "mp": "EU/1/20/1528",
+                    "ma": "ORG-100030215",
+                    "dn": 2,
+                    "sd": 2,
+                    "dt": "2021-04-10",
+                    "co": "IT",
+                    "is": "IT",
+                    "ci": "01ITE7300E1AB2A84C719004F103DCB1F70A#6"
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert!(store.contains(&dgc));
+
+        dgc.vaccines[0].id = "01ITE7300E1AB2A84C719004F103DCB1F70A#7".into();
+        assert!(!store.contains(&dgc));
+    }
+}
@@ -1,5 +1,9 @@
 #![deny(missing_docs)]
 #![doc = include_str!("../README.md")]
 
+pub mod drl;
 pub mod settings;
+pub mod signer_certificates;
+pub mod verification;
 pub use settings::Settings;
+pub use verification::{verify, CertificateStatus, ScanMode};
@@ -1,6 +1,8 @@
 //! A set of helpers to handle official DGC settings.
 
-use std::{borrow::Cow, fmt};
+use std::{borrow::Cow, collections::BTreeSet, fmt};
+
+use dgc::Dgc;
 
 use serde::{
     de::{self, value::StrDeserializer, IntoDeserializer},
@@ -249,11 +251,10 @@ impl<'de: 'a, 'a> Deserialize<'de> for Setting<'a> {
                         "type" => {
                             try_deserialize!(ty: SettingType, "type")
                         }
-                        "value" => {
-                            if value.replace(val).is_some() {
-                                return Err(de::Error::duplicate_field("value"));
-                            }
+                        "value" if value.is_some() => {
+                            return Err(de::Error::duplicate_field("value"));
                         }
+                        "value" => value = Some(val),
                         _ => {
                             // FIXME: Should we ignore unknown fields or should we return an error?
                             // Or log if a logging crate is available?
@@ -340,7 +341,7 @@ impl<'de: 'a, 'a> Deserialize<'de> for Settings<'a> {
 
         let vaccines = vaccines.into_complete().map_err(de::Error::custom)?;
         let deny_list = deny_list
-            .map(|deny_list| DenyList(deny_list.into()))
+            .map(DenyList::from)
             .ok_or_else(|| de::Error::custom(IncompleteSettings::MissingDenyList))?;
         let min_versions = min_versions.into_complete().map_err(de::Error::custom)?;
         let tests = tests.into_complete().map_err(de::Error::custom)?;
@@ -421,6 +422,24 @@ pub struct Vaccines {
     pub sputnik_v: VaccineSettings,
 }
 
+impl Vaccines {
+    /// Returns the settings for the given medicinal product (the `mp` field of a vaccination),
+    /// if the product is known.
+    pub fn get(&self, medicinal_product: &str) -> Option<&VaccineSettings> {
+        Some(match medicinal_product {
+            "EU/1/20/1525" => &self.janssen,
+            "EU/1/21/1529" => &self.vaxzevria,
+            "EU/1/20/1507" => &self.spikevax,
+            "EU/1/20/1528" => &self.comirnaty,
+            "Covishield" => &self.covishield,
+            "R-COVI" => &self.r_covi,
+            "Covid-19-recombinant" => &self.recombinant,
+            "Sputnik-V" => &self.sputnik_v,
+            _ => return None,
+        })
+    }
+}
+
 #[derive(Debug, Default)]
 struct PartialVaccines {
     janssen: PartialVaccineSettings,
@@ -515,11 +534,65 @@ impl PartialVaccineSettings {
 }
 
 /// A wrapper to help handling a list a Unique Vaccination Certificate/Assertion Identifiers (UVCIs) that must be considered invalid.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct DenyList<'a>(
-    /// The raw representation of the deny list.
-    pub Cow<'a, str>,
-);
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DenyList<'a>(BTreeSet<Cow<'a, str>>);
+
+impl<'a> DenyList<'a> {
+    /// The separator used between UVCIs in the raw representation of the deny list.
+    pub const SEPARATOR: char = ';';
+
+    /// Checks if the given UVCI is in the deny list.
+    pub fn contains_uvci(&self, uvci: &str) -> bool {
+        self.0.contains(uvci)
+    }
+
+    /// Checks if any vaccination, test or recovery entry of the certificate has a UVCI that
+    /// is in the deny list.
+    pub fn contains(&self, dgc: &Dgc) -> bool {
+        dgc.vaccines
+            .iter()
+            .map(|vaccination| &vaccination.id)
+            .chain(dgc.tests.iter().map(|test| &test.id))
+            .chain(dgc.recoveries.iter().map(|recovery| &recovery.id))
+            .any(|uvci| self.contains_uvci(uvci))
+    }
+
+    /// Returns an iterator over the UVCIs in the deny list.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(AsRef::as_ref)
+    }
+
+    /// The number of UVCIs in the deny list.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks if the deny list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Parses the raw representation of the deny list, a list of UVCIs separated by
+/// [`DenyList::SEPARATOR`].
+impl<'a> From<&'a str> for DenyList<'a> {
+    fn from(raw: &'a str) -> Self {
+        raw.split(Self::SEPARATOR)
+            .map(str::trim)
+            .filter(|uvci| !uvci.is_empty())
+            .map(Cow::Borrowed)
+            .collect()
+    }
+}
+
+impl<'a, T> FromIterator<T> for DenyList<'a>
+where
+    T: Into<Cow<'a, str>>,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        DenyList(iter.into_iter().map(Into::into).collect())
+    }
+}
 
 /// Minimal app versions by OS.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -2033,4 +2106,42 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn deny_list_from_raw() {
+        let deny_list =
+            DenyList::from("URN:UVCI:01:FR:W7V2BE46QSBJ#L; ;URN:UVCI:01:FR:T5DWTJYS4ZR8#4;");
+
+        assert_eq!(deny_list.len(), 2);
+        assert!(deny_list.contains_uvci("URN:UVCI:01:FR:W7V2BE46QSBJ#L"));
+        assert!(deny_list.contains_uvci("URN:UVCI:01:FR:T5DWTJYS4ZR8#4"));
+        assert!(!deny_list.contains_uvci("URN:UVCI:01:FR:W7V2BE46QSBJ"));
+        assert!(DenyList::from("").is_empty());
+    }
+
+    #[test]
+    fn deny_list_contains_dgc() {
+        let deny_list = DenyList::from("URN:UVCI:01:IT:REVOKED#1");
+        let mut dgc: Dgc = serde_json::from_str(
+            r#"{
+                "ver": "1.3.0",
+                "nam": { "fnt": "ROSSI", "gnt": "MARIO" },
+                "dob": "1980-01-01",
+                "r": [{
+                    "tg": "840539006",
+                    "fr": "2021-10-01",
+                    "co": "IT",
+                    "is": "Ministero della Salute",
+                    "df": "2021-10-12",
+                    "du": "2022-03-30",
+                    "ci": "URN:UVCI:01:IT:VALID#1"
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(!deny_list.contains(&dgc));
+        dgc.recoveries[0].id = "URN:UVCI:01:IT:REVOKED#1".into();
+        assert!(deny_list.contains(&dgc));
+    }
 }
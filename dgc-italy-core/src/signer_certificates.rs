@@ -0,0 +1,151 @@
+//! A set of helpers to load the official Document Signer Certificates (DSCs) into a
+//! [`TrustList`].
+//!
+//! The certificates are published one per page: a client repeatedly calls [`UPDATE_URL`],
+//! passing the resume token of the last page in the `X-RESUME-TOKEN` header, and it adds each
+//! page to a [`SignerCertificatesUpdate`]. Every page contains a base64 encoded certificate in
+//! the body and its KID in the `X-KID` header. When there are no more pages, the list of the
+//! KIDs of the valid certificates is retrieved from [`STATUS_URL`] and the update is applied
+//! to a [`TrustList`].
+
+use std::collections::HashSet;
+
+use dgc::{KeySource, TrustList, TrustListEntryError, TrustListLoadReport};
+
+/// The URL from which the pages of signer certificates can be retrieved.
+///
+/// The `X-RESUME-TOKEN` header must be set to the resume token of the last retrieved page,
+/// if any.
+pub const UPDATE_URL: &str = "https://get.dgc.gov.it/v1/dgc/signercertificate/update";
+
+/// The URL from which the KIDs of the valid signer certificates can be retrieved as a JSON
+/// array of strings.
+pub const STATUS_URL: &str = "https://get.dgc.gov.it/v1/dgc/signercertificate/status";
+
+/// The name of the [`KeySource`] of the keys added by [`SignerCertificatesUpdate::apply`].
+pub const KEY_SOURCE: &str = "Italian signer certificates";
+
+/// The header containing the KID of the certificate of a page.
+pub const KID_HEADER: &str = "X-KID";
+
+/// The header containing the resume token of a page.
+pub const RESUME_TOKEN_HEADER: &str = "X-RESUME-TOKEN";
+
+/// The signer certificates retrieved from [official APIs](UPDATE_URL), page after page.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignerCertificatesUpdate {
+    resume_token: Option<String>,
+    certificates: Vec<(String, String)>,
+}
+
+impl SignerCertificatesUpdate {
+    /// Creates an empty update, that starts from the first page.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The resume token of the last added page, to be used to retrieve the next one.
+    pub fn resume_token(&self) -> Option<&str> {
+        self.resume_token.as_deref()
+    }
+
+    /// The number of retrieved certificates.
+    pub fn len(&self) -> usize {
+        self.certificates.len()
+    }
+
+    /// Checks if no certificates have been retrieved.
+    pub fn is_empty(&self) -> bool {
+        self.certificates.is_empty()
+    }
+
+    /// Adds a page, given the values of its [`KID_HEADER`] and [`RESUME_TOKEN_HEADER`]
+    /// headers and its body.
+    pub fn add_page(&mut self, kid: &str, resume_token: &str, certificate: &str) {
+        self.resume_token = Some(resume_token.to_string());
+        self.certificates
+            .push((kid.to_string(), certificate.trim().to_string()));
+    }
+
+    /// Adds the retrieved certificates to the trust list, skipping the ones whose KID is not in
+    /// the given list of valid KIDs retrieved from [`STATUS_URL`].
+    ///
+    /// Invalid certificates do not prevent the other ones from being added: their errors are
+    /// reported instead.
+    pub fn apply<I, S>(&self, valid_kids: I, trustlist: &mut TrustList) -> TrustListLoadReport
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let valid_kids: HashSet<_> = valid_kids
+            .into_iter()
+            .map(|kid| kid.as_ref().to_string())
+            .collect();
+
+        let mut report = TrustListLoadReport::default();
+        for (kid, certificate) in &self.certificates {
+            if !valid_kids.contains(kid) {
+                continue;
+            }
+            report.record(add_certificate(trustlist, kid, certificate));
+        }
+        report
+    }
+}
+
+fn add_certificate(
+    trustlist: &mut TrustList,
+    kid: &str,
+    certificate: &str,
+) -> Result<(), TrustListEntryError> {
+    let decoded_kid = base64::decode(kid)
+        .map_err(|e| TrustListEntryError::Base64DecodeError(kid.to_string(), "kid", e))?;
+    trustlist
+        .add_key_from_certificate_with_kid(
+            &decoded_kid,
+            certificate,
+            KeySource::Other(String::from(KEY_SOURCE)),
+        )
+        .map_err(|e| TrustListEntryError::KeyParseError(kid.to_string(), e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERTIFICATE: &str = "MIIEHjCCAgagAwIBAgIUM5lJeGCHoRF1raR6cbZqDV4vPA8wDQYJKoZIhvcNAQELBQAwTjELMAkGA1UEBhMCSVQxHzAdBgNVBAoMFk1pbmlzdGVybyBkZWxsYSBTYWx1dGUxHjAcBgNVBAMMFUl0YWx5IERHQyBDU0NBIFRFU1QgMTAeFw0yMTA1MDcxNzAyMTZaFw0yMzA1MDgxNzAyMTZaME0xCzAJBgNVBAYTAklUMR8wHQYDVQQKDBZNaW5pc3Rlcm8gZGVsbGEgU2FsdXRlMR0wGwYDVQQDDBRJdGFseSBER0MgRFNDIFRFU1QgMTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABDSp7t86JxAmjZFobmmu0wkii53snRuwqVWe3/g/wVz9i306XA5iXpHkRPZVUkSZmYhutMDrheg6sfwMRdql3aajgb8wgbwwHwYDVR0jBBgwFoAUS2iy4oMAoxUY87nZRidUqYg9yyMwagYDVR0fBGMwYTBfoF2gW4ZZbGRhcDovL2NhZHMuZGdjLmdvdi5pdC9DTj1JdGFseSUyMERHQyUyMENTQ0ElMjBURVNUJTIwMSxPPU1pbmlzdGVybyUyMGRlbGxhJTIwU2FsdXRlLEM9SVQwHQYDVR0OBBYEFNSEwjzu61pAMqliNhS9vzGJFqFFMA4GA1UdDwEB/wQEAwIHgDANBgkqhkiG9w0BAQsFAAOCAgEAIF74yHgzCGdor5MaqYSvkS5aog5+7u52TGggiPl78QAmIpjPO5qcYpJZVf6AoL4MpveEI/iuCUVQxBzYqlLACjSbZEbtTBPSzuhfvsf9T3MUq5cu10lkHKbFgApUDjrMUnG9SMqmQU2Cv5S4t94ec2iLmokXmhYP/JojRXt1ZMZlsw/8/lRJ8vqPUorJ/fMvOLWDE/fDxNhh3uK5UHBhRXCT8MBep4cgt9cuT9O4w1JcejSr5nsEfeo8u9Pb/h6MnmxpBSq3JbnjONVK5ak7iwCkLr5PMk09ncqG+/8Kq+qTjNC76IetS9ST6bWzTZILX4BD1BL8bHsFGgIeeCO0GqalFZAsbapnaB+36HVUZVDYOoA+VraIWECNxXViikZdjQONaeWDVhCxZ/vBl1/KLAdX3OPxRwl/jHLnaSXeqr/zYf9a8UqFrpadT0tQff/q3yH5hJRJM0P6Yp5CPIEArJRW6ovDBbp3DVF2GyAI1lFA2Trs798NN6qf7SkuySz5HSzm53g6JsLY/HLzdwJPYLObD7U+x37n+DDi4Wa6vM5xdC7FZ5IyWXuT1oAa9yM4h6nW3UvC+wNUusW6adqqtdd4F1gHPjCf5lpW5Ye1bdLUmO7TGlePmbOkzEB08Mlc6atl/vkx/crfl4dq1LZivLgPBwDzE8arIk0f2vCx1+4=";
+
+    #[test]
+    fn it_applies_the_valid_certificates() {
+        let mut update = SignerCertificatesUpdate::new();
+        assert_eq!(update.resume_token(), None);
+
+        update.add_page("AAECAwQFBgc=", "1", CERTIFICATE);
+        update.add_page("AQIDBAUGBwg=", "2", CERTIFICATE);
+        update.add_page("AgMEBQYHCAk=", "3", "not a certificate");
+        update.add_page("revoked", "4", CERTIFICATE);
+        assert_eq!(update.resume_token(), Some("4"));
+        assert_eq!(update.len(), 4);
+
+        let mut trustlist = TrustList::new();
+        let report = update.apply(
+            ["AAECAwQFBgc=", "AgMEBQYHCAk=", "AQIDBAUGBwg="],
+            &mut trustlist,
+        );
+
+        assert_eq!(report.loaded, 2);
+        assert!(matches!(
+            &report.errors[..],
+            [TrustListEntryError::KeyParseError(kid, _)] if kid == "AgMEBQYHCAk="
+        ));
+        assert_eq!(
+            trustlist
+                .get_entry(&[0, 1, 2, 3, 4, 5, 6, 7])
+                .unwrap()
+                .source,
+            KeySource::Other(String::from(KEY_SOURCE))
+        );
+        assert!(trustlist.get_key(&[1, 2, 3, 4, 5, 6, 7, 8]).is_some());
+    }
+}
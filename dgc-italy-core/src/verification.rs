@@ -0,0 +1,506 @@
+//! The algorithms to verify DGCs using Italian criteria.
+//!
+//! The rules follow the ones applied by the official
+//! [VerificaC19 SDK](https://github.com/ministero-salute/it-dgc-verificac19-sdk-android): the
+//! validity intervals of every kind of certificate are taken from the [`Settings`] and they
+//! are evaluated against the given validation instant.
+
+use std::fmt;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use dgc::{Dgc, PartialDate, Recovery, Test, Vaccination};
+
+use crate::settings::{Interval, Settings, TestData};
+
+const JANSSEN: &str = "EU/1/20/1525";
+const SPUTNIK_V: &str = "Sputnik-V";
+const ITALY: &str = "IT";
+const SAN_MARINO: &str = "SM";
+const MOLECULAR_TEST: &str = "LP6464-4";
+const RAPID_TEST: &str = "LP217198-3";
+const TEST_DETECTED: &str = "260373001";
+/// The number of days a complete vaccination cycle or a recovery is accepted in schools.
+const SCHOOL_END_DAY: u16 = 120;
+/// The age from which workers need a strengthened certificate.
+const WORK_STRENGTHENED_AGE: u32 = 50;
+
+/// The way a certificate is verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScanMode {
+    /// Base verification (_Green Pass base_): vaccinations, recoveries and negative tests
+    /// are accepted.
+    Base,
+
+    /// Strengthened verification (_Green Pass rafforzato_): only vaccinations and recoveries
+    /// are accepted.
+    Strengthened,
+
+    /// Booster verification: booster doses are accepted, while complete primary cycles and
+    /// recoveries also need a negative test.
+    Booster,
+
+    /// Verification for workplaces: people who are at least 50 years old need a strengthened
+    /// certificate, the others a base one.
+    Work,
+
+    /// Verification for schools: only vaccinations and recoveries are accepted, and complete
+    /// primary cycles and recoveries are valid for a shorter period.
+    School,
+
+    /// Verification for the entry into Italy: the intervals for certificates issued abroad
+    /// are applied to every certificate.
+    Entry,
+}
+
+/// The status of a certificate according to the Italian criteria.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CertificateStatus {
+    /// The certificate is valid.
+    Valid,
+
+    /// The certificate is not valid (e.g. it is expired or it is not accepted with the
+    /// selected scan mode).
+    NotValid,
+
+    /// The certificate will be valid in the future.
+    NotValidYet,
+
+    /// The certificate is valid only together with a negative test.
+    TestNeeded,
+
+    /// The certificate has been revoked.
+    Revoked,
+
+    /// The data is not a valid EU Digital COVID Certificate.
+    NotEuDcc,
+}
+
+impl CertificateStatus {
+    /// Checks if the certificate can be accepted.
+    pub fn is_valid(self) -> bool {
+        self == CertificateStatus::Valid
+    }
+}
+
+impl fmt::Display for CertificateStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CertificateStatus::*;
+        let s = match self {
+            Valid => "Valid",
+            NotValid => "Not valid",
+            NotValidYet => "Not valid yet",
+            TestNeeded => "Test needed",
+            Revoked => "Revoked",
+            NotEuDcc => "Not an EU DCC",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Verifies a certificate at the given instant using the Italian criteria.
+///
+/// The certificate must contain exactly one vaccination, test or recovery entry group,
+/// otherwise [`CertificateStatus::NotEuDcc`] is returned. When more entries of the same
+/// group are present, the last one is evaluated. Certificates with a UVCI contained in the
+/// deny list of the settings are reported as [`CertificateStatus::Revoked`].
+///
+/// The outcome depends on the [`ScanMode`]: the same certificate can be valid with a mode
+/// and not valid (or [`CertificateStatus::TestNeeded`]) with another one.
+pub fn verify(
+    dgc: &Dgc,
+    settings: &Settings<'_>,
+    validation_instant: DateTime<Utc>,
+    scan_mode: ScanMode,
+) -> CertificateStatus {
+    if settings.deny_list.contains(dgc) {
+        return CertificateStatus::Revoked;
+    }
+
+    let scan_mode = match scan_mode {
+        ScanMode::Work => work_scan_mode(dgc, validation_instant),
+        scan_mode => scan_mode,
+    };
+
+    match (dgc.vaccines.last(), dgc.tests.last(), dgc.recoveries.last()) {
+        (Some(vaccination), None, None) => {
+            verify_vaccination(vaccination, settings, validation_instant, scan_mode)
+        }
+        (None, Some(test), None) => verify_test(test, settings, validation_instant, scan_mode),
+        (None, None, Some(recovery)) => {
+            verify_recovery(recovery, settings, validation_instant, scan_mode)
+        }
+        _ => CertificateStatus::NotEuDcc,
+    }
+}
+
+fn verify_vaccination(
+    vaccination: &Vaccination,
+    settings: &Settings<'_>,
+    validation_instant: DateTime<Utc>,
+    scan_mode: ScanMode,
+) -> CertificateStatus {
+    let product = vaccination.medicinal_product.as_ref();
+    let vaccine = match settings.vaccines.get(product) {
+        Some(vaccine) => vaccine,
+        None => return CertificateStatus::NotValid,
+    };
+    if product == SPUTNIK_V && vaccination.country != SAN_MARINO {
+        return CertificateStatus::NotValid;
+    }
+    let date = match vaccination.parsed_date() {
+        Ok(date) => date,
+        Err(_) => return CertificateStatus::NotValid,
+    };
+
+    let is_italian = vaccination.country == ITALY && scan_mode != ScanMode::Entry;
+    let generic = &settings.generic_vaccine;
+    let cycle = cycle(vaccination);
+    let interval = match cycle {
+        Cycle::NotComplete if matches!(scan_mode, ScanMode::Booster | ScanMode::School) => {
+            return CertificateStatus::NotValid
+        }
+        Cycle::NotComplete => vaccine.not_complete,
+        Cycle::Complete => {
+            let generic = if is_italian {
+                generic.complete_it
+            } else {
+                generic.complete_not_it
+            };
+            // the single dose vaccine needs some days before being effective
+            let start_day = if product == JANSSEN {
+                vaccine.complete.start_day
+            } else {
+                generic.start_day
+            };
+            let end_day = if scan_mode == ScanMode::School {
+                generic.end_day.min(SCHOOL_END_DAY)
+            } else {
+                generic.end_day
+            };
+            Interval { start_day, end_day }
+        }
+        Cycle::Booster => {
+            if is_italian {
+                generic.booster_it
+            } else {
+                generic.booster_not_it
+            }
+        }
+    };
+
+    match check_days(date, interval, validation_instant) {
+        CertificateStatus::Valid if scan_mode == ScanMode::Booster && cycle == Cycle::Complete => {
+            CertificateStatus::TestNeeded
+        }
+        status => status,
+    }
+}
+
+/// The stage of a vaccination cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cycle {
+    NotComplete,
+    Complete,
+    Booster,
+}
+
+fn cycle(vaccination: &Vaccination) -> Cycle {
+    let dose_number = vaccination.dose_number;
+    let total_doses = vaccination.total_doses;
+    let booster_dose = if vaccination.medicinal_product == JANSSEN {
+        2
+    } else {
+        3
+    };
+
+    if dose_number < total_doses {
+        Cycle::NotComplete
+    } else if dose_number > total_doses || dose_number >= booster_dose {
+        Cycle::Booster
+    } else {
+        Cycle::Complete
+    }
+}
+
+fn verify_test(
+    test: &Test,
+    settings: &Settings<'_>,
+    validation_instant: DateTime<Utc>,
+    scan_mode: ScanMode,
+) -> CertificateStatus {
+    let accepts_tests = matches!(scan_mode, ScanMode::Base | ScanMode::Entry);
+    if !accepts_tests || test.result == TEST_DETECTED {
+        return CertificateStatus::NotValid;
+    }
+    let test_data = match test.test_type.as_ref() {
+        MOLECULAR_TEST => settings.tests.molecular,
+        RAPID_TEST => settings.tests.rapid,
+        _ => return CertificateStatus::NotValid,
+    };
+    let date_of_collection = match test.parsed_date_of_collection() {
+        Ok(date_of_collection) => date_of_collection,
+        Err(_) => return CertificateStatus::NotValid,
+    };
+
+    check_hours(date_of_collection, test_data, validation_instant)
+}
+
+fn verify_recovery(
+    recovery: &Recovery,
+    settings: &Settings<'_>,
+    validation_instant: DateTime<Utc>,
+    scan_mode: ScanMode,
+) -> CertificateStatus {
+    let valid_from = match recovery.parsed_valid_from() {
+        Ok(valid_from) => valid_from,
+        Err(_) => return CertificateStatus::NotValid,
+    };
+    let mut interval = if recovery.country == ITALY && scan_mode != ScanMode::Entry {
+        settings.recovery.cert_it
+    } else {
+        settings.recovery.cert_not_it
+    };
+    if scan_mode == ScanMode::School {
+        interval.end_day = interval.end_day.min(SCHOOL_END_DAY);
+    }
+
+    match check_days(valid_from, interval, validation_instant) {
+        CertificateStatus::Valid if scan_mode == ScanMode::Booster => CertificateStatus::TestNeeded,
+        status => status,
+    }
+}
+
+/// Resolves [`ScanMode::Work`] to the mode to apply depending on the age of the holder.
+///
+/// Partial dates of birth are considered as the first day of the known period; when the date
+/// of birth is unknown the strengthened verification is applied.
+fn work_scan_mode(dgc: &Dgc, validation_instant: DateTime<Utc>) -> ScanMode {
+    let date_of_birth = match dgc.parsed_date_of_birth() {
+        Ok(PartialDate::Complete(date)) => Some(date),
+        Ok(PartialDate::YearMonth(year, month)) => NaiveDate::from_ymd_opt(year, month, 1),
+        Ok(PartialDate::Year(year)) => NaiveDate::from_ymd_opt(year, 1, 1),
+        Ok(PartialDate::Empty) | Err(_) => None,
+    };
+    let age = date_of_birth.and_then(|date| validation_instant.date_naive().years_since(date));
+
+    match age {
+        Some(age) if age < WORK_STRENGTHENED_AGE => ScanMode::Base,
+        _ => ScanMode::Strengthened,
+    }
+}
+
+fn check_days(
+    date: NaiveDate,
+    interval: Interval,
+    validation_instant: DateTime<Utc>,
+) -> CertificateStatus {
+    let today = validation_instant.date_naive();
+    if today < date + Duration::days(interval.start_day.into()) {
+        CertificateStatus::NotValidYet
+    } else if today > date + Duration::days(interval.end_day.into()) {
+        CertificateStatus::NotValid
+    } else {
+        CertificateStatus::Valid
+    }
+}
+
+fn check_hours(
+    date: DateTime<Utc>,
+    test_data: TestData,
+    validation_instant: DateTime<Utc>,
+) -> CertificateStatus {
+    if validation_instant < date + Duration::hours(test_data.start_hours.into()) {
+        CertificateStatus::NotValidYet
+    } else if validation_instant > date + Duration::hours(test_data.end_hours.into()) {
+        CertificateStatus::NotValid
+    } else {
+        CertificateStatus::Valid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn settings() -> Settings<'static> {
+        serde_json::from_str(include_str!("../tests/data/settings.json")).unwrap()
+    }
+
+    fn at(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap()
+    }
+
+    fn vaccination(mp: &str, dn: usize, sd: usize, dt: &str, co: &str) -> Dgc {
+        serde_json::from_value(serde_json::json!({
+            "ver": "1.3.0",
+            "nam": { "fnt": "ROSSI", "gnt": "MARIO" },
+            "dob": "1980-01-01",
+            "v": [{
+                "tg": "840539006",
+                "vp": "1119349007",
+                "mp": mp,
+                "ma": "ORG-100030215",
+                "dn": dn,
+                "sd": sd,
+                "dt": dt,
+                "co": co,
+                "is": "Ministero della Salute",
+                "ci": "URN:UVCI:01:IT:ABCDEF#1"
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn it_verifies_vaccinations() {
+        let settings = settings();
+        let verify = |dgc: &Dgc, instant| verify(dgc, &settings, instant, ScanMode::Base);
+
+        // Comirnaty first dose: valid from day 15 to day 42
+        let first_dose = vaccination("EU/1/20/1528", 1, 2, "2021-10-01", "IT");
+        assert_eq!(
+            verify(&first_dose, at(2021, 10, 15)),
+            CertificateStatus::NotValidYet
+        );
+        assert_eq!(
+            verify(&first_dose, at(2021, 10, 16)),
+            CertificateStatus::Valid
+        );
+        assert_eq!(
+            verify(&first_dose, at(2021, 11, 12)),
+            CertificateStatus::Valid
+        );
+        assert_eq!(
+            verify(&first_dose, at(2021, 11, 13)),
+            CertificateStatus::NotValid
+        );
+
+        // complete cycle: 180 days in Italy, 270 days abroad
+        let complete = vaccination("EU/1/20/1528", 2, 2, "2021-10-01", "IT");
+        assert_eq!(verify(&complete, at(2021, 10, 1)), CertificateStatus::Valid);
+        assert_eq!(verify(&complete, at(2022, 3, 30)), CertificateStatus::Valid);
+        assert_eq!(
+            verify(&complete, at(2022, 3, 31)),
+            CertificateStatus::NotValid
+        );
+        let complete_abroad = vaccination("EU/1/20/1528", 2, 2, "2021-10-01", "FR");
+        assert_eq!(
+            verify(&complete_abroad, at(2022, 3, 31)),
+            CertificateStatus::Valid
+        );
+
+        // Janssen is effective after 15 days
+        let janssen = vaccination("EU/1/20/1525", 1, 1, "2021-10-01", "IT");
+        assert_eq!(
+            verify(&janssen, at(2021, 10, 15)),
+            CertificateStatus::NotValidYet
+        );
+        assert_eq!(verify(&janssen, at(2021, 10, 16)), CertificateStatus::Valid);
+
+        // booster
+        let booster = vaccination("EU/1/20/1528", 3, 3, "2021-12-01", "IT");
+        assert_eq!(verify(&booster, at(2021, 12, 1)), CertificateStatus::Valid);
+    }
+
+    #[test]
+    fn it_rejects_unknown_or_not_allowed_vaccines() {
+        let settings = settings();
+        let verify = |dgc: &Dgc| verify(dgc, &settings, at(2021, 10, 10), ScanMode::Base);
+
+        assert_eq!(
+            verify(&vaccination("Unknown", 2, 2, "2021-10-01", "IT")),
+            CertificateStatus::NotValid
+        );
+        assert_eq!(
+            verify(&vaccination("Sputnik-V", 2, 2, "2021-10-01", "IT")),
+            CertificateStatus::NotValid
+        );
+        assert_eq!(
+            verify(&vaccination("Sputnik-V", 2, 2, "2021-10-01", "SM")),
+            CertificateStatus::Valid
+        );
+    }
+
+    #[test]
+    fn it_identifies_the_vaccination_cycle() {
+        let cycle_of = |mp, dn, sd| cycle(&vaccination(mp, dn, sd, "2021-10-01", "IT").vaccines[0]);
+
+        assert_eq!(cycle_of("EU/1/20/1528", 1, 2), Cycle::NotComplete);
+        assert_eq!(cycle_of("EU/1/20/1528", 2, 2), Cycle::Complete);
+        assert_eq!(cycle_of("EU/1/20/1528", 1, 1), Cycle::Complete);
+        assert_eq!(cycle_of("EU/1/20/1528", 3, 3), Cycle::Booster);
+        assert_eq!(cycle_of("EU/1/20/1528", 2, 1), Cycle::Booster);
+        assert_eq!(cycle_of("EU/1/20/1525", 1, 1), Cycle::Complete);
+        assert_eq!(cycle_of("EU/1/20/1525", 2, 2), Cycle::Booster);
+    }
+
+    #[test]
+    fn it_applies_the_scan_mode_to_vaccinations() {
+        let settings = settings();
+        let instant = at(2021, 12, 15);
+        let first_dose = vaccination("EU/1/20/1528", 1, 2, "2021-12-01", "IT");
+        let complete = vaccination("EU/1/20/1528", 2, 2, "2021-07-01", "IT");
+        let booster = vaccination("EU/1/20/1528", 3, 3, "2021-12-01", "IT");
+        let janssen_booster = vaccination("EU/1/20/1525", 2, 2, "2021-12-01", "IT");
+
+        let verify = |dgc: &Dgc, scan_mode| verify(dgc, &settings, instant, scan_mode);
+        assert_eq!(
+            verify(&first_dose, ScanMode::Strengthened),
+            CertificateStatus::NotValidYet
+        );
+        assert_eq!(
+            verify(&first_dose, ScanMode::Booster),
+            CertificateStatus::NotValid
+        );
+        assert_eq!(
+            verify(&first_dose, ScanMode::School),
+            CertificateStatus::NotValid
+        );
+        assert_eq!(
+            verify(&complete, ScanMode::Booster),
+            CertificateStatus::TestNeeded
+        );
+        assert_eq!(
+            verify(&complete, ScanMode::School),
+            CertificateStatus::NotValid
+        );
+        assert_eq!(
+            verify(&booster, ScanMode::Booster),
+            CertificateStatus::Valid
+        );
+        assert_eq!(verify(&booster, ScanMode::School), CertificateStatus::Valid);
+        assert_eq!(
+            verify(&janssen_booster, ScanMode::Booster),
+            CertificateStatus::Valid
+        );
+    }
+
+    #[test]
+    fn it_reports_revoked_certificates() {
+        let settings = settings();
+        let mut dgc = vaccination("EU/1/20/1528", 2, 2, "2021-10-01", "FR");
+        assert_eq!(
+            verify(&dgc, &settings, at(2021, 10, 10), ScanMode::Base),
+            CertificateStatus::Valid
+        );
+
+        dgc.vaccines[0].id = "URN:UVCI:01:FR:W7V2BE46QSBJ#L".into();
+        assert_eq!(
+            verify(&dgc, &settings, at(2021, 10, 10), ScanMode::Base),
+            CertificateStatus::Revoked
+        );
+    }
+
+    #[test]
+    fn it_requires_exactly_one_entry_group() {
+        let settings = settings();
+        let mut dgc = vaccination("EU/1/20/1528", 2, 2, "2021-10-01", "IT");
+        dgc.vaccines.clear();
+        assert_eq!(
+            verify(&dgc, &settings, at(2021, 10, 10), ScanMode::Base),
+            CertificateStatus::NotEuDcc
+        );
+    }
+}
@@ -1,11 +1,14 @@
 use std::borrow::Cow;
 
-use dgc_italy_core::settings::*;
+use chrono::{DateTime, Utc};
+use dgc::Dgc;
+use dgc_italy_core::{settings::*, verify, CertificateStatus, ScanMode};
+use rstest::rstest;
+
+const RAW_SETTINGS: &str = include_str!("data/settings.json");
 
 #[test]
 fn settings() {
-    const RAW_SETTINGS: &str = include_str!("data/settings.json");
-
     assert_eq!(
         serde_json::from_str::<Settings>(RAW_SETTINGS).unwrap(),
         Settings {
@@ -91,9 +94,10 @@ fn settings() {
                     }
                 }
             },
-            deny_list: DenyList(Cow::Borrowed(
-                "URN:UVCI:01:FR:W7V2BE46QSBJ#L;URN:UVCI:01:FR:T5DWTJYS4ZR8#4"
-            )),
+            deny_list: DenyList::from_iter([
+                "URN:UVCI:01:FR:W7V2BE46QSBJ#L",
+                "URN:UVCI:01:FR:T5DWTJYS4ZR8#4",
+            ]),
             min_versions: MinVersions {
                 ios: Cow::Borrowed("1.2.0"),
                 android: Cow::Borrowed("1.2.0")
@@ -148,3 +152,155 @@ fn settings() {
         }
     );
 }
+
+fn it_fixture(name: &str) -> Dgc {
+    let path = format!("../dgc/tests/data/IT/2DCode/raw/{}.json", name);
+    let raw = std::fs::read_to_string(path).unwrap();
+    let mut fixture: serde_json::Value = serde_json::from_str(&raw).unwrap();
+    serde_json::from_value(fixture["JSON"].take()).unwrap()
+}
+
+#[rstest]
+// vaccination: complete primary cycle with Comirnaty on 2021-04-10
+#[case("1", "2021-05-21T12:00:00Z", ScanMode::Base, CertificateStatus::Valid)]
+#[case(
+    "1",
+    "2021-05-21T12:00:00Z",
+    ScanMode::Strengthened,
+    CertificateStatus::Valid
+)]
+#[case(
+    "1",
+    "2021-05-21T12:00:00Z",
+    ScanMode::Booster,
+    CertificateStatus::TestNeeded
+)]
+#[case("1", "2021-05-21T12:00:00Z", ScanMode::Work, CertificateStatus::Valid)]
+#[case(
+    "1",
+    "2021-05-21T12:00:00Z",
+    ScanMode::School,
+    CertificateStatus::Valid
+)]
+#[case("1", "2021-05-21T12:00:00Z", ScanMode::Entry, CertificateStatus::Valid)]
+#[case("1", "2021-09-01T12:00:00Z", ScanMode::Base, CertificateStatus::Valid)]
+#[case(
+    "1",
+    "2021-09-01T12:00:00Z",
+    ScanMode::School,
+    CertificateStatus::NotValid
+)]
+#[case(
+    "1",
+    "2021-10-08T12:00:00Z",
+    ScanMode::Base,
+    CertificateStatus::NotValid
+)]
+#[case("1", "2021-10-08T12:00:00Z", ScanMode::Entry, CertificateStatus::Valid)]
+// recovery: valid from 2021-05-04
+#[case("2", "2021-05-21T12:00:00Z", ScanMode::Base, CertificateStatus::Valid)]
+#[case(
+    "2",
+    "2021-05-21T12:00:00Z",
+    ScanMode::Strengthened,
+    CertificateStatus::Valid
+)]
+#[case(
+    "2",
+    "2021-05-21T12:00:00Z",
+    ScanMode::Booster,
+    CertificateStatus::TestNeeded
+)]
+#[case("2", "2021-05-21T12:00:00Z", ScanMode::Work, CertificateStatus::Valid)]
+#[case(
+    "2",
+    "2021-05-21T12:00:00Z",
+    ScanMode::School,
+    CertificateStatus::Valid
+)]
+#[case("2", "2021-05-21T12:00:00Z", ScanMode::Entry, CertificateStatus::Valid)]
+#[case("2", "2021-10-01T12:00:00Z", ScanMode::Base, CertificateStatus::Valid)]
+#[case(
+    "2",
+    "2021-10-01T12:00:00Z",
+    ScanMode::School,
+    CertificateStatus::NotValid
+)]
+#[case(
+    "2",
+    "2021-05-03T12:00:00Z",
+    ScanMode::Base,
+    CertificateStatus::NotValidYet
+)]
+// molecular tests collected on 2021-05-03 and on 2021-05-10
+#[case("3", "2021-05-04T10:00:00Z", ScanMode::Base, CertificateStatus::Valid)]
+#[case(
+    "3",
+    "2021-05-04T10:00:00Z",
+    ScanMode::Strengthened,
+    CertificateStatus::NotValid
+)]
+#[case(
+    "3",
+    "2021-05-04T10:00:00Z",
+    ScanMode::Booster,
+    CertificateStatus::NotValid
+)]
+#[case("3", "2021-05-04T10:00:00Z", ScanMode::Work, CertificateStatus::Valid)]
+#[case(
+    "3",
+    "2021-05-04T10:00:00Z",
+    ScanMode::School,
+    CertificateStatus::NotValid
+)]
+#[case("3", "2021-05-04T10:00:00Z", ScanMode::Entry, CertificateStatus::Valid)]
+#[case(
+    "3",
+    "2021-05-21T12:00:00Z",
+    ScanMode::Base,
+    CertificateStatus::NotValid
+)]
+#[case(
+    "4",
+    "2021-05-10T10:00:00Z",
+    ScanMode::Base,
+    CertificateStatus::NotValidYet
+)]
+#[case("4", "2021-05-13T10:00:00Z", ScanMode::Base, CertificateStatus::Valid)]
+#[case(
+    "4",
+    "2021-05-13T11:00:00Z",
+    ScanMode::Base,
+    CertificateStatus::NotValid
+)]
+fn scan_modes(
+    #[case] fixture: &str,
+    #[case] validation_instant: &str,
+    #[case] scan_mode: ScanMode,
+    #[case] expected: CertificateStatus,
+) {
+    let settings: Settings = serde_json::from_str(RAW_SETTINGS).unwrap();
+    let dgc = it_fixture(fixture);
+    let validation_instant: DateTime<Utc> = validation_instant.parse().unwrap();
+
+    assert_eq!(
+        verify(&dgc, &settings, validation_instant, scan_mode),
+        expected
+    );
+}
+
+#[rstest]
+#[case("1", CertificateStatus::Valid)]
+#[case("2", CertificateStatus::Valid)]
+#[case("3", CertificateStatus::NotValid)]
+fn work_scan_mode_over_50(#[case] fixture: &str, #[case] expected: CertificateStatus) {
+    let settings: Settings = serde_json::from_str(RAW_SETTINGS).unwrap();
+    let mut dgc = it_fixture(fixture);
+    dgc.date_of_birth = "1960-06".into();
+    let validation_instant: DateTime<Utc> = "2021-05-04T10:00:00Z".parse().unwrap();
+
+    assert_eq!(
+        verify(&dgc, &settings, validation_instant, ScanMode::Work),
+        expected
+    );
+}
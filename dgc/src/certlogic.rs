@@ -0,0 +1,976 @@
+use crate::{
+    dates::{parse_date, parse_datetime, parse_partial_date},
+    DateParseError, Dgc, PartialDate,
+};
+use chrono::{DateTime, Duration, Months, NaiveDate, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    fmt,
+};
+use thiserror::Error;
+
+/// Represents all the possible failures that can occur when evaluating a
+/// [CertLogic](https://github.com/ehn-dcc-development/dgc-business-rules/blob/main/certlogic/specification/README.md)
+/// expression.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CertLogicError {
+    /// The expression is not a literal, an array or an operation
+    #[error("Invalid CertLogic expression: {0}")]
+    InvalidExpression(String),
+    /// The operation is not part of CertLogic
+    #[error("Unrecognised operation '{0}'")]
+    UnknownOperation(String),
+    /// The operation was given the wrong number or type of operands
+    #[error("Invalid operands for operation '{0}': {1}")]
+    InvalidOperands(&'static str, String),
+    /// A date or date time operand could not be parsed
+    #[error("Invalid date operand: {0}")]
+    InvalidDate(#[from] DateParseError),
+}
+
+/// Evaluates a CertLogic expression against the given data.
+///
+/// ## Example
+///
+/// ```
+/// use serde_json::json;
+///
+/// let logic = json!({ "in": [{ "var": "payload.v.0.mp" }, ["EU/1/20/1528", "EU/1/20/1507"]] });
+/// let data = json!({ "payload": { "v": [{ "mp": "EU/1/20/1528" }] } });
+///
+/// assert_eq!(dgc::evaluate_certlogic(&logic, &data), Ok(json!(true)));
+/// ```
+pub fn evaluate_certlogic(expression: &Value, data: &Value) -> Result<Value, CertLogicError> {
+    match expression {
+        Value::String(_) | Value::Bool(_) => Ok(expression.clone()),
+        Value::Number(number) if number.is_i64() || number.is_u64() => Ok(expression.clone()),
+        Value::Number(number) => Err(CertLogicError::InvalidExpression(format!(
+            "{} is not an integer",
+            number
+        ))),
+        Value::Null => Err(CertLogicError::InvalidExpression("null".to_string())),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| evaluate_certlogic(item, data))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Value::Object(operation) => {
+            let (operator, operands) = match operation.iter().next() {
+                Some(entry) if operation.len() == 1 => entry,
+                _ => {
+                    return Err(CertLogicError::InvalidExpression(format!(
+                        "an operation must have exactly one key, found {}",
+                        expression
+                    )))
+                }
+            };
+            if operator == "var" {
+                return evaluate_var(operands, data);
+            }
+            let operands = match operands {
+                Value::Array(operands) => operands.as_slice(),
+                _ => {
+                    return Err(CertLogicError::InvalidExpression(format!(
+                        "the operands of '{}' must be an array",
+                        operator
+                    )))
+                }
+            };
+            evaluate_operation(operator, operands, data)
+        }
+    }
+}
+
+/// Checks if a value is considered truthy by CertLogic
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(value) => !value.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn evaluate_var(path: &Value, data: &Value) -> Result<Value, CertLogicError> {
+    let path = path.as_str().ok_or_else(|| {
+        CertLogicError::InvalidOperands("var", format!("expected a string, found {}", path))
+    })?;
+    if path.is_empty() {
+        return Ok(data.clone());
+    }
+    let value = path.split('.').try_fold(data, |current, fragment| {
+        match (current, fragment.parse::<usize>()) {
+            (Value::Array(items), Ok(index)) => items.get(index),
+            (Value::Object(map), _) => map.get(fragment),
+            _ => None,
+        }
+    });
+    Ok(value.cloned().unwrap_or(Value::Null))
+}
+
+fn evaluate_operation(
+    operator: &str,
+    operands: &[Value],
+    data: &Value,
+) -> Result<Value, CertLogicError> {
+    match operator {
+        "if" => {
+            let [guard, then, otherwise] = exact_operands::<3>("if", operands)?;
+            if is_truthy(&evaluate_certlogic(guard, data)?) {
+                evaluate_certlogic(then, data)
+            } else {
+                evaluate_certlogic(otherwise, data)
+            }
+        }
+        "===" => {
+            let [left, right] = exact_operands::<2>("===", operands)?;
+            Ok(Value::Bool(
+                evaluate_certlogic(left, data)? == evaluate_certlogic(right, data)?,
+            ))
+        }
+        "and" => {
+            if operands.len() < 2 {
+                return Err(CertLogicError::InvalidOperands(
+                    "and",
+                    "expected at least 2 operands".to_string(),
+                ));
+            }
+            let mut result = Value::Null;
+            for operand in operands {
+                result = evaluate_certlogic(operand, data)?;
+                if !is_truthy(&result) {
+                    break;
+                }
+            }
+            Ok(result)
+        }
+        "!" => {
+            let [operand] = exact_operands::<1>("!", operands)?;
+            Ok(Value::Bool(!is_truthy(&evaluate_certlogic(operand, data)?)))
+        }
+        "<" => compare_integers("<", operands, data, |a, b| a < b),
+        ">" => compare_integers(">", operands, data, |a, b| a > b),
+        "<=" => compare_integers("<=", operands, data, |a, b| a <= b),
+        ">=" => compare_integers(">=", operands, data, |a, b| a >= b),
+        "+" => {
+            let [left, right] = exact_operands::<2>("+", operands)?;
+            let left = integer_operand("+", &evaluate_certlogic(left, data)?)?;
+            let right = integer_operand("+", &evaluate_certlogic(right, data)?)?;
+            let sum = left.checked_add(right).ok_or_else(|| {
+                CertLogicError::InvalidOperands("+", format!("{} + {} overflows", left, right))
+            })?;
+            Ok(json!(sum))
+        }
+        "in" => {
+            let [item, items] = exact_operands::<2>("in", operands)?;
+            let item = evaluate_certlogic(item, data)?;
+            match evaluate_certlogic(items, data)? {
+                Value::Array(items) => Ok(Value::Bool(items.contains(&item))),
+                items => Err(CertLogicError::InvalidOperands(
+                    "in",
+                    format!("expected an array, found {}", items),
+                )),
+            }
+        }
+        "plusTime" => {
+            let [datetime, amount, unit] = exact_operands::<3>("plusTime", operands)?;
+            let datetime = datetime_operand("plusTime", &evaluate_certlogic(datetime, data)?)?;
+            let amount = integer_operand("plusTime", &evaluate_certlogic(amount, data)?)?;
+            let unit = evaluate_certlogic(unit, data)?;
+            let result = plus_time(datetime, amount, unit.as_str().unwrap_or_default())
+                .ok_or_else(|| {
+                    CertLogicError::InvalidOperands(
+                        "plusTime",
+                        format!("cannot add {} {} to {}", amount, unit, datetime),
+                    )
+                })?;
+            Ok(Value::String(
+                result.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            ))
+        }
+        "dccDateOfBirth" => {
+            let [date] = exact_operands::<1>("dccDateOfBirth", operands)?;
+            let date = match evaluate_certlogic(date, data)? {
+                Value::String(date) => date,
+                date => {
+                    return Err(CertLogicError::InvalidOperands(
+                        "dccDateOfBirth",
+                        format!("expected a string, found {}", date),
+                    ))
+                }
+            };
+            Ok(Value::String(
+                date_of_birth(&date)?.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            ))
+        }
+        "after" => compare_datetimes("after", operands, data, |a, b| a > b),
+        "before" => compare_datetimes("before", operands, data, |a, b| a < b),
+        "not-after" => compare_datetimes("not-after", operands, data, |a, b| a <= b),
+        "not-before" => compare_datetimes("not-before", operands, data, |a, b| a >= b),
+        "reduce" => {
+            let [items, lambda, initial] = exact_operands::<3>("reduce", operands)?;
+            let initial = evaluate_certlogic(initial, data)?;
+            match evaluate_certlogic(items, data)? {
+                Value::Null => Ok(initial),
+                Value::Array(items) => {
+                    items.into_iter().try_fold(initial, |accumulator, current| {
+                        let lambda_data = json!({
+                            "accumulator": accumulator,
+                            "current": current,
+                            "data": data,
+                        });
+                        evaluate_certlogic(lambda, &lambda_data)
+                    })
+                }
+                items => Err(CertLogicError::InvalidOperands(
+                    "reduce",
+                    format!("expected an array or null, found {}", items),
+                )),
+            }
+        }
+        "extractFromUVCI" => {
+            let [uvci, index] = exact_operands::<2>("extractFromUVCI", operands)?;
+            let index = integer_operand("extractFromUVCI", &evaluate_certlogic(index, data)?)?;
+            match evaluate_certlogic(uvci, data)? {
+                Value::Null => Ok(Value::Null),
+                Value::String(uvci) => Ok(extract_from_uvci(&uvci, index)
+                    .map_or(Value::Null, |fragment| Value::String(fragment.to_string()))),
+                uvci => Err(CertLogicError::InvalidOperands(
+                    "extractFromUVCI",
+                    format!("expected a string or null, found {}", uvci),
+                )),
+            }
+        }
+        _ => Err(CertLogicError::UnknownOperation(operator.to_string())),
+    }
+}
+
+fn exact_operands<'a, const N: usize>(
+    operator: &'static str,
+    operands: &'a [Value],
+) -> Result<&'a [Value; N], CertLogicError> {
+    operands.try_into().map_err(|_| {
+        CertLogicError::InvalidOperands(
+            operator,
+            format!("expected {} operands, found {}", N, operands.len()),
+        )
+    })
+}
+
+fn integer_operand(operator: &'static str, value: &Value) -> Result<i64, CertLogicError> {
+    value.as_i64().ok_or_else(|| {
+        CertLogicError::InvalidOperands(operator, format!("expected an integer, found {}", value))
+    })
+}
+
+/// Parses a date (which is considered as midnight UTC) or a date time
+fn datetime_operand(
+    operator: &'static str,
+    value: &Value,
+) -> Result<DateTime<Utc>, CertLogicError> {
+    let value = value.as_str().ok_or_else(|| {
+        CertLogicError::InvalidOperands(operator, format!("expected a date, found {}", value))
+    })?;
+    if value.contains('T') {
+        Ok(parse_datetime(value)?)
+    } else {
+        Ok(parse_date(value)?.and_hms_opt(0, 0, 0).unwrap().and_utc())
+    }
+}
+
+/// Converts a date of birth, which can be partial, to midnight UTC of the latest day it can
+/// refer to (e.g. `1977` is the 31st of December 1977)
+fn date_of_birth(value: &str) -> Result<DateTime<Utc>, CertLogicError> {
+    let date = match parse_partial_date(value)? {
+        PartialDate::Empty => {
+            return Err(CertLogicError::InvalidOperands(
+                "dccDateOfBirth",
+                "the date of birth is empty".to_string(),
+            ))
+        }
+        PartialDate::Year(year) => NaiveDate::from_ymd_opt(year, 12, 31),
+        PartialDate::YearMonth(year, month) => NaiveDate::from_ymd_opt(year, month, 1)
+            .and_then(|first| first.checked_add_months(Months::new(1)))
+            .and_then(|next| next.pred_opt()),
+        PartialDate::Complete(date) => Some(date),
+    };
+    date.and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc())
+        .ok_or_else(|| DateParseError::InvalidDate(value.to_string()).into())
+}
+
+fn plus_time(datetime: DateTime<Utc>, amount: i64, unit: &str) -> Option<DateTime<Utc>> {
+    let add_months = |months: i64| {
+        let abs_months = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+        if months < 0 {
+            datetime.checked_sub_months(abs_months)
+        } else {
+            datetime.checked_add_months(abs_months)
+        }
+    };
+    match unit {
+        "year" => add_months(amount.checked_mul(12)?),
+        "month" => add_months(amount),
+        "day" => datetime.checked_add_signed(Duration::try_days(amount)?),
+        "hour" => datetime.checked_add_signed(Duration::try_hours(amount)?),
+        _ => None,
+    }
+}
+
+/// Applies a comparison to 2 or 3 operands. With 3 operands the comparison
+/// is chained (e.g. `a < b < c`)
+fn compare_chain<T, F>(operands: &[T], compare: F) -> bool
+where
+    F: Fn(&T, &T) -> bool,
+{
+    operands.windows(2).all(|pair| compare(&pair[0], &pair[1]))
+}
+
+fn compare_integers<F>(
+    operator: &'static str,
+    operands: &[Value],
+    data: &Value,
+    compare: F,
+) -> Result<Value, CertLogicError>
+where
+    F: Fn(&i64, &i64) -> bool,
+{
+    check_comparison_operands(operator, operands)?;
+    let operands = operands
+        .iter()
+        .map(|operand| integer_operand(operator, &evaluate_certlogic(operand, data)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::Bool(compare_chain(&operands, compare)))
+}
+
+fn compare_datetimes<F>(
+    operator: &'static str,
+    operands: &[Value],
+    data: &Value,
+    compare: F,
+) -> Result<Value, CertLogicError>
+where
+    F: Fn(&DateTime<Utc>, &DateTime<Utc>) -> bool,
+{
+    check_comparison_operands(operator, operands)?;
+    let operands = operands
+        .iter()
+        .map(|operand| datetime_operand(operator, &evaluate_certlogic(operand, data)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::Bool(compare_chain(&operands, compare)))
+}
+
+fn check_comparison_operands(
+    operator: &'static str,
+    operands: &[Value],
+) -> Result<(), CertLogicError> {
+    if operands.len() == 2 || operands.len() == 3 {
+        Ok(())
+    } else {
+        Err(CertLogicError::InvalidOperands(
+            operator,
+            format!("expected 2 or 3 operands, found {}", operands.len()),
+        ))
+    }
+}
+
+/// Extracts a fragment from a UVCI, splitting it on `/`, `#` and `:` after removing
+/// the optional `URN:UVCI:` prefix
+fn extract_from_uvci(uvci: &str, index: i64) -> Option<&str> {
+    let uvci = uvci.strip_prefix("URN:UVCI:").unwrap_or(uvci);
+    let index = usize::try_from(index).ok()?;
+    uvci.split(['/', '#', ':']).nth(index)
+}
+
+/// The type of a business rule.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleType {
+    /// A rule defined by the country of arrival to accept a certificate
+    Acceptance,
+    /// A rule defined by the issuing country to invalidate its own certificates
+    Invalidation,
+}
+
+/// The type of certificates a business rule applies to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleCertificateType {
+    /// Applies to all the certificates
+    General,
+    /// Applies to vaccination certificates
+    Vaccination,
+    /// Applies to test certificates
+    Test,
+    /// Applies to recovery certificates
+    Recovery,
+}
+
+impl RuleCertificateType {
+    /// Checks if a rule with this certificate type should be applied to the given certificate
+    pub fn applies_to(&self, dgc: &Dgc) -> bool {
+        match self {
+            RuleCertificateType::General => true,
+            RuleCertificateType::Vaccination => !dgc.vaccines.is_empty(),
+            RuleCertificateType::Test => !dgc.tests.is_empty(),
+            RuleCertificateType::Recovery => !dgc.recoveries.is_empty(),
+        }
+    }
+}
+
+/// The description of a business rule in a given language.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RuleDescription {
+    /// The language of the description
+    pub lang: String,
+    /// The description of the rule
+    pub desc: String,
+}
+
+/// A business rule as distributed by the EU DCC gateway.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct BusinessRule {
+    /// The unique identifier of the rule (e.g. `VR-DE-0001`)
+    pub identifier: String,
+    /// The type of the rule
+    #[serde(rename = "Type")]
+    pub rule_type: RuleType,
+    /// The country that defined the rule (ISO 3166 alpha-2)
+    pub country: String,
+    /// An optional region of the country where the rule applies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// The version of the rule (semver)
+    pub version: String,
+    /// The version of the rule schema
+    #[serde(default)]
+    pub schema_version: String,
+    /// The engine to be used to evaluate the rule (e.g. `CERTLOGIC`)
+    #[serde(default)]
+    pub engine: String,
+    /// The version of the engine
+    #[serde(default)]
+    pub engine_version: String,
+    /// The type of certificates the rule applies to
+    pub certificate_type: RuleCertificateType,
+    /// The description of the rule in one or more languages
+    #[serde(default)]
+    pub description: Vec<RuleDescription>,
+    /// The moment in time from which the rule is valid
+    pub valid_from: String,
+    /// The moment in time until which the rule is valid
+    pub valid_to: String,
+    /// The fields of the certificate used by the rule
+    #[serde(default)]
+    pub affected_fields: Vec<String>,
+    /// The CertLogic expression of the rule
+    pub logic: Value,
+}
+
+/// The outcome of the evaluation of a business rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleOutcome {
+    /// The logic of the rule evaluated to a truthy value
+    Passed,
+    /// The logic of the rule evaluated to a falsy value
+    Failed,
+    /// The rule could not be evaluated. Contains the reason
+    Open(String),
+}
+
+impl fmt::Display for RuleOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleOutcome::Passed => write!(f, "Passed"),
+            RuleOutcome::Failed => write!(f, "Failed"),
+            RuleOutcome::Open(reason) => write!(f, "Open: {}", reason),
+        }
+    }
+}
+
+/// The result of the evaluation of a business rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleResult {
+    /// The identifier of the evaluated rule
+    pub identifier: String,
+    /// The version of the evaluated rule
+    pub version: String,
+    /// The outcome of the evaluation
+    pub outcome: RuleOutcome,
+}
+
+/// The parameters that are not part of the certificate but that are needed to evaluate
+/// business rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalParameters {
+    /// The moment in time at which the certificate is verified
+    pub validation_clock: DateTime<Utc>,
+    /// The value sets, indexed by their id (e.g. `covid-19-lab-test-type`)
+    pub value_sets: HashMap<String, Vec<String>>,
+    /// The country of arrival (ISO 3166 alpha-2)
+    pub country_code: String,
+    /// The country that issued the certificate (ISO 3166 alpha-2)
+    pub issuer_country_code: String,
+    /// The expiration time of the certificate (`exp` claim)
+    pub exp: Option<DateTime<Utc>>,
+    /// The issue time of the certificate (`iat` claim)
+    pub iat: Option<DateTime<Utc>>,
+}
+
+impl ExternalParameters {
+    /// Creates the parameters to verify a certificate issued by `issuer_country_code` when
+    /// arriving in `country_code`, without value sets
+    pub fn new(
+        validation_clock: DateTime<Utc>,
+        country_code: impl Into<String>,
+        issuer_country_code: impl Into<String>,
+    ) -> Self {
+        ExternalParameters {
+            validation_clock,
+            value_sets: HashMap::new(),
+            country_code: country_code.into(),
+            issuer_country_code: issuer_country_code.into(),
+            exp: None,
+            iat: None,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let format = |datetime: &DateTime<Utc>| datetime.to_rfc3339_opts(SecondsFormat::Secs, true);
+        json!({
+            "validationClock": format(&self.validation_clock),
+            "valueSets": self.value_sets,
+            "countryCode": self.country_code,
+            "issuerCountryCode": self.issuer_country_code,
+            "exp": self.exp.as_ref().map(format),
+            "iat": self.iat.as_ref().map(format),
+        })
+    }
+}
+
+impl BusinessRule {
+    /// Checks if the rule has to be applied to the given certificate with the given parameters.
+    ///
+    /// Acceptance rules are applied when they are defined by the country of arrival,
+    /// invalidation rules when they are defined by the issuing country.
+    /// In both cases the rule must be valid at the validation clock and it must target
+    /// the type of the certificate.
+    pub fn applies_to(&self, dgc: &Dgc, external: &ExternalParameters) -> bool {
+        let country = match self.rule_type {
+            RuleType::Acceptance => &external.country_code,
+            RuleType::Invalidation => &external.issuer_country_code,
+        };
+        let is_valid_at = |clock: DateTime<Utc>| {
+            matches!(
+                (parse_datetime(&self.valid_from), parse_datetime(&self.valid_to)),
+                (Ok(valid_from), Ok(valid_to)) if valid_from <= clock && clock < valid_to
+            )
+        };
+
+        self.country.eq_ignore_ascii_case(country)
+            && self.certificate_type.applies_to(dgc)
+            && is_valid_at(external.validation_clock)
+    }
+
+    /// Evaluates the logic of the rule against the given certificate.
+    pub fn evaluate(&self, dgc: &Dgc, external: &ExternalParameters) -> RuleResult {
+        let outcome = match serde_json::to_value(dgc) {
+            Err(e) => RuleOutcome::Open(e.to_string()),
+            Ok(payload) => {
+                let data = json!({ "payload": payload, "external": external.to_json() });
+                match evaluate_certlogic(&self.logic, &data) {
+                    Ok(value) if is_truthy(&value) => RuleOutcome::Passed,
+                    Ok(_) => RuleOutcome::Failed,
+                    Err(e) => RuleOutcome::Open(e.to_string()),
+                }
+            }
+        };
+        RuleResult {
+            identifier: self.identifier.clone(),
+            version: self.version.clone(),
+            outcome,
+        }
+    }
+}
+
+/// Compares two semver versions (`major.minor.patch`), ignoring anything that is not a number
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parse(a).cmp(&parse(b))
+}
+
+/// Evaluates all the business rules that apply to the given certificate.
+///
+/// When more versions of the same rule apply, only the latest one is evaluated.
+pub fn evaluate_rules(
+    rules: &[BusinessRule],
+    dgc: &Dgc,
+    external: &ExternalParameters,
+) -> Vec<RuleResult> {
+    let mut latest: Vec<&BusinessRule> = vec![];
+    for rule in rules.iter().filter(|rule| rule.applies_to(dgc, external)) {
+        match latest
+            .iter_mut()
+            .find(|other| other.identifier == rule.identifier)
+        {
+            Some(other) => {
+                if compare_versions(&rule.version, &other.version).is_gt() {
+                    *other = rule;
+                }
+            }
+            None => latest.push(rule),
+        }
+    }
+    latest
+        .into_iter()
+        .map(|rule| rule.evaluate(dgc, external))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn eval(logic: Value, data: Value) -> Result<Value, CertLogicError> {
+        evaluate_certlogic(&logic, &data)
+    }
+
+    #[test]
+    fn it_evaluates_literals_and_vars() {
+        let data = json!({ "a": { "b": [10, { "c": "x" }] } });
+        assert_eq!(eval(json!("text"), json!({})), Ok(json!("text")));
+        assert_eq!(eval(json!([1, true]), json!({})), Ok(json!([1, true])));
+        assert_eq!(eval(json!({ "var": "a.b.0" }), data.clone()), Ok(json!(10)));
+        assert_eq!(
+            eval(json!({ "var": "a.b.1.c" }), data.clone()),
+            Ok(json!("x"))
+        );
+        assert_eq!(
+            eval(json!({ "var": "a.x.y" }), data.clone()),
+            Ok(Value::Null)
+        );
+        assert_eq!(eval(json!({ "var": "" }), data.clone()), Ok(data));
+        assert!(eval(json!(null), json!({})).is_err());
+        assert!(eval(json!(1.5), json!({})).is_err());
+        assert!(eval(json!({ "var": "a", "if": [] }), json!({})).is_err());
+    }
+
+    #[test]
+    fn it_evaluates_boolean_operations() {
+        assert_eq!(
+            eval(json!({ "if": [1, "yes", "no"] }), json!({})),
+            Ok(json!("yes"))
+        );
+        assert_eq!(
+            eval(json!({ "if": [[], "yes", "no"] }), json!({})),
+            Ok(json!("no"))
+        );
+        assert_eq!(
+            eval(json!({ "===": ["a", "a"] }), json!({})),
+            Ok(json!(true))
+        );
+        assert_eq!(
+            eval(json!({ "===": [1, "1"] }), json!({})),
+            Ok(json!(false))
+        );
+        assert_eq!(
+            eval(json!({ "and": [1, "a", true] }), json!({})),
+            Ok(json!(true))
+        );
+        assert_eq!(
+            eval(json!({ "and": [1, "", true] }), json!({})),
+            Ok(json!(""))
+        );
+        // short-circuits before evaluating invalid operands
+        assert_eq!(
+            eval(json!({ "and": [false, { "unknown": [] }] }), json!({})),
+            Ok(json!(false))
+        );
+        assert_eq!(
+            eval(json!({ "!": [{ "var": "x" }] }), json!({})),
+            Ok(json!(true))
+        );
+        assert!(eval(json!({ "and": [true] }), json!({})).is_err());
+        assert_eq!(
+            eval(json!({ "unknown": [] }), json!({})),
+            Err(CertLogicError::UnknownOperation("unknown".to_string()))
+        );
+    }
+
+    #[test]
+    fn it_evaluates_integer_operations() {
+        assert_eq!(eval(json!({ "<": [1, 2] }), json!({})), Ok(json!(true)));
+        assert_eq!(eval(json!({ "<": [1, 2, 2] }), json!({})), Ok(json!(false)));
+        assert_eq!(eval(json!({ "<=": [1, 2, 2] }), json!({})), Ok(json!(true)));
+        assert_eq!(eval(json!({ ">": [3, 2] }), json!({})), Ok(json!(true)));
+        assert_eq!(eval(json!({ ">=": [2, 3] }), json!({})), Ok(json!(false)));
+        assert_eq!(eval(json!({ "+": [2, 3] }), json!({})), Ok(json!(5)));
+        assert!(matches!(
+            eval(json!({ "+": [i64::MAX, 1] }), json!({})),
+            Err(CertLogicError::InvalidOperands("+", _))
+        ));
+        assert!(eval(json!({ "<": [1, "2"] }), json!({})).is_err());
+        assert!(eval(json!({ "<": [1] }), json!({})).is_err());
+    }
+
+    #[test]
+    fn it_evaluates_in_and_reduce() {
+        assert_eq!(
+            eval(json!({ "in": ["a", ["a", "b"]] }), json!({})),
+            Ok(json!(true))
+        );
+        assert_eq!(
+            eval(json!({ "in": ["c", ["a", "b"]] }), json!({})),
+            Ok(json!(false))
+        );
+        assert!(eval(json!({ "in": ["a", "a"] }), json!({})).is_err());
+
+        let sum = json!({
+            "reduce": [
+                { "var": "items" },
+                { "+": [{ "var": "accumulator" }, { "var": "current.n" }] },
+                0
+            ]
+        });
+        assert_eq!(
+            eval(sum.clone(), json!({ "items": [{ "n": 1 }, { "n": 2 }] })),
+            Ok(json!(3))
+        );
+        assert_eq!(eval(sum, json!({})), Ok(json!(0)));
+    }
+
+    #[test]
+    fn it_evaluates_date_operations() {
+        let plus = |date: &str, amount: i64, unit: &str| {
+            eval(json!({ "plusTime": [date, amount, unit] }), json!({})).unwrap()
+        };
+        assert_eq!(plus("2021-06-01", 1, "day"), json!("2021-06-02T00:00:00Z"));
+        assert_eq!(
+            plus("2021-06-01T10:00:00+02:00", -3, "hour"),
+            json!("2021-06-01T05:00:00Z")
+        );
+        assert_eq!(
+            plus("2021-06-01", 2, "month"),
+            json!("2021-08-01T00:00:00Z")
+        );
+        assert_eq!(
+            plus("2021-06-01", -1, "year"),
+            json!("2020-06-01T00:00:00Z")
+        );
+        assert!(eval(json!({ "plusTime": ["2021-06-01", 1, "week"] }), json!({})).is_err());
+
+        let date_of_birth = |dob: &str| eval(json!({ "dccDateOfBirth": [dob] }), json!({}));
+        assert_eq!(
+            date_of_birth("1977-06-16"),
+            Ok(json!("1977-06-16T00:00:00Z"))
+        );
+        assert_eq!(date_of_birth("1980-02"), Ok(json!("1980-02-29T00:00:00Z")));
+        assert_eq!(date_of_birth("1977"), Ok(json!("1977-12-31T00:00:00Z")));
+        assert!(date_of_birth("").is_err());
+        assert!(date_of_birth("1977-13").is_err());
+        assert!(eval(json!({ "dccDateOfBirth": [1977] }), json!({})).is_err());
+
+        let data = json!({ "dt": "2021-06-01", "clock": "2021-06-15T00:00:00Z" });
+        let after_14_days = json!({
+            "not-before": [
+                { "plusTime": [{ "var": "clock" }, 0, "day"] },
+                { "plusTime": [{ "var": "dt" }, 14, "day"] }
+            ]
+        });
+        assert_eq!(eval(after_14_days, data.clone()), Ok(json!(true)));
+        assert_eq!(
+            eval(
+                json!({ "after": ["2021-06-01", "2021-06-02", "2021-06-03"] }),
+                json!({})
+            ),
+            Ok(json!(false))
+        );
+        assert_eq!(
+            eval(
+                json!({ "before": ["2021-06-01", "2021-06-02", "2021-06-03"] }),
+                json!({})
+            ),
+            Ok(json!(true))
+        );
+        assert_eq!(
+            eval(
+                json!({ "not-after": ["2021-06-02", "2021-06-02"] }),
+                json!({})
+            ),
+            Ok(json!(true))
+        );
+        assert!(matches!(
+            eval(json!({ "before": ["not a date", "2021-06-02"] }), json!({})),
+            Err(CertLogicError::InvalidDate(_))
+        ));
+    }
+
+    #[test]
+    fn it_extracts_fragments_from_uvci() {
+        let extract = |uvci: Value, index: i64| {
+            eval(json!({ "extractFromUVCI": [uvci, index] }), json!({})).unwrap()
+        };
+        let uvci = json!("URN:UVCI:01:NL:187/37512422923");
+        assert_eq!(extract(uvci.clone(), 0), json!("01"));
+        assert_eq!(extract(uvci.clone(), 1), json!("NL"));
+        assert_eq!(extract(uvci.clone(), 2), json!("187"));
+        assert_eq!(extract(uvci.clone(), 3), json!("37512422923"));
+        assert_eq!(extract(uvci, 4), Value::Null);
+        assert_eq!(extract(json!("01:IT:ABC#1"), 3), json!("1"));
+        assert_eq!(extract(json!({ "var": "missing" }), 0), Value::Null);
+    }
+
+    fn vaccination_rule() -> BusinessRule {
+        serde_json::from_value(json!({
+            "Identifier": "VR-EU-0002",
+            "Type": "Acceptance",
+            "Country": "IT",
+            "Version": "1.0.0",
+            "SchemaVersion": "1.0.0",
+            "Engine": "CERTLOGIC",
+            "EngineVersion": "0.7.5",
+            "CertificateType": "Vaccination",
+            "Description": [{ "lang": "en", "desc": "At least 14 days after the last dose" }],
+            "ValidFrom": "2021-07-01T00:00:00Z",
+            "ValidTo": "2030-06-01T00:00:00Z",
+            "AffectedFields": ["v.0", "v.0.dt"],
+            "Logic": {
+                "if": [
+                    { "var": "payload.v.0" },
+                    {
+                        "not-before": [
+                            { "plusTime": [{ "var": "external.validationClock" }, 0, "day"] },
+                            { "plusTime": [{ "var": "payload.v.0.dt" }, 14, "day"] }
+                        ]
+                    },
+                    true
+                ]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn vaccinated_on(date: &str) -> Dgc {
+        serde_json::from_value(json!({
+            "ver": "1.3.0",
+            "nam": { "fnt": "ROSSI", "gnt": "MARIO" },
+            "dob": "1980-01-01",
+            "v": [{
+                "tg": "840539006",
+                "vp": "1119349007",
+                "mp": "EU/1/20/1528",
+                "ma": "ORG-100030215",
+                "dn": 2,
+                "sd": 2,
+                "dt": date,
+                "co": "IT",
+                "is": "Ministero della Salute",
+                "ci": "URN:UVCI:01:IT:ABCDEF#1"
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn it_evaluates_business_rules() {
+        let rule = vaccination_rule();
+        let external = ExternalParameters::new(
+            Utc.with_ymd_and_hms(2021, 8, 1, 0, 0, 0).unwrap(),
+            "IT",
+            "IT",
+        );
+
+        assert_eq!(
+            rule.evaluate(&vaccinated_on("2021-07-01"), &external)
+                .outcome,
+            RuleOutcome::Passed
+        );
+        assert_eq!(
+            rule.evaluate(&vaccinated_on("2021-07-25"), &external)
+                .outcome,
+            RuleOutcome::Failed
+        );
+        assert!(matches!(
+            rule.evaluate(&vaccinated_on("not a date"), &external)
+                .outcome,
+            RuleOutcome::Open(_)
+        ));
+    }
+
+    #[test]
+    fn it_selects_the_rules_to_evaluate() {
+        let dgc = vaccinated_on("2021-07-01");
+        let mut newer_rule = vaccination_rule();
+        newer_rule.version = "1.10.0".to_string();
+        newer_rule.logic = json!(false);
+        let mut other_country = vaccination_rule();
+        other_country.identifier = "VR-DE-0001".to_string();
+        other_country.country = "DE".to_string();
+        let mut test_rule = vaccination_rule();
+        test_rule.identifier = "TR-IT-0001".to_string();
+        test_rule.certificate_type = RuleCertificateType::Test;
+        let mut general_rule = vaccination_rule();
+        general_rule.identifier = "GR-IT-0001".to_string();
+        general_rule.certificate_type = RuleCertificateType::General;
+        let mut invalidation_rule = vaccination_rule();
+        invalidation_rule.identifier = "IR-FR-0001".to_string();
+        invalidation_rule.rule_type = RuleType::Invalidation;
+        invalidation_rule.country = "FR".to_string();
+        let rules = vec![
+            vaccination_rule(),
+            newer_rule,
+            other_country,
+            test_rule,
+            general_rule,
+            invalidation_rule,
+        ];
+
+        let external = ExternalParameters::new(
+            Utc.with_ymd_and_hms(2021, 8, 1, 0, 0, 0).unwrap(),
+            "IT",
+            "FR",
+        );
+        let results: Vec<_> = evaluate_rules(&rules, &dgc, &external)
+            .into_iter()
+            .map(|result| (result.identifier, result.version, result.outcome))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                (
+                    "VR-EU-0002".to_string(),
+                    "1.10.0".to_string(),
+                    RuleOutcome::Failed
+                ),
+                (
+                    "GR-IT-0001".to_string(),
+                    "1.0.0".to_string(),
+                    RuleOutcome::Passed
+                ),
+                (
+                    "IR-FR-0001".to_string(),
+                    "1.0.0".to_string(),
+                    RuleOutcome::Passed
+                ),
+            ]
+        );
+
+        // none of the rules is valid yet
+        let external = ExternalParameters::new(
+            Utc.with_ymd_and_hms(2021, 6, 1, 0, 0, 0).unwrap(),
+            "IT",
+            "FR",
+        );
+        assert!(evaluate_rules(&rules, &dgc, &external).is_empty());
+    }
+}
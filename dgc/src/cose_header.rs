@@ -0,0 +1,285 @@
+use crate::{
+    cwt::{COSE_HEADER_KEY_ALG, COSE_HEADER_KEY_KID},
+    EcAlg,
+};
+use ciborium::value::Value;
+use std::{convert::TryFrom, fmt, iter::FromIterator};
+
+/// COSE header label of the critical headers
+pub(crate) const COSE_HEADER_KEY_CRIT: i128 = 2;
+/// COSE header label of the content type
+pub(crate) const COSE_HEADER_KEY_CONTENT_TYPE: i128 = 3;
+/// COSE header label of the counter signature
+pub(crate) const COSE_HEADER_KEY_COUNTER_SIGNATURE: i128 = 7;
+/// COSE header label of the chain of X509 certificates
+pub(crate) const COSE_HEADER_KEY_X5CHAIN: i128 = 33;
+/// COSE header label of the hash of a X509 certificate
+pub(crate) const COSE_HEADER_KEY_X5T: i128 = 34;
+
+/// The header labels defined by [RFC 8152](https://datatracker.ietf.org/doc/html/rfc8152#section-3.1)
+/// that are processed by this library (`alg`, `crit`, `content type` and `kid`): they are the
+/// only ones understood when they are marked as critical. The IVs are only used by encrypted
+/// messages and counter signatures are not verified.
+const UNDERSTOOD_LABELS: std::ops::RangeInclusive<i128> = 1..=4;
+
+/// The label of a COSE header parameter, either an integer or a text string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HeaderLabel {
+    /// An integer label, as used by all the registered parameters
+    Int(i128),
+    /// A text label
+    Text(String),
+}
+
+impl HeaderLabel {
+    fn from_value(value: Value) -> Result<Self, Value> {
+        match value {
+            Value::Integer(label) => Ok(HeaderLabel::Int(label.into())),
+            Value::Text(label) => Ok(HeaderLabel::Text(label)),
+            value => Err(value),
+        }
+    }
+
+    /// Checks if the parameter with this label is understood by this library, which is required
+    /// when it is marked as critical.
+    pub fn is_understood(&self) -> bool {
+        matches!(self, HeaderLabel::Int(label) if UNDERSTOOD_LABELS.contains(label))
+    }
+}
+
+impl fmt::Display for HeaderLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderLabel::Int(label) => write!(f, "{}", label),
+            HeaderLabel::Text(label) => write!(f, "'{}'", label),
+        }
+    }
+}
+
+/// The content type of a COSE message: either a CoAP Content-Format or a media type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentType {
+    /// A [CoAP Content-Format](https://www.iana.org/assignments/core-parameters/core-parameters.xhtml#content-formats)
+    CoapFormat(u64),
+    /// A media type, such as `application/cwt`
+    MediaType(String),
+}
+
+/// The hash of a X509 certificate (`x5t`), as defined by
+/// [RFC 9360](https://datatracker.ietf.org/doc/html/rfc9360#section-2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateHash {
+    /// The COSE algorithm identifier of the hash function (e.g. `-16` for SHA-256)
+    pub alg: i128,
+    /// The hash of the DER encoded certificate
+    pub hash: Vec<u8>,
+}
+
+/// The parameters of a single COSE header map (either the protected or the unprotected one).
+///
+/// Parsing is permissive: the parameters whose label is not known, as well as the known ones
+/// whose value does not have the expected type, are kept in [`other`](CoseHeaderMap::other).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoseHeaderMap {
+    /// The signature algorithm (label 1)
+    pub alg: Option<EcAlg>,
+    /// The labels of the parameters that must be understood to process the message (label 2)
+    pub crit: Vec<HeaderLabel>,
+    /// The content type of the payload (label 3)
+    pub content_type: Option<ContentType>,
+    /// The Key ID (label 4)
+    pub kid: Option<Vec<u8>>,
+    /// The raw counter signature or signatures (label 7)
+    pub counter_signature: Option<Value>,
+    /// The DER encoded X509 certificates of the signer, starting from the signing certificate
+    /// (label 33)
+    pub x5chain: Vec<Vec<u8>>,
+    /// The hash of the signing certificate (label 34)
+    pub x5t: Option<CertificateHash>,
+    /// Any other parameter, in the order they appear in the header
+    pub other: Vec<(HeaderLabel, Value)>,
+}
+
+impl CoseHeaderMap {
+    /// Checks if the map contains no parameters.
+    pub fn is_empty(&self) -> bool {
+        self == &CoseHeaderMap::default()
+    }
+
+    /// Returns the value of the parameter with the given label among the [`other`](CoseHeaderMap::other)
+    /// parameters.
+    pub fn get_other(&self, label: &HeaderLabel) -> Option<&Value> {
+        self.other
+            .iter()
+            .find(|(other, _)| other == label)
+            .map(|(_, value)| value)
+    }
+
+    /// Tries to parse a known parameter, returning its value back if it does not have the
+    /// expected type.
+    fn set(&mut self, label: i128, value: Value) -> Result<(), Value> {
+        match (label, value) {
+            (COSE_HEADER_KEY_ALG, Value::Integer(alg)) => self.alg = Some(alg.into()),
+            (COSE_HEADER_KEY_CRIT, Value::Array(labels)) if !labels.is_empty() => {
+                match labels
+                    .iter()
+                    .cloned()
+                    .map(HeaderLabel::from_value)
+                    .collect()
+                {
+                    Ok(crit) => self.crit = crit,
+                    Err(_) => return Err(Value::Array(labels)),
+                }
+            }
+            (COSE_HEADER_KEY_CONTENT_TYPE, Value::Integer(format)) => {
+                let format = u64::try_from(format).map_err(|_| Value::Integer(format))?;
+                self.content_type = Some(ContentType::CoapFormat(format));
+            }
+            (COSE_HEADER_KEY_CONTENT_TYPE, Value::Text(media_type)) => {
+                self.content_type = Some(ContentType::MediaType(media_type))
+            }
+            (COSE_HEADER_KEY_KID, Value::Bytes(kid)) => self.kid = Some(kid),
+            (COSE_HEADER_KEY_COUNTER_SIGNATURE, value @ Value::Array(_)) => {
+                self.counter_signature = Some(value)
+            }
+            // a single certificate or an array of certificates
+            (COSE_HEADER_KEY_X5CHAIN, Value::Bytes(certificate)) => {
+                self.x5chain = vec![certificate]
+            }
+            (COSE_HEADER_KEY_X5CHAIN, Value::Array(certificates)) => {
+                match certificates.iter().map(|c| c.as_bytes().cloned()).collect() {
+                    Some(chain) => self.x5chain = chain,
+                    None => return Err(Value::Array(certificates)),
+                }
+            }
+            (COSE_HEADER_KEY_X5T, Value::Array(parts)) => match &parts[..] {
+                [Value::Integer(alg), Value::Bytes(hash)] => {
+                    self.x5t = Some(CertificateHash {
+                        alg: (*alg).into(),
+                        hash: hash.clone(),
+                    })
+                }
+                _ => return Err(Value::Array(parts)),
+            },
+            (_, value) => return Err(value),
+        }
+        Ok(())
+    }
+}
+
+impl FromIterator<(Value, Value)> for CoseHeaderMap {
+    fn from_iter<T: IntoIterator<Item = (Value, Value)>>(iter: T) -> Self {
+        // permissive parsing. We don't want to fail if we can't decode the header
+        let mut header = CoseHeaderMap::default();
+        for (key, val) in iter {
+            match HeaderLabel::from_value(key) {
+                Ok(HeaderLabel::Int(label)) => {
+                    if let Err(val) = header.set(label, val) {
+                        header.other.push((HeaderLabel::Int(label), val));
+                    }
+                }
+                Ok(label) => header.other.push((label, val)),
+                // labels must be integers or text strings, so this is not a header parameter
+                Err(_) => {}
+            }
+        }
+        header
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i64) -> Value {
+        Value::from(value)
+    }
+
+    #[test]
+    fn it_parses_every_parameter() {
+        let header: CoseHeaderMap = vec![
+            (int(1), int(-7)),
+            (int(2), Value::Array(vec![int(33), Value::from("custom")])),
+            (int(3), Value::from("application/cwt")),
+            (int(4), Value::Bytes(vec![1, 2, 3])),
+            (int(7), Value::Array(vec![])),
+            (int(33), Value::Bytes(vec![0x30])),
+            (
+                int(34),
+                Value::Array(vec![int(-16), Value::Bytes(vec![0xaa])]),
+            ),
+            (Value::from("custom"), Value::Bool(true)),
+            (int(-70000), Value::Null),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(header.alg, Some(EcAlg::Es256));
+        assert_eq!(
+            header.crit,
+            vec![
+                HeaderLabel::Int(33),
+                HeaderLabel::Text(String::from("custom"))
+            ]
+        );
+        assert_eq!(
+            header.content_type,
+            Some(ContentType::MediaType(String::from("application/cwt")))
+        );
+        assert_eq!(header.kid, Some(vec![1, 2, 3]));
+        assert_eq!(header.counter_signature, Some(Value::Array(vec![])));
+        assert_eq!(header.x5chain, vec![vec![0x30]]);
+        assert_eq!(
+            header.x5t,
+            Some(CertificateHash {
+                alg: -16,
+                hash: vec![0xaa]
+            })
+        );
+        assert_eq!(
+            header.get_other(&HeaderLabel::Text(String::from("custom"))),
+            Some(&Value::Bool(true))
+        );
+        assert_eq!(
+            header.get_other(&HeaderLabel::Int(-70000)),
+            Some(&Value::Null)
+        );
+        assert!(!header.is_empty());
+    }
+
+    #[test]
+    fn it_keeps_malformed_parameters() {
+        let header: CoseHeaderMap = vec![
+            (int(4), Value::from("not bytes")),
+            (int(3), int(-1)),
+            (int(33), Value::Array(vec![int(1)])),
+            (int(2), Value::Array(vec![])),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(header.kid, None);
+        assert_eq!(header.content_type, None);
+        assert!(header.x5chain.is_empty());
+        assert!(header.crit.is_empty());
+        assert_eq!(header.other.len(), 4);
+        assert_eq!(
+            header.get_other(&HeaderLabel::Int(4)),
+            Some(&Value::from("not bytes"))
+        );
+        assert_eq!(
+            header.get_other(&HeaderLabel::Int(33)),
+            Some(&Value::Array(vec![int(1)]))
+        );
+    }
+
+    #[test]
+    fn it_knows_which_labels_are_understood() {
+        assert!(HeaderLabel::Int(1).is_understood());
+        assert!(HeaderLabel::Int(4).is_understood());
+        assert!(!HeaderLabel::Int(5).is_understood());
+        assert!(!HeaderLabel::Int(7).is_understood());
+        assert!(!HeaderLabel::Int(33).is_understood());
+        assert!(!HeaderLabel::Text(String::from("alg")).is_understood());
+    }
+}
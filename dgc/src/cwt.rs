@@ -1,23 +1,33 @@
-use crate::DgcContainer;
+use crate::{cose_header::COSE_HEADER_KEY_CRIT, CoseHeaderMap, DgcContainer, HeaderLabel};
 use ciborium::{
     ser::into_writer,
     value::{Integer, Value},
 };
-use std::iter::FromIterator;
 use std::{
     convert::{TryFrom, TryInto},
     ops::Not,
 };
 use thiserror::Error;
 
-const COSE_SIGN1_CBOR_TAG: u64 = 18;
+pub(crate) const COSE_SIGN1_CBOR_TAG: u64 = 18;
+pub(crate) const COSE_SIGN_CBOR_TAG: u64 = 98;
 const CBOR_WEB_TOKEN_TAG: u64 = 61;
-const COSE_HEADER_KEY_KID: i128 = 4;
-const COSE_HEADER_KEY_ALG: i128 = 1;
+pub(crate) const COSE_HEADER_KEY_KID: i128 = 4;
+pub(crate) const COSE_HEADER_KEY_ALG: i128 = 1;
 /// COSE key for ECDSA w/ SHA-256
 const COSE_ES256: i128 = -7;
+/// COSE key for ECDSA w/ SHA-384
+const COSE_ES384: i128 = -35;
+/// COSE key for ECDSA w/ SHA-512
+const COSE_ES512: i128 = -36;
 /// COSE key for RSASSA-PSS w/ SHA-256
 const COSE_PS256: i128 = -37;
+/// COSE key for RSASSA-PSS w/ SHA-384
+const COSE_PS384: i128 = -38;
+/// COSE key for RSASSA-PSS w/ SHA-512
+const COSE_PS512: i128 = -39;
+/// COSE key for EdDSA
+const COSE_EDDSA: i128 = -8;
 
 /// An enum representing all the possible errors that can occur while trying
 /// to parse data representing a CWT ([CBOR Web Token](https://datatracker.ietf.org/doc/html/rfc8392)).
@@ -31,8 +41,9 @@ pub enum CwtParseError {
     InvalidRootValue,
     /// The root tag is invalid
     #[error(
-        "Expected COSE_SIGN1_CBOR_TAG ({}) or CBOR_WEB_TOKEN_TAG ({}). Found: {0}",
+        "Expected COSE_SIGN1_CBOR_TAG ({}), COSE_SIGN_CBOR_TAG ({}) or CBOR_WEB_TOKEN_TAG ({}). Found: {0}",
         COSE_SIGN1_CBOR_TAG,
+        COSE_SIGN_CBOR_TAG,
         CBOR_WEB_TOKEN_TAG
     )]
     InvalidTag(u64),
@@ -63,10 +74,19 @@ pub enum CwtParseError {
     /// The signature section is not a binary string
     #[error("The signature section is not a binary string")]
     SignatureNotBinary,
+    /// The signatures section of a COSE_Sign message is not an array
+    #[error("The signatures section of a COSE_Sign message is not an array")]
+    SignersNotArray,
+    /// The signatures section of a COSE_Sign message is empty
+    #[error("The signatures section of a COSE_Sign message is empty")]
+    NoSigners,
+    /// A signature of a COSE_Sign message is not an array of 3 parts
+    #[error("The signature {0} of a COSE_Sign message is not an array of 3 parts")]
+    InvalidSigner(usize),
 }
 
 /// An enum representing the supported signing verification algorithms.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EcAlg {
     /// ECDSA w/ SHA-256
     ///
@@ -77,6 +97,24 @@ pub enum EcAlg {
     /// [ecdsa]: https://en.wikipedia.org/wiki/Elliptic_Curve_Digital_Signature_Algorithm
     /// [sha2]: https://en.wikipedia.org/wiki/SHA-2
     Es256,
+    /// ECDSA w/ SHA-384
+    ///
+    /// [Elliptic Curve Digital Signature Algorithm][ecdsa] using the
+    /// [Secure Hash Algorithm 2][sha2] hash function
+    /// with digest size of 384 bits.
+    ///
+    /// [ecdsa]: https://en.wikipedia.org/wiki/Elliptic_Curve_Digital_Signature_Algorithm
+    /// [sha2]: https://en.wikipedia.org/wiki/SHA-2
+    Es384,
+    /// ECDSA w/ SHA-512
+    ///
+    /// [Elliptic Curve Digital Signature Algorithm][ecdsa] using the
+    /// [Secure Hash Algorithm 2][sha2] hash function
+    /// with digest size of 512 bits.
+    ///
+    /// [ecdsa]: https://en.wikipedia.org/wiki/Elliptic_Curve_Digital_Signature_Algorithm
+    /// [sha2]: https://en.wikipedia.org/wiki/SHA-2
+    Es512,
     /// RSASSA-PSS w/ SHA-256
     ///
     /// [Rivest-Shamir-Adleman][rsa] signing algorithm using the
@@ -86,6 +124,31 @@ pub enum EcAlg {
     /// [rsa]: https://en.wikipedia.org/wiki/RSA_(cryptosystem)
     /// [sha2]: https://en.wikipedia.org/wiki/SHA-2
     Ps256,
+    /// RSASSA-PSS w/ SHA-384
+    ///
+    /// [Rivest-Shamir-Adleman][rsa] signing algorithm using the
+    /// [Secure Hash Algorithm 2][sha2] hash function
+    /// with digest size of 384 bits.
+    ///
+    /// [rsa]: https://en.wikipedia.org/wiki/RSA_(cryptosystem)
+    /// [sha2]: https://en.wikipedia.org/wiki/SHA-2
+    Ps384,
+    /// RSASSA-PSS w/ SHA-512
+    ///
+    /// [Rivest-Shamir-Adleman][rsa] signing algorithm using the
+    /// [Secure Hash Algorithm 2][sha2] hash function
+    /// with digest size of 512 bits.
+    ///
+    /// [rsa]: https://en.wikipedia.org/wiki/RSA_(cryptosystem)
+    /// [sha2]: https://en.wikipedia.org/wiki/SHA-2
+    Ps512,
+    /// EdDSA
+    ///
+    /// [Edwards-curve Digital Signature Algorithm][eddsa]. Only the Ed25519
+    /// curve is supported.
+    ///
+    /// [eddsa]: https://en.wikipedia.org/wiki/EdDSA
+    EdDsa,
     /// Unknown algorithm
     ///
     /// The value is the COSE algorithm identifier defined by the IANA,
@@ -98,134 +161,274 @@ impl From<Integer> for EcAlg {
         let u: i128 = i.into();
         match u {
             COSE_ES256 => EcAlg::Es256,
+            COSE_ES384 => EcAlg::Es384,
+            COSE_ES512 => EcAlg::Es512,
             COSE_PS256 => EcAlg::Ps256,
+            COSE_PS384 => EcAlg::Ps384,
+            COSE_PS512 => EcAlg::Ps512,
+            COSE_EDDSA => EcAlg::EdDsa,
             _ => EcAlg::Unknown(u),
         }
     }
 }
 
+/// The COSE header bucket a header parameter was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderSource {
+    /// The protected header, covered by the signature
+    Protected,
+    /// The unprotected header, not covered by the signature
+    Unprotected,
+}
+
 /// The CWT header object.
 ///
 /// This is a simplification of the actual CWT structure. In fact,
 /// in the CWT spec there are 2 headers (protected header and unprotected header).
 ///
-/// For the sake of DGC, we only need to extract `kid` and `alg` from either of them,
-/// so we use this struct to keep those values.
+/// For the sake of DGC, we mostly need to extract `kid` and `alg` from either of them,
+/// so we use this struct to keep those values, next to the complete
+/// [`protected`](CwtHeader::protected) and [`unprotected`](CwtHeader::unprotected) headers.
+///
+/// A parameter should not appear in both headers
+/// ([RFC 8152, section 3](https://datatracker.ietf.org/doc/html/rfc8152#section-3)), but
+/// some issuers put the `kid` in the unprotected header only, and others in both of them.
+/// As required by the DCC specification, the values of the protected header take precedence
+/// and the unprotected header is only used as a fallback.
 #[derive(Debug)]
 pub struct CwtHeader {
     /// The Key ID used for signing the certificate
     pub kid: Option<Vec<u8>>,
+    /// The header the Key ID was read from
+    pub kid_source: Option<HeaderSource>,
     /// The signature algorithm used to sign the certificate
     pub alg: Option<EcAlg>,
+    /// All the parameters of the protected header
+    pub protected: CoseHeaderMap,
+    /// All the parameters of the unprotected header
+    pub unprotected: CoseHeaderMap,
+}
+
+impl From<&EcAlg> for i128 {
+    fn from(alg: &EcAlg) -> Self {
+        match alg {
+            EcAlg::Es256 => COSE_ES256,
+            EcAlg::Es384 => COSE_ES384,
+            EcAlg::Es512 => COSE_ES512,
+            EcAlg::Ps256 => COSE_PS256,
+            EcAlg::Ps384 => COSE_PS384,
+            EcAlg::Ps512 => COSE_PS512,
+            EcAlg::EdDsa => COSE_EDDSA,
+            EcAlg::Unknown(alg) => *alg,
+        }
+    }
 }
 
 impl CwtHeader {
-    fn new() -> Self {
+    /// Creates the header of a COSE message from its protected and unprotected header maps,
+    /// giving precedence to the parameters of the protected one.
+    pub fn new(protected: CoseHeaderMap, unprotected: CoseHeaderMap) -> Self {
+        let (kid, kid_source) = match (&protected.kid, &unprotected.kid) {
+            (Some(kid), _) => (Some(kid.clone()), Some(HeaderSource::Protected)),
+            (None, Some(kid)) => (Some(kid.clone()), Some(HeaderSource::Unprotected)),
+            (None, None) => (None, None),
+        };
         Self {
-            kid: None,
-            alg: None,
+            kid,
+            kid_source,
+            alg: protected.alg.or(unprotected.alg),
+            protected,
+            unprotected,
         }
     }
 
-    fn kid(&mut self, kid: Vec<u8>) {
-        self.kid = Some(kid);
+    /// Checks that the critical headers (`crit`), if present, are a non-empty array of labels
+    /// in the protected header, as required by
+    /// [RFC 8152](https://datatracker.ietf.org/doc/html/rfc8152#section-3.1).
+    pub fn has_valid_critical_headers(&self) -> bool {
+        let crit = HeaderLabel::Int(COSE_HEADER_KEY_CRIT);
+        self.protected.get_other(&crit).is_none()
+            && self.unprotected.crit.is_empty()
+            && self.unprotected.get_other(&crit).is_none()
     }
 
-    fn alg(&mut self, alg: EcAlg) {
-        self.alg = Some(alg);
+    /// Returns the labels marked as critical that are not understood by this library.
+    ///
+    /// As required by [RFC 8152](https://datatracker.ietf.org/doc/html/rfc8152#section-3.1),
+    /// a message with any such label must be rejected.
+    pub fn unknown_critical_headers(&self) -> Vec<&HeaderLabel> {
+        self.protected
+            .crit
+            .iter()
+            .filter(|label| !label.is_understood())
+            .collect()
     }
 }
 
-impl FromIterator<(Value, Value)> for CwtHeader {
-    fn from_iter<T: IntoIterator<Item = (Value, Value)>>(iter: T) -> Self {
-        // permissive parsing. We don't want to fail if we can't decode the header
-        let mut header = CwtHeader::new();
-        // tries to find kid and alg and apply them to the header before returning it
-        for (key, val) in iter {
-            if let Value::Integer(k) = key {
-                let k: i128 = k.into();
-                if k == COSE_HEADER_KEY_KID {
-                    // found kid
-                    if let Value::Bytes(kid) = val {
-                        header.kid(kid);
-                    }
-                } else if k == COSE_HEADER_KEY_ALG {
-                    // found alg
-                    if let Value::Integer(raw_alg) = val {
-                        let alg: EcAlg = raw_alg.into();
-                        header.alg(alg);
-                    }
-                }
-            }
-        }
-        header
-    }
+/// A signer of a [COSE_Sign](https://datatracker.ietf.org/doc/html/rfc8152#section-4.1) message.
+#[derive(Debug)]
+pub struct CoseSigner {
+    sign_protected_raw: Vec<u8>,
+    /// The headers of the signer (protected + unprotected)
+    pub header: CwtHeader,
+    /// The raw bytes of the signature
+    pub signature: Vec<u8>,
 }
 
 /// A representation of a CWT ([CBOR Web Token](https://datatracker.ietf.org/doc/html/rfc8392)).
 ///
-/// In the context of DGC only a portion of the original CWT specification is actually used
-/// ([COSE_Sign1](https://datatracker.ietf.org/doc/html/rfc8152#section-4.2)) so this module
-/// is limited to implementing exclusively that portion.
+/// In the context of DGC the CWT is usually a
+/// [COSE_Sign1](https://datatracker.ietf.org/doc/html/rfc8152#section-4.2) message, with a
+/// single signature. [COSE_Sign](https://datatracker.ietf.org/doc/html/rfc8152#section-4.1)
+/// messages, which can have several signers, are also supported.
 #[derive(Debug)]
 pub struct Cwt {
     header_protected_raw: Vec<u8>,
     payload_raw: Vec<u8>,
-    /// A simplified representation of the original CWT headers (protected + unprotected)
+    /// The CWT headers (protected + unprotected)
     ///
-    /// Stores only the `kid` and `alg`
+    /// For COSE_Sign messages these are the headers of the message body, while the `kid` and
+    /// `alg` are usually in the headers of the [`signers`](Cwt::signers).
     pub header: CwtHeader,
     /// The CWT payload parse as a DgcContainer
     pub payload: DgcContainer,
     /// The raw bytes of the signature
+    ///
+    /// For COSE_Sign messages this is the signature of the first signer.
     pub signature: Vec<u8>,
+    /// The signers of a COSE_Sign message, or an empty list for a COSE_Sign1 message
+    pub signers: Vec<CoseSigner>,
 }
 
 impl Cwt {
     /// Creates the [sig structure](https://datatracker.ietf.org/doc/html/rfc8152#section-4.4) needed to be able
     /// to verify the signature against a public key.
+    ///
+    /// For COSE_Sign messages use [`make_signer_sig_structure`](Cwt::make_signer_sig_structure)
+    /// instead.
     pub fn make_sig_structure(&self) -> Vec<u8> {
+        make_sig_structure(&self.header_protected_raw, &self.payload_raw)
+    }
+
+    /// Creates the [sig structure](https://datatracker.ietf.org/doc/html/rfc8152#section-4.4)
+    /// needed to verify the signature of a signer of a COSE_Sign message.
+    pub fn make_signer_sig_structure(&self, signer: &CoseSigner) -> Vec<u8> {
         let sig_structure_cbor = Value::Array(vec![
-            Value::Text(String::from("Signature1")), // context of the signature
-            Value::Bytes(self.header_protected_raw.clone()), // protected attributes from the body structure
-            Value::Bytes(vec![]), // protected attributes from the application (these are not used in hcert so we keep them empty as per spec)
+            Value::Text(String::from("Signature")),
+            Value::Bytes(self.header_protected_raw.clone()),
+            Value::Bytes(signer.sign_protected_raw.clone()),
+            Value::Bytes(vec![]),
             Value::Bytes(self.payload_raw.clone()),
         ]);
         let mut sig_structure: Vec<u8> = vec![];
         into_writer(&sig_structure_cbor, &mut sig_structure).unwrap();
         sig_structure
     }
-}
 
-/// Extends `ciborium::value::Value` with some useful methods.
-/// TODO: send a PR to `ciborium` to have these utilities out of the box.
-trait ValueExt: Sized {
-    fn into_tag(self) -> Result<(u64, Box<Value>), Self>;
-    fn into_array(self) -> Result<Vec<Value>, Self>;
-    fn into_bytes(self) -> Result<Vec<u8>, Self>;
-}
+    /// Returns the headers containing the `kid` and `alg` of the signature: the headers of the
+    /// message for COSE_Sign1, the ones of the first signer for COSE_Sign.
+    pub fn signing_header(&self) -> &CwtHeader {
+        self.signers
+            .first()
+            .map_or(&self.header, |signer| &signer.header)
+    }
 
-impl ValueExt for Value {
-    fn into_tag(self) -> Result<(u64, Box<Value>), Self> {
-        match self {
-            Self::Tag(tag, content) => Ok((tag, content)),
-            _ => Err(self),
+    /// Returns the headers, the signature and the sig structure of every signature of the
+    /// message.
+    pub(crate) fn signatures(&self) -> Vec<(&CwtHeader, &[u8], Vec<u8>)> {
+        if self.signers.is_empty() {
+            return vec![(&self.header, &self.signature, self.make_sig_structure())];
         }
+        self.signers
+            .iter()
+            .map(|signer| {
+                let sig_structure = self.make_signer_sig_structure(signer);
+                (&signer.header, signer.signature.as_slice(), sig_structure)
+            })
+            .collect()
     }
+}
 
-    fn into_array(self) -> Result<Vec<Value>, Self> {
-        match self {
-            Self::Array(array) => Ok(array),
-            _ => Err(self),
-        }
+/// Creates the [sig structure](https://datatracker.ietf.org/doc/html/rfc8152#section-4.4) for
+/// the given raw protected header and payload.
+pub(crate) fn make_sig_structure(header_protected_raw: &[u8], payload_raw: &[u8]) -> Vec<u8> {
+    let sig_structure_cbor = Value::Array(vec![
+        Value::Text(String::from("Signature1")), // context of the signature
+        Value::Bytes(header_protected_raw.to_vec()), // protected attributes from the body structure
+        Value::Bytes(vec![]), // protected attributes from the application (these are not used in hcert so we keep them empty as per spec)
+        Value::Bytes(payload_raw.to_vec()),
+    ]);
+    let mut sig_structure: Vec<u8> = vec![];
+    into_writer(&sig_structure_cbor, &mut sig_structure).unwrap();
+    sig_structure
+}
+
+/// Parses the raw protected header and the unprotected header of a message or of a signer.
+fn parse_header(
+    header_protected_raw: &[u8],
+    unprotected_header: Value,
+) -> Result<CwtHeader, CwtParseError> {
+    use CwtParseError::*;
+
+    // unprotected header must be a cbor map or an empty sequence of bytes
+    let unprotected_header = match unprotected_header {
+        Value::Map(values) => Some(values),
+        Value::Bytes(values) if values.is_empty() => Some(Vec::new()),
+        _ => None,
     }
+    .ok_or(MalformedUnProtectedHeader)?;
+
+    // protected header is a bytes sequence.
+    // If the length of the sequence is 0 we assume it represents an empty map.
+    // Otherwise we decode the binary string as a CBOR value and we make sure it represents a map.
+    let protected_header_values = header_protected_raw
+        .is_empty()
+        .not()
+        .then(|| {
+            let value = ciborium::de::from_reader(header_protected_raw)
+                .map_err(|_| ProtectedHeaderNotValidCbor)?;
+
+            match value {
+                Value::Map(map) => Ok(map),
+                _ => Err(ProtectedHeaderNotMap),
+            }
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    // parameters of the protected header take precedence over the unprotected ones
+    Ok(CwtHeader::new(
+        protected_header_values.into_iter().collect(),
+        unprotected_header.into_iter().collect(),
+    ))
+}
 
-    fn into_bytes(self) -> Result<Vec<u8>, Self> {
-        match self {
-            Self::Bytes(bytes) => Ok(bytes),
-            _ => Err(self),
-        }
+/// Parses the `signatures` of a COSE_Sign message.
+fn parse_signers(signers: Value) -> Result<Vec<CoseSigner>, CwtParseError> {
+    use CwtParseError::*;
+
+    let signers = signers.into_array().map_err(|_| SignersNotArray)?;
+    if signers.is_empty() {
+        return Err(NoSigners);
     }
+    signers
+        .into_iter()
+        .enumerate()
+        .map(|(index, signer)| {
+            let parts = signer.into_array().map_err(|_| InvalidSigner(index))?;
+            let [sign_protected_raw, unprotected_header, signature]: [Value; 3] =
+                parts.try_into().map_err(|_| InvalidSigner(index))?;
+            let sign_protected_raw = sign_protected_raw
+                .into_bytes()
+                .map_err(|_| ProtectedHeaderNotBinary)?;
+            let signature = signature.into_bytes().map_err(|_| SignatureNotBinary)?;
+            Ok(CoseSigner {
+                header: parse_header(&sign_protected_raw, unprotected_header)?,
+                sign_protected_raw,
+                signature,
+            })
+        })
+        .collect()
 }
 
 impl TryFrom<&[u8]> for Cwt {
@@ -238,10 +441,11 @@ impl TryFrom<&[u8]> for Cwt {
             Value::Tag(tag_id, content) if tag_id == CBOR_WEB_TOKEN_TAG => *content,
             cwt => cwt,
         };
-        let cwt_content = match cwt_content.into_tag() {
-            Ok((COSE_SIGN1_CBOR_TAG, content)) => *content,
+        let (cwt_content, is_multi_signer) = match cwt_content.into_tag() {
+            Ok((COSE_SIGN1_CBOR_TAG, content)) => (*content, false),
+            Ok((COSE_SIGN_CBOR_TAG, content)) => (*content, true),
             Ok((tag_id, _)) => return Err(InvalidTag(tag_id)),
-            Err(cwt) => cwt,
+            Err(cwt) => (cwt, false),
         };
 
         let parts = cwt_content.into_array().map_err(|_| InvalidParts)?;
@@ -254,39 +458,15 @@ impl TryFrom<&[u8]> for Cwt {
             .into_bytes()
             .map_err(|_| ProtectedHeaderNotBinary)?;
         let payload_raw = payload_raw.into_bytes().map_err(|_| PayloadNotBinary)?;
-        let signature = signature.into_bytes().map_err(|_| SignatureNotBinary)?;
-
-        // unprotected header must be a cbor map or an empty sequence of bytes
-        let unprotected_header = match unprotected_header {
-            Value::Map(values) => Some(values),
-            Value::Bytes(values) if values.is_empty() => Some(Vec::new()),
-            _ => None,
-        }
-        .ok_or(MalformedUnProtectedHeader)?;
-
-        // protected header is a bytes sequence.
-        // If the length of the sequence is 0 we assume it represents an empty map.
-        // Otherwise we decode the binary string as a CBOR value and we make sure it represents a map.
-        let protected_header_values = header_protected_raw
-            .is_empty()
-            .not()
-            .then(|| {
-                let value = ciborium::de::from_reader(header_protected_raw.as_slice())
-                    .map_err(|_| ProtectedHeaderNotValidCbor)?;
-
-                match value {
-                    Value::Map(map) => Ok(map),
-                    _ => Err(ProtectedHeaderNotMap),
-                }
-            })
-            .transpose()?
-            .unwrap_or_default();
+        let (signature, signers) = if is_multi_signer {
+            let signers = parse_signers(signature)?;
+            (signers[0].signature.clone(), signers)
+        } else {
+            let signature = signature.into_bytes().map_err(|_| SignatureNotBinary)?;
+            (signature, Vec::new())
+        };
 
-        // Take data from unprotected header first, then from the protected one
-        let header: CwtHeader = unprotected_header
-            .into_iter()
-            .chain(protected_header_values)
-            .collect();
+        let header = parse_header(&header_protected_raw, unprotected_header)?;
 
         let payload: DgcContainer =
             ciborium::de::from_reader(payload_raw.as_slice()).map_err(InvalidPayload)?;
@@ -297,6 +477,7 @@ impl TryFrom<&[u8]> for Cwt {
             header,
             payload,
             signature,
+            signers,
         })
     }
 }
@@ -315,6 +496,11 @@ mod tests {
 
     // test data from https://dgc.a-sit.at/ehn/generate
     use super::*;
+    use crate::{
+        parse::check_signature,
+        test_fixtures::{alg, cose_sign, cose_sign1, kid, signer},
+        ContentType, SignatureValidity, TrustList,
+    };
 
     #[test]
     fn it_parses_cose_data() {
@@ -327,10 +513,157 @@ mod tests {
         let cwt: Cwt = raw_cose_data.as_slice().try_into().unwrap();
 
         assert_eq!(Some(expected_kid), cwt.header.kid);
+        assert_eq!(Some(HeaderSource::Protected), cwt.header.kid_source);
         assert_eq!(Some(expected_alg), cwt.header.alg);
         assert_eq!(
             expected_sig_structure,
             hex::encode(cwt.make_sig_structure())
         );
     }
+
+    #[test]
+    fn it_gives_precedence_to_the_protected_header() {
+        let cwt = cose_sign1(vec![alg(-7)], vec![kid(b"unprotected")]);
+        assert_eq!(cwt.header.kid.as_deref(), Some(&b"unprotected"[..]));
+        assert_eq!(cwt.header.kid_source, Some(HeaderSource::Unprotected));
+        assert_eq!(cwt.header.alg, Some(EcAlg::Es256));
+
+        let cwt = cose_sign1(
+            vec![kid(b"protected"), alg(-7)],
+            vec![kid(b"unprotected"), alg(-35)],
+        );
+        assert_eq!(cwt.header.kid.as_deref(), Some(&b"protected"[..]));
+        assert_eq!(cwt.header.kid_source, Some(HeaderSource::Protected));
+        assert_eq!(cwt.header.alg, Some(EcAlg::Es256));
+
+        let cwt = cose_sign1(vec![], vec![]);
+        assert_eq!(cwt.header.kid, None);
+        assert_eq!(cwt.header.kid_source, None);
+        assert_eq!(cwt.header.alg, None);
+    }
+
+    #[test]
+    fn it_keeps_the_whole_headers() {
+        let cwt = cose_sign1(
+            vec![kid(b"kid"), alg(-7), (Value::from(3), Value::from(61))],
+            vec![(Value::from(33), Value::Bytes(vec![0x30]))],
+        );
+        assert_eq!(cwt.header.protected.kid.as_deref(), Some(&b"kid"[..]));
+        assert_eq!(
+            cwt.header.protected.content_type,
+            Some(ContentType::CoapFormat(61))
+        );
+        assert_eq!(cwt.header.unprotected.x5chain, vec![vec![0x30]]);
+        assert!(cwt.header.unknown_critical_headers().is_empty());
+    }
+
+    #[test]
+    fn it_rejects_unknown_critical_headers() {
+        let crit = |labels: Vec<Value>| (Value::from(2), Value::Array(labels));
+
+        let cwt = cose_sign1(
+            vec![kid(b"kid"), alg(-7), crit(vec![Value::from(4)])],
+            vec![],
+        );
+        assert!(cwt.header.unknown_critical_headers().is_empty());
+
+        let cwt = cose_sign1(
+            vec![
+                kid(b"kid"),
+                alg(-7),
+                crit(vec![Value::from(4), Value::from("custom")]),
+                (Value::from("custom"), Value::Bool(true)),
+            ],
+            vec![],
+        );
+        let label = HeaderLabel::Text(String::from("custom"));
+        assert_eq!(cwt.header.unknown_critical_headers(), vec![&label]);
+
+        let mut trustlist = TrustList::new();
+        trustlist.add(b"kid", vec![0x04; 65]);
+        let (validity, matched_entry) = check_signature(&cwt, &trustlist);
+        assert!(matches!(
+            validity,
+            SignatureValidity::UnknownCriticalHeaders(labels) if labels == vec![label]
+        ));
+        assert!(matched_entry.is_none());
+    }
+
+    #[test]
+    fn it_rejects_malformed_or_unprotected_critical_headers() {
+        let crit = |labels: Value| (Value::from(2), labels);
+        let mut trustlist = TrustList::new();
+        trustlist.add(b"kid", vec![0x04; 65]);
+
+        let cases = vec![
+            (vec![crit(Value::Array(vec![]))], vec![]),
+            (vec![crit(Value::from(4))], vec![]),
+            (vec![crit(Value::Array(vec![Value::Bool(true)]))], vec![]),
+            (vec![], vec![crit(Value::Array(vec![Value::from(4)]))]),
+            (vec![], vec![crit(Value::from(4))]),
+        ];
+        for (protected, unprotected) in cases {
+            let protected = [vec![kid(b"kid"), alg(-7)], protected].concat();
+            let cwt = cose_sign1(protected, unprotected);
+            assert!(!cwt.header.has_valid_critical_headers());
+            let (validity, matched_entry) = check_signature(&cwt, &trustlist);
+            assert!(matches!(
+                validity,
+                SignatureValidity::InvalidCriticalHeaders
+            ));
+            assert!(matched_entry.is_none());
+        }
+
+        let cwt = cose_sign1(vec![kid(b"kid"), alg(-7)], vec![]);
+        assert!(cwt.header.has_valid_critical_headers());
+    }
+
+    #[test]
+    fn it_validates_cose_sign_messages_with_any_trusted_signer() {
+        use crate::{x509_test_utils::TestKey, SigningKey};
+
+        let mut cwt = cose_sign(vec![
+            signer(vec![kid(b"unknown"), alg(-7)], vec![]),
+            signer(vec![alg(-7)], vec![kid(b"trusted")]),
+        ])
+        .unwrap();
+        assert_eq!(cwt.signers.len(), 2);
+        assert_eq!(cwt.header.kid, None);
+        assert_eq!(cwt.signing_header().kid.as_deref(), Some(&b"unknown"[..]));
+        assert_eq!(
+            cwt.signers[1].header.kid_source,
+            Some(HeaderSource::Unprotected)
+        );
+
+        let key = TestKey::generate();
+        let signing_key = SigningKey::from_pkcs8(EcAlg::Es256, &key.pkcs8).unwrap();
+        let sig_structure = cwt.make_signer_sig_structure(&cwt.signers[1]);
+        cwt.signers[1].signature = signing_key.sign(&sig_structure).unwrap();
+
+        let mut trustlist = TrustList::new();
+        let (validity, matched) = check_signature(&cwt, &trustlist);
+        assert!(matches!(validity, SignatureValidity::KeyNotInTrustList(_)));
+        assert!(matched.is_none());
+
+        trustlist.add(b"trusted", key.public_key().to_vec());
+        let (validity, matched) = check_signature(&cwt, &trustlist);
+        assert!(validity.is_valid());
+        assert_eq!(matched.unwrap().kid, b"trusted");
+
+        // the signature of the first signer is not the one of the second
+        trustlist.add(b"unknown", key.public_key().to_vec());
+        cwt.signers[1].signature[0] ^= 0xff;
+        let (validity, matched) = check_signature(&cwt, &trustlist);
+        assert!(matches!(validity, SignatureValidity::Invalid));
+        assert!(matched.is_none());
+    }
+
+    #[test]
+    fn it_rejects_malformed_cose_sign_messages() {
+        assert!(matches!(cose_sign(vec![]), Err(CwtParseError::NoSigners)));
+        assert!(matches!(
+            cose_sign(vec![Value::Array(vec![Value::Bytes(vec![])])]),
+            Err(CwtParseError::InvalidSigner(0))
+        ));
+    }
 }
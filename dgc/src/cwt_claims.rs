@@ -0,0 +1,261 @@
+use crate::IntegerOrFloat;
+use ciborium::value::{Integer, Value};
+use serde::{de::MapAccess, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+
+pub(crate) const ISSUER: i128 = 1;
+pub(crate) const SUBJECT: i128 = 2;
+pub(crate) const AUDIENCE: i128 = 3;
+pub(crate) const EXPIRATION_TIME: i128 = 4;
+pub(crate) const NOT_BEFORE: i128 = 5;
+pub(crate) const ISSUED_AT: i128 = 6;
+pub(crate) const CWT_ID: i128 = 7;
+
+/// The claims set of a CWT ([RFC 8392](https://datatracker.ietf.org/doc/html/rfc8392#section-3)).
+///
+/// It contains the registered claims that are not fields of the
+/// [`DgcContainer`](crate::DgcContainer) as typed values and every other claim (except the
+/// `hcert` claim, whose certificates are [parsed](crate::DgcContainer::certs)) as raw CBOR
+/// values. The issuer and the timestamps are fields of the container: use
+/// [`DgcContainer::registered_claims`](crate::DgcContainer::registered_claims) to read all the
+/// registered claims together.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CwtClaims {
+    /// The subject (`sub`, claim 2)
+    pub sub: Option<String>,
+    /// The audience (`aud`, claim 3), which can be a single value
+    pub aud: Vec<String>,
+    /// The CWT ID (`cti`, claim 7)
+    pub cti: Option<Vec<u8>>,
+    /// Any private or unknown claim, with its raw key and value, in the order they appear in
+    /// the CWT. The `sub`, `aud` and `cti` claims are kept here too when their value does not
+    /// have the expected type, or when they are repeated
+    pub private: Vec<(Value, Value)>,
+}
+
+impl CwtClaims {
+    /// Returns the value of the private claim with the given key (an integer or a text string).
+    pub fn get_private(&self, key: impl Into<Value>) -> Option<&Value> {
+        let key = key.into();
+        self.private
+            .iter()
+            .find(|(claim, _)| claim == &key)
+            .map(|(_, value)| value)
+    }
+
+    /// Reads the value of a claim other than `hcert`, the issuer and the timestamps from the
+    /// given map.
+    ///
+    /// Unexpected values of the claims are kept as private claims.
+    pub(crate) fn read_claim<'de, V: MapAccess<'de>>(
+        &mut self,
+        key: ClaimKey,
+        map: &mut V,
+    ) -> Result<(), V::Error> {
+        match key {
+            ClaimKey::Int(SUBJECT) if self.sub.is_none() => {
+                match map.next_value()? {
+                    Value::Text(sub) => self.sub = Some(sub),
+                    value => self.private.push((key.into(), value)),
+                }
+                Ok(())
+            }
+            ClaimKey::Int(AUDIENCE) if self.aud.is_empty() => {
+                let value: Value = map.next_value()?;
+                match value.deserialized() {
+                    Ok(Audience::One(aud)) => self.aud = vec![aud],
+                    Ok(Audience::Many(aud)) => self.aud = aud,
+                    Err(_) => self.private.push((key.into(), value)),
+                }
+                Ok(())
+            }
+            ClaimKey::Int(CWT_ID) if self.cti.is_none() => {
+                match map.next_value()? {
+                    Value::Bytes(cti) => self.cti = Some(cti),
+                    value => self.private.push((key.into(), value)),
+                }
+                Ok(())
+            }
+            key => {
+                self.private.push((key.into(), map.next_value()?));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Every registered claim of a CWT
+/// ([RFC 8392](https://datatracker.ietf.org/doc/html/rfc8392#section-3.1)), as returned by
+/// [`DgcContainer::registered_claims`](crate::DgcContainer::registered_claims).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegisteredClaims<'a> {
+    /// The issuer (`iss`, claim 1)
+    pub iss: &'a str,
+    /// The subject (`sub`, claim 2)
+    pub sub: Option<&'a str>,
+    /// The audience (`aud`, claim 3)
+    pub aud: &'a [String],
+    /// The expiration time (`exp`, claim 4)
+    pub exp: Option<&'a IntegerOrFloat>,
+    /// The time before which the CWT must not be accepted (`nbf`, claim 5)
+    pub nbf: Option<&'a IntegerOrFloat>,
+    /// The time at which the CWT was issued (`iat`, claim 6)
+    pub iat: &'a IntegerOrFloat,
+    /// The CWT ID (`cti`, claim 7)
+    pub cti: Option<&'a [u8]>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// The key of a claim.
+///
+/// Integer keys written as text strings, as they are in JSON, are considered integers. Text
+/// keys of a binary format (CBOR) are always kept as text.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ClaimKey {
+    Int(i128),
+    Text(String),
+    Other(Value),
+}
+
+impl From<ClaimKey> for Value {
+    fn from(key: ClaimKey) -> Self {
+        match key {
+            ClaimKey::Int(key) => match Integer::try_from(key) {
+                Ok(key) => Value::Integer(key),
+                // out of the CBOR range, so it cannot come from a CWT
+                Err(_) => Value::Text(key.to_string()),
+            },
+            ClaimKey::Text(key) => Value::Text(key),
+            ClaimKey::Other(key) => key,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ClaimKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let human_readable = deserializer.is_human_readable();
+        Ok(match Value::deserialize(deserializer)? {
+            Value::Integer(key) => ClaimKey::Int(key.into()),
+            Value::Text(key) if human_readable => match key.parse() {
+                Ok(key) => ClaimKey::Int(key),
+                Err(_) => ClaimKey::Text(key),
+            },
+            Value::Text(key) => ClaimKey::Text(key),
+            key => ClaimKey::Other(key),
+        })
+    }
+}
+
+/// A CWT ID, encoded as CBOR bytes instead of an array of integers.
+pub(crate) struct CwtId<'a>(pub(crate) &'a [u8]);
+
+impl Serialize for CwtId<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_fixtures::container, DgcContainer, IntegerOrFloat};
+    use ciborium::value::Value;
+    use serde_json::json;
+
+    #[test]
+    fn it_keeps_every_claim() {
+        let container = container(json!({
+            "2": "subject",
+            "3": ["verifier", "other verifier"],
+            "4": 1630000000,
+            "5": 1620000000,
+            "-70000": { "national": true },
+            "custom": "value"
+        }));
+
+        assert_eq!(container.issuer, "IT");
+        assert!(container.expires_at.is_some() && container.not_before.is_some());
+        let claims = &container.claims;
+        assert_eq!(claims.sub.as_deref(), Some("subject"));
+        assert_eq!(claims.aud, vec!["verifier", "other verifier"]);
+        assert_eq!(claims.private.len(), 2);
+        assert!(claims.get_private(-70000).unwrap().is_map());
+        assert_eq!(
+            claims.get_private("custom").and_then(|v| v.as_text()),
+            Some("value")
+        );
+    }
+
+    #[test]
+    fn it_returns_every_registered_claim() {
+        let container = container(json!({
+            "2": "subject",
+            "3": "verifier",
+            "4": 1630000000,
+            "5": 1620000000
+        }));
+
+        let claims = container.registered_claims();
+        assert_eq!(claims.iss, "IT");
+        assert_eq!(claims.sub, Some("subject"));
+        assert_eq!(claims.aud, ["verifier"]);
+        assert_eq!(claims.exp, Some(&IntegerOrFloat::Float(1630000000.0)));
+        assert_eq!(claims.nbf, Some(&IntegerOrFloat::Float(1620000000.0)));
+        assert_eq!(claims.iat, &IntegerOrFloat::Float(1620000000.0));
+        assert_eq!(claims.cti, None);
+    }
+
+    #[test]
+    fn it_keeps_malformed_claims_as_private() {
+        let container = container(json!({ "2": 42, "3": [1, 2] }));
+
+        assert_eq!(container.claims.sub, None);
+        assert!(container.claims.aud.is_empty());
+        assert_eq!(container.claims.get_private(2), Some(&Value::from(42)));
+        assert!(container.claims.get_private(3).unwrap().is_array());
+    }
+
+    #[test]
+    fn it_keeps_cbor_text_keys_as_text() {
+        let mut container = container(json!({}));
+        container
+            .claims
+            .private
+            .push((Value::from("2"), Value::from("not the subject")));
+
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&container, &mut cbor).unwrap();
+        let decoded: DgcContainer = ciborium::de::from_reader(cbor.as_slice()).unwrap();
+
+        assert_eq!(decoded.claims.sub, None);
+        assert_eq!(
+            decoded.claims.get_private("2"),
+            Some(&Value::from("not the subject"))
+        );
+        assert_eq!(decoded, container);
+    }
+
+    #[test]
+    fn it_preserves_the_claims_through_cbor() {
+        let mut container = container(json!({ "3": "verifier" }));
+        container.claims.cti = Some(vec![1, 2, 3]);
+        container
+            .claims
+            .private
+            .push((Value::from(-70000), Value::from(42)));
+
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&container, &mut cbor).unwrap();
+        let decoded: DgcContainer = ciborium::de::from_reader(cbor.as_slice()).unwrap();
+
+        assert_eq!(decoded, container);
+        assert_eq!(decoded.claims.aud, vec!["verifier"]);
+        assert_eq!(decoded.claims.cti, Some(vec![1, 2, 3]));
+        assert_eq!(decoded.claims.get_private(-70000), Some(&Value::from(42)));
+    }
+}
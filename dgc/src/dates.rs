@@ -0,0 +1,217 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use std::fmt;
+use thiserror::Error;
+
+/// Represents the possible failures when converting the textual dates and the timestamps
+/// contained in a certificate to typed values.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum DateParseError {
+    /// The value is not a valid ISO 8601 date
+    #[error("Invalid date '{0}'")]
+    InvalidDate(String),
+    /// The value is not a valid ISO 8601 date and time
+    #[error("Invalid date and time '{0}'")]
+    InvalidDateTime(String),
+    /// The value cannot be represented as a unix timestamp
+    #[error("Invalid timestamp '{0}'")]
+    InvalidTimestamp(String),
+}
+
+/// A date that can be only partially known, like the date of birth of a person.
+///
+/// The specification allows a date of birth to contain only the year (`1977`),
+/// the year and the month (`1977-06`) or to be empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PartialDate {
+    /// The date is completely unknown
+    Empty,
+    /// Only the year is known
+    Year(i32),
+    /// Only the year and the month (1-12) are known
+    YearMonth(i32, u32),
+    /// The date is completely known
+    Complete(NaiveDate),
+}
+
+impl PartialDate {
+    /// The year of the date, if known
+    pub fn year(&self) -> Option<i32> {
+        match self {
+            PartialDate::Empty => None,
+            PartialDate::Year(year) | PartialDate::YearMonth(year, _) => Some(*year),
+            PartialDate::Complete(date) => Some(chrono::Datelike::year(date)),
+        }
+    }
+}
+
+impl fmt::Display for PartialDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartialDate::Empty => Ok(()),
+            PartialDate::Year(year) => write!(f, "{:04}", year),
+            PartialDate::YearMonth(year, month) => write!(f, "{:04}-{:02}", year, month),
+            PartialDate::Complete(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+        }
+    }
+}
+
+/// Parses a complete date (`YYYY-MM-DD`).
+///
+/// Some issuers add a time to the date (`YYYY-MM-DDThh:mm:ss`), in which case the time is ignored.
+pub(crate) fn parse_date(value: &str) -> Result<NaiveDate, DateParseError> {
+    let date = match value.split_once('T') {
+        Some((date, _)) => date,
+        None => value,
+    };
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| DateParseError::InvalidDate(value.to_string()))
+}
+
+/// Parses a date that can be partially specified (`YYYY`, `YYYY-MM`, `YYYY-MM-DD` or empty).
+///
+/// Months and days set to `00` or `XX` are considered unknown.
+pub(crate) fn parse_partial_date(value: &str) -> Result<PartialDate, DateParseError> {
+    let invalid = || DateParseError::InvalidDate(value.to_string());
+    let date = match value.split_once('T') {
+        Some((date, _)) => date,
+        None => value,
+    };
+    if date.is_empty() {
+        return Ok(PartialDate::Empty);
+    }
+
+    let mut parts = date.split('-');
+    let year = parts
+        .next()
+        .filter(|year| year.len() == 4)
+        .and_then(|year| year.parse::<i32>().ok())
+        .ok_or_else(invalid)?;
+    let month = parse_optional_component(parts.next(), 12).map_err(|_| invalid())?;
+    let day = parse_optional_component(parts.next(), 31).map_err(|_| invalid())?;
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+
+    match (month, day) {
+        (None, _) => Ok(PartialDate::Year(year)),
+        (Some(month), None) => Ok(PartialDate::YearMonth(year, month)),
+        (Some(month), Some(day)) => NaiveDate::from_ymd_opt(year, month, day)
+            .map(PartialDate::Complete)
+            .ok_or_else(invalid),
+    }
+}
+
+fn parse_optional_component(component: Option<&str>, max: u32) -> Result<Option<u32>, ()> {
+    match component {
+        None | Some("00") | Some("XX") => Ok(None),
+        Some(component) if component.len() == 2 => match component.parse() {
+            Ok(value) if value <= max => Ok(Some(value)),
+            _ => Err(()),
+        },
+        Some(_) => Err(()),
+    }
+}
+
+/// Parses an ISO 8601 date and time, converting it to UTC.
+///
+/// Both `Z` and numeric offsets (`+00:00` or `+0000`) are supported. When the offset is missing
+/// the time is assumed to be in UTC.
+pub(crate) fn parse_datetime(value: &str) -> Result<DateTime<Utc>, DateParseError> {
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f%z"))
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                .map(|datetime| datetime.and_utc())
+        })
+        .map_err(|_| DateParseError::InvalidDateTime(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn it_parses_partial_dates() {
+        assert_eq!(parse_partial_date(""), Ok(PartialDate::Empty));
+        assert_eq!(parse_partial_date("1977"), Ok(PartialDate::Year(1977)));
+        assert_eq!(
+            parse_partial_date("1977-06"),
+            Ok(PartialDate::YearMonth(1977, 6))
+        );
+        assert_eq!(
+            parse_partial_date("1977-06-21"),
+            Ok(PartialDate::Complete(
+                NaiveDate::from_ymd_opt(1977, 6, 21).unwrap()
+            ))
+        );
+        assert_eq!(parse_partial_date("1963-00"), Ok(PartialDate::Year(1963)));
+        assert_eq!(
+            parse_partial_date("1963-XX-XX"),
+            Ok(PartialDate::Year(1963))
+        );
+        assert_eq!(
+            parse_partial_date("1978-01-26T00:00:00"),
+            Ok(PartialDate::Complete(
+                NaiveDate::from_ymd_opt(1978, 1, 26).unwrap()
+            ))
+        );
+        assert!(parse_partial_date("77").is_err());
+        assert!(parse_partial_date("1977-13").is_err());
+        assert!(parse_partial_date("1977-02-30").is_err());
+        assert!(parse_partial_date("1977-06-21-01").is_err());
+    }
+
+    #[test]
+    fn it_displays_partial_dates() {
+        assert_eq!(PartialDate::Empty.to_string(), "");
+        assert_eq!(PartialDate::Year(1977).to_string(), "1977");
+        assert_eq!(PartialDate::YearMonth(1977, 6).to_string(), "1977-06");
+        assert_eq!(
+            PartialDate::Complete(NaiveDate::from_ymd_opt(1977, 6, 1).unwrap()).to_string(),
+            "1977-06-01"
+        );
+    }
+
+    #[test]
+    fn it_parses_dates() {
+        let expected = NaiveDate::from_ymd_opt(2021, 5, 4).unwrap();
+        assert_eq!(parse_date("2021-05-04"), Ok(expected));
+        assert_eq!(parse_date("2021-05-04T00:00:00"), Ok(expected));
+        assert!(parse_date("2021-05").is_err());
+        assert!(parse_date("").is_err());
+    }
+
+    #[test]
+    fn it_parses_datetimes_with_any_offset() {
+        let expected = Utc.with_ymd_and_hms(2021, 6, 30, 12, 34, 56).unwrap();
+        assert_eq!(parse_datetime("2021-06-30T12:34:56Z"), Ok(expected));
+        assert_eq!(parse_datetime("2021-06-30T12:34:56+00:00"), Ok(expected));
+        assert_eq!(parse_datetime("2021-06-30T14:34:56+02:00"), Ok(expected));
+        assert_eq!(parse_datetime("2021-06-30T14:34:56+0200"), Ok(expected));
+        assert_eq!(parse_datetime("2021-06-30T12:34:56"), Ok(expected));
+        assert_eq!(
+            parse_datetime("2021-06-30T12:34:56.123Z"),
+            Ok(expected + chrono::Duration::milliseconds(123))
+        );
+        assert!(parse_datetime("2021-06-30").is_err());
+    }
+
+    #[test]
+    fn it_converts_timestamps_to_datetimes() {
+        use crate::IntegerOrFloat;
+
+        let expected = Utc.with_ymd_and_hms(2021, 6, 30, 12, 34, 56).unwrap();
+        assert_eq!(
+            IntegerOrFloat::Integer(1625056496).to_datetime(),
+            Ok(expected)
+        );
+        assert_eq!(
+            IntegerOrFloat::Float(1625056496.5).to_datetime(),
+            Ok(expected + chrono::Duration::milliseconds(500))
+        );
+        assert!(IntegerOrFloat::Float(f64::NAN).to_datetime().is_err());
+        assert!(IntegerOrFloat::Integer(u64::MAX).to_datetime().is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! Helpers to encode the few ASN.1 DER structures built by the crate.
+
+/// Encodes a DER element with the given tag and content.
+pub(crate) fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        der.push(len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len
+            .to_be_bytes()
+            .iter()
+            .copied()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        der.push(0x80 | len_bytes.len() as u8);
+        der.extend(len_bytes);
+    }
+    der.extend_from_slice(content);
+    der
+}
+
+/// Encodes a DER SEQUENCE of already encoded elements.
+pub(crate) fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    tlv(0x30, &items.concat())
+}
+
+/// Encodes an unsigned big-endian integer as a DER INTEGER.
+///
+/// Integers are minimally encoded, with a leading zero if the high bit is set.
+pub(crate) fn integer(bytes: &[u8]) -> Vec<u8> {
+    let bytes = match bytes.iter().position(|byte| *byte != 0) {
+        Some(start) => &bytes[start..],
+        None => &[0],
+    };
+    let mut content = Vec::with_capacity(bytes.len() + 1);
+    if bytes[0] & 0x80 != 0 {
+        content.push(0);
+    }
+    content.extend_from_slice(bytes);
+    tlv(0x02, &content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encodes_integers_minimally() {
+        assert_eq!(integer(&[]), vec![0x02, 0x01, 0x00]);
+        assert_eq!(integer(&[0, 0]), vec![0x02, 0x01, 0x00]);
+        assert_eq!(integer(&[0, 0x7f]), vec![0x02, 0x01, 0x7f]);
+        assert_eq!(integer(&[0x80]), vec![0x02, 0x02, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn it_encodes_long_lengths() {
+        let content = vec![0xaa; 0x100];
+        let encoded = tlv(0x04, &content);
+        assert_eq!(&encoded[..4], &[0x04, 0x82, 0x01, 0x00]);
+        assert_eq!(&encoded[4..], content.as_slice());
+        assert_eq!(sequence(&[vec![0x05, 0x00]]), vec![0x30, 0x02, 0x05, 0x00]);
+    }
+}
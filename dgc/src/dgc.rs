@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::fmt;
 
-use crate::{Recovery, Test, Vaccination};
+use crate::{dates::parse_partial_date, DateParseError, PartialDate, Recovery, Test, Vaccination};
 use serde::{Deserialize, Deserializer, Serialize};
 
 /// Contains all the info related to the subject name (forename, surname, etc.).
@@ -107,6 +107,13 @@ impl Dgc {
         self.vaccines.iter_mut().for_each(|v| v.expand_values());
         self.recoveries.iter_mut().for_each(|r| r.expand_values());
     }
+
+    /// The date of birth as a typed value.
+    ///
+    /// The date of birth can be only partially known, so this returns a [`PartialDate`].
+    pub fn parsed_date_of_birth(&self) -> Result<PartialDate, DateParseError> {
+        parse_partial_date(&self.date_of_birth)
+    }
 }
 
 #[cfg(test)]
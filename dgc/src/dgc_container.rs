@@ -1,30 +1,122 @@
-use crate::Dgc;
+use crate::{
+    cwt_claims::{
+        ClaimKey, CwtId, AUDIENCE, CWT_ID, EXPIRATION_TIME, ISSUED_AT, ISSUER, NOT_BEFORE, SUBJECT,
+    },
+    CwtClaims, DateParseError, Dgc, RegisteredClaims,
+};
+use chrono::{DateTime, Duration, Utc};
+use ciborium::value::Value;
 use serde::{
     de::{MapAccess, Visitor},
+    ser::SerializeMap,
     Deserialize, Serialize,
 };
-use std::{borrow::Cow, collections::HashMap};
+use std::{borrow::Cow, collections::HashMap, convert::TryFrom, fmt};
 
-const ISSUER: i64 = 1;
-const ISSUED_AT: i64 = 6;
-const EXPIRATION_TIME: i64 = 4;
-const CERTS: i64 = -260;
+const CERTS: i128 = -260;
+/// The key of the EU Digital COVID Certificates in the `hcert` claim
+pub(crate) const EU_DCC_V1: i128 = 1;
 
 /// The main container for one or more DGC entries.
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DgcContainer {
     /// The issuer of the data in the container
-    #[serde(rename = "1")]
     pub issuer: Cow<'static, str>,
     /// A unix timestamp representing the moment in time when the data in the container was issued
-    #[serde(rename = "6")]
     pub issued_at: IntegerOrFloat,
     /// A unix timestamp representing the moment in time when the data in the container is to be considered expired
-    #[serde(rename = "4", skip_serializing_if = "Option::is_none")]
     pub expires_at: Option<IntegerOrFloat>,
-    /// A collection of certificates embedded in the container
-    #[serde(rename = "-260")]
-    pub certs: HashMap<usize, Dgc>,
+    /// A unix timestamp representing the moment in time before which the data in the container is not to be considered valid
+    pub not_before: Option<IntegerOrFloat>,
+    /// A collection of certificates embedded in the container (the `hcert` claim), indexed by
+    /// the kind of their payload
+    pub certs: HashMap<i128, HcertPayload>,
+    /// All the other claims of the CWT, except the certificates
+    pub claims: CwtClaims,
+}
+
+/// A payload of the `hcert` claim of a container.
+///
+/// Payloads whose kind is not known are kept as raw CBOR values, so that the rest of the
+/// container (and its signature) can still be checked. An EU DCC that does not adhere to the
+/// schema is still an error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HcertPayload {
+    /// An EU Digital COVID Certificate (key 1)
+    EuDcc(Dgc),
+    /// Any other payload, as a raw CBOR value
+    Raw(Value),
+}
+
+impl HcertPayload {
+    fn from_value(key: i128, value: Value) -> Result<Self, ciborium::value::Error> {
+        match key {
+            EU_DCC_V1 => value.deserialized().map(HcertPayload::EuDcc),
+            _ => Ok(HcertPayload::Raw(value)),
+        }
+    }
+
+    /// Returns the EU Digital COVID Certificate, if this payload is one.
+    pub fn as_eu_dcc(&self) -> Option<&Dgc> {
+        match self {
+            HcertPayload::EuDcc(dgc) => Some(dgc),
+            HcertPayload::Raw(_) => None,
+        }
+    }
+
+    /// Returns the raw CBOR value, if this payload is not a decoded EU DCC.
+    pub fn as_raw(&self) -> Option<&Value> {
+        match self {
+            HcertPayload::EuDcc(_) => None,
+            HcertPayload::Raw(value) => Some(value),
+        }
+    }
+}
+
+impl Serialize for HcertPayload {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            HcertPayload::EuDcc(dgc) => dgc.serialize(serializer),
+            HcertPayload::Raw(value) => value.serialize(serializer),
+        }
+    }
+}
+
+/// The result of checking the timestamps of a container against a given moment in time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeValidity {
+    /// The container is valid at the given moment in time
+    Valid,
+    /// The container is not valid yet. Contains the moment in time when it becomes valid
+    NotYetValid(DateTime<Utc>),
+    /// The container is expired. Contains the moment in time when it expired
+    Expired(DateTime<Utc>),
+    /// The validity could not be checked because one of the timestamps is malformed
+    MalformedTimestamp(DateParseError),
+}
+
+impl fmt::Display for TimeValidity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TimeValidity::*;
+        match self {
+            Valid => write!(f, "The certificate is valid"),
+            NotYetValid(valid_from) => {
+                write!(f, "The certificate is not valid before {}", valid_from)
+            }
+            Expired(expired_at) => write!(f, "The certificate expired on {}", expired_at),
+            MalformedTimestamp(e) => write!(f, "The certificate has a malformed timestamp: {}", e),
+        }
+    }
+}
+
+impl TimeValidity {
+    /// Checks if the container is valid at the given moment in time
+    pub fn is_valid(&self) -> bool {
+        matches!(self, TimeValidity::Valid)
+    }
 }
 
 /// Represents an integer or a float value.
@@ -39,13 +131,155 @@ pub enum IntegerOrFloat {
     Integer(u64),
 }
 
+impl IntegerOrFloat {
+    /// Converts the unix timestamp to a UTC date and time
+    pub fn to_datetime(&self) -> Result<DateTime<Utc>, DateParseError> {
+        let datetime = match *self {
+            IntegerOrFloat::Integer(timestamp) => i64::try_from(timestamp)
+                .ok()
+                .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+            IntegerOrFloat::Float(timestamp) if timestamp.is_finite() => {
+                let seconds = timestamp.floor();
+                let nanos = ((timestamp - seconds) * 1e9) as u32;
+                DateTime::from_timestamp(seconds as i64, nanos)
+            }
+            IntegerOrFloat::Float(_) => None,
+        };
+        datetime.ok_or_else(|| DateParseError::InvalidTimestamp(self.to_string()))
+    }
+}
+
+impl fmt::Display for IntegerOrFloat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegerOrFloat::Float(value) => write!(f, "{}", value),
+            IntegerOrFloat::Integer(value) => write!(f, "{}", value),
+        }
+    }
+}
+
 impl DgcContainer {
     /// Updates all the ids in all the entries with their descriptive counterparts using
     /// the official valueset.
     ///
     /// Useful shortcut to print all the details in a more descriptive way.
     pub fn expand_values(&mut self) {
-        self.certs.iter_mut().for_each(|(_, t)| t.expand_values());
+        self.certs.values_mut().for_each(|payload| {
+            if let HcertPayload::EuDcc(dgc) = payload {
+                dgc.expand_values()
+            }
+        });
+    }
+
+    /// Iterates over the EU Digital COVID Certificates of the container, skipping the payloads
+    /// of other kinds.
+    pub fn eu_dccs(&self) -> impl Iterator<Item = &Dgc> {
+        self.certs.values().filter_map(HcertPayload::as_eu_dcc)
+    }
+
+    /// Returns every registered claim of the CWT, including the issuer and the timestamps that
+    /// are fields of the container.
+    pub fn registered_claims(&self) -> RegisteredClaims<'_> {
+        RegisteredClaims {
+            iss: &self.issuer,
+            sub: self.claims.sub.as_deref(),
+            aud: &self.claims.aud,
+            exp: self.expires_at.as_ref(),
+            nbf: self.not_before.as_ref(),
+            iat: &self.issued_at,
+            cti: self.claims.cti.as_deref(),
+        }
+    }
+
+    /// The moment in time before which the data in the container is not to be considered valid (if present)
+    pub fn parsed_not_before(&self) -> Result<Option<DateTime<Utc>>, DateParseError> {
+        self.not_before
+            .as_ref()
+            .map(IntegerOrFloat::to_datetime)
+            .transpose()
+    }
+
+    /// Checks the issue time (`iat`), the expiration time (`exp`) and, if present, the
+    /// "not before" time (`nbf`) of the container against the given moment in time.
+    ///
+    /// `clock_skew` is the tolerance applied to every comparison, to account for
+    /// clocks that are not perfectly in sync between issuers and verifiers.
+    pub fn time_validity(&self, now: DateTime<Utc>, clock_skew: Duration) -> TimeValidity {
+        let (issued_at, not_before, expires_at) = match (
+            self.parsed_issued_at(),
+            self.parsed_not_before(),
+            self.parsed_expires_at(),
+        ) {
+            (Ok(issued_at), Ok(not_before), Ok(expires_at)) => (issued_at, not_before, expires_at),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                return TimeValidity::MalformedTimestamp(e)
+            }
+        };
+
+        let valid_from = not_before.map_or(issued_at, |not_before| not_before.max(issued_at));
+        if now + clock_skew < valid_from {
+            return TimeValidity::NotYetValid(valid_from);
+        }
+        match expires_at {
+            Some(expires_at) if now - clock_skew >= expires_at => TimeValidity::Expired(expires_at),
+            _ => TimeValidity::Valid,
+        }
+    }
+
+    /// The moment in time when the data in the container was issued
+    pub fn parsed_issued_at(&self) -> Result<DateTime<Utc>, DateParseError> {
+        self.issued_at.to_datetime()
+    }
+
+    /// The moment in time when the data in the container is to be considered expired (if present)
+    pub fn parsed_expires_at(&self) -> Result<Option<DateTime<Utc>>, DateParseError> {
+        self.expires_at
+            .as_ref()
+            .map(IntegerOrFloat::to_datetime)
+            .transpose()
+    }
+}
+
+/// Needs a specialized serializer to be able to write keys as integers
+impl Serialize for DgcContainer {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let claims = &self.claims;
+        let len = 3
+            + self.expires_at.is_some() as usize
+            + self.not_before.is_some() as usize
+            + claims.sub.is_some() as usize
+            + !claims.aud.is_empty() as usize
+            + claims.cti.is_some() as usize
+            + claims.private.len();
+        let mut map = serializer.serialize_map(Some(len))?;
+        map.serialize_entry(&ISSUER, &self.issuer)?;
+        map.serialize_entry(&ISSUED_AT, &self.issued_at)?;
+        if let Some(expires_at) = &self.expires_at {
+            map.serialize_entry(&EXPIRATION_TIME, expires_at)?;
+        }
+        if let Some(not_before) = &self.not_before {
+            map.serialize_entry(&NOT_BEFORE, not_before)?;
+        }
+        if let Some(subject) = &claims.sub {
+            map.serialize_entry(&SUBJECT, subject)?;
+        }
+        match &claims.aud[..] {
+            [] => {}
+            // a single audience is written as a text string
+            [audience] => map.serialize_entry(&AUDIENCE, audience)?,
+            audience => map.serialize_entry(&AUDIENCE, audience)?,
+        }
+        if let Some(cwt_id) = &claims.cti {
+            map.serialize_entry(&CWT_ID, &CwtId(cwt_id))?;
+        }
+        for (key, value) in &claims.private {
+            map.serialize_entry(key, value)?;
+        }
+        map.serialize_entry(&CERTS, &self.certs)?;
+        map.end()
     }
 }
 
@@ -62,40 +296,44 @@ impl<'de> Visitor<'de> for DgcContainerVisitor {
     where
         V: MapAccess<'de>,
     {
+        fn set<T, E: serde::de::Error>(
+            field: &mut Option<T>,
+            value: T,
+            name: &'static str,
+        ) -> Result<(), E> {
+            if field.is_some() {
+                return Err(E::duplicate_field(name));
+            }
+            *field = Some(value);
+            Ok(())
+        }
+
+        let mut claims = CwtClaims::default();
         let mut issuer = None;
         let mut issued_at = None;
-        let mut expiration_time = None;
+        let mut expires_at = None;
+        let mut not_before = None;
         let mut certs = None;
 
         while let Some(key) = map.next_key()? {
             match key {
-                ISSUER => {
-                    if issuer.is_some() {
-                        return Err(serde::de::Error::duplicate_field("issuer"));
-                    }
-                    issuer = Some(map.next_value()?);
-                }
-                ISSUED_AT => {
-                    if issued_at.is_some() {
-                        return Err(serde::de::Error::duplicate_field("issued_at"));
-                    }
-                    issued_at = Some(map.next_value()?);
-                }
-                EXPIRATION_TIME => {
-                    if expiration_time.is_some() {
-                        return Err(serde::de::Error::duplicate_field("expiration_time"));
-                    }
-                    expiration_time = Some(map.next_value()?);
-                }
-                CERTS => {
+                ClaimKey::Int(ISSUER) => set(&mut issuer, map.next_value::<String>()?, "iss")?,
+                ClaimKey::Int(ISSUED_AT) => set(&mut issued_at, map.next_value()?, "iat")?,
+                ClaimKey::Int(EXPIRATION_TIME) => set(&mut expires_at, map.next_value()?, "exp")?,
+                ClaimKey::Int(NOT_BEFORE) => set(&mut not_before, map.next_value()?, "nbf")?,
+                ClaimKey::Int(CERTS) => {
                     if certs.is_some() {
                         return Err(serde::de::Error::duplicate_field("certs"));
                     }
-                    certs = Some(map.next_value()?);
-                }
-                _ => {
-                    // ignore other fields
+                    let raw_payloads: HashMap<i128, Value> = map.next_value()?;
+                    let payloads = raw_payloads
+                        .into_iter()
+                        .map(|(key, value)| Ok((key, HcertPayload::from_value(key, value)?)))
+                        .collect::<Result<_, ciborium::value::Error>>()
+                        .map_err(serde::de::Error::custom)?;
+                    certs = Some(payloads);
                 }
+                key => claims.read_claim(key, &mut map)?,
             }
         }
         let issuer = issuer.ok_or_else(|| serde::de::Error::missing_field("issuer"))?;
@@ -103,10 +341,12 @@ impl<'de> Visitor<'de> for DgcContainerVisitor {
         let certs = certs.ok_or_else(|| serde::de::Error::missing_field("certs"))?;
 
         Ok(DgcContainer {
-            issuer,
+            issuer: issuer.into(),
             issued_at,
-            expires_at: expiration_time,
+            expires_at,
+            not_before,
             certs,
+            claims,
         })
     }
 }
@@ -120,3 +360,143 @@ impl<'de> Deserialize<'de> for DgcContainer {
         deserializer.deserialize_map(DgcContainerVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use chrono::TimeZone;
+
+    fn container(issued_at: u64, not_before: Option<u64>, expires_at: Option<u64>) -> DgcContainer {
+        DgcContainer {
+            issuer: "IT".into(),
+            issued_at: IntegerOrFloat::Integer(issued_at),
+            expires_at: expires_at.map(IntegerOrFloat::Integer),
+            not_before: not_before.map(IntegerOrFloat::Integer),
+            certs: HashMap::new(),
+            claims: CwtClaims::default(),
+        }
+    }
+
+    #[test]
+    fn it_checks_the_time_validity() {
+        // 2021-06-01T00:00:00Z and 2021-12-01T00:00:00Z
+        let container = container(1622505600, None, Some(1638316800));
+        let issued_at = Utc.timestamp_opt(1622505600, 0).unwrap();
+        let expires_at = Utc.timestamp_opt(1638316800, 0).unwrap();
+        let no_skew = Duration::zero();
+
+        assert_eq!(
+            container.time_validity(issued_at, no_skew),
+            TimeValidity::Valid
+        );
+        assert_eq!(
+            container.time_validity(issued_at - Duration::seconds(1), no_skew),
+            TimeValidity::NotYetValid(issued_at)
+        );
+        assert_eq!(
+            container.time_validity(expires_at - Duration::seconds(1), no_skew),
+            TimeValidity::Valid
+        );
+        assert_eq!(
+            container.time_validity(expires_at, no_skew),
+            TimeValidity::Expired(expires_at)
+        );
+    }
+
+    #[test]
+    fn it_applies_the_clock_skew() {
+        let container = container(1622505600, None, Some(1638316800));
+        let issued_at = Utc.timestamp_opt(1622505600, 0).unwrap();
+        let expires_at = Utc.timestamp_opt(1638316800, 0).unwrap();
+        let skew = Duration::minutes(5);
+
+        assert!(container
+            .time_validity(issued_at - Duration::minutes(4), skew)
+            .is_valid());
+        assert!(container
+            .time_validity(expires_at + Duration::minutes(4), skew)
+            .is_valid());
+        assert!(!container
+            .time_validity(expires_at + Duration::minutes(5), skew)
+            .is_valid());
+    }
+
+    #[test]
+    fn it_uses_not_before_when_present() {
+        let container = container(1622505600, Some(1622592000), None);
+        let not_before = Utc.timestamp_opt(1622592000, 0).unwrap();
+
+        assert_eq!(
+            container.time_validity(not_before - Duration::seconds(1), Duration::zero()),
+            TimeValidity::NotYetValid(not_before)
+        );
+        // without an expiration time the container never expires
+        assert_eq!(
+            container.time_validity(
+                Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap(),
+                Duration::zero()
+            ),
+            TimeValidity::Valid
+        );
+    }
+
+    #[test]
+    fn it_reports_malformed_timestamps() {
+        let mut container = container(1622505600, None, None);
+        container.expires_at = Some(IntegerOrFloat::Float(f64::INFINITY));
+
+        assert!(matches!(
+            container.time_validity(Utc.timestamp_opt(1622505600, 0).unwrap(), Duration::zero()),
+            TimeValidity::MalformedTimestamp(DateParseError::InvalidTimestamp(_))
+        ));
+    }
+
+    #[test]
+    fn it_serializes_and_deserializes_not_before() {
+        let container = container(1622505600, Some(1622592000), Some(1638316800));
+        let json = serde_json::to_string(&container).unwrap();
+        assert_eq!(
+            json,
+            "{\"1\":\"IT\",\"6\":1622505600,\"4\":1638316800,\"5\":1622592000,\"-260\":{}}"
+        );
+        let deserialized: DgcContainer = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            deserialized.parsed_not_before().unwrap(),
+            container.parsed_not_before().unwrap()
+        );
+    }
+
+    #[test]
+    fn it_keeps_unknown_payloads_as_raw_values() {
+        let container = test_fixtures::container(serde_json::json!({
+            "-260": {
+                "1": {
+                    "ver": "1.3.0",
+                    "nam": { "fnt": "ROSSI" },
+                    "dob": "1970-01-01"
+                },
+                "2": { "national": "payload" }
+            }
+        }));
+
+        assert_eq!(container.certs.len(), 2);
+        assert_eq!(container.eu_dccs().count(), 1);
+        let raw = container.certs[&2].as_raw().unwrap();
+        assert!(raw.is_map());
+        assert!(container.certs[&2].as_eu_dcc().is_none());
+
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&container, &mut cbor).unwrap();
+        let decoded: DgcContainer = ciborium::de::from_reader(cbor.as_slice()).unwrap();
+        assert_eq!(decoded.certs, container.certs);
+    }
+
+    #[test]
+    fn it_rejects_malformed_eu_dccs() {
+        let result: Result<DgcContainer, _> = serde_json::from_value(test_fixtures::payload(
+            serde_json::json!({ "-260": { "1": { "nam": "not a name" } } }),
+        ));
+        assert!(result.is_err());
+    }
+}
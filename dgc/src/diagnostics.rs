@@ -0,0 +1,491 @@
+use crate::{
+    parse::decode_stages, zlib::has_zlib_header, Cwt, CwtParseError, Leniency, ParseError,
+};
+use ciborium::value::Value;
+use std::{fmt, fmt::Write};
+
+const BASE45_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// A stage of the decoding pipeline of a certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeStage {
+    /// Checking and removing the `HC1:` prefix
+    Prefix,
+    /// Decoding the base45 string
+    Base45,
+    /// Decompressing the zlib data
+    Decompression,
+    /// Parsing the COSE message (tags, structure and headers)
+    Cose,
+    /// Parsing the CWT claims in the COSE payload
+    Payload,
+}
+
+impl fmt::Display for DecodeStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DecodeStage::*;
+        match self {
+            Prefix => write!(f, "prefix"),
+            Base45 => write!(f, "base45"),
+            Decompression => write!(f, "decompression"),
+            Cose => write!(f, "COSE"),
+            Payload => write!(f, "payload"),
+        }
+    }
+}
+
+/// Where and why decoding a certificate failed.
+#[derive(Debug)]
+pub struct DecodeFailure {
+    /// The stage that failed
+    pub stage: DecodeStage,
+    /// The byte offset of the error, when it is known, in the input of the failing stage: the
+    /// raw input (without the whitespace removed by lenient decoding) for the
+    /// [`Prefix`](DecodeStage::Prefix) and [`Base45`](DecodeStage::Base45) stages, the base45 decoded bytes for [`Decompression`](DecodeStage::Decompression), the
+    /// COSE bytes for [`Cose`](DecodeStage::Cose) and the payload bytes for
+    /// [`Payload`](DecodeStage::Payload)
+    pub offset: Option<usize>,
+    /// The error returned by the stage
+    pub error: ParseError,
+}
+
+impl fmt::Display for DecodeFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(
+                f,
+                "{} stage failed at byte {}: {}",
+                self.stage, offset, self.error
+            ),
+            None => write!(f, "{} stage failed: {}", self.stage, self.error),
+        }
+    }
+}
+
+/// Every intermediate artefact of decoding a certificate, as returned by [`decode_diagnostics`].
+///
+/// The artefacts of the stages after the failing one (if any) are not available.
+#[derive(Debug)]
+pub struct DecodeDiagnostics {
+    /// The raw input
+    pub input: String,
+    /// The prefix found at the start of the input
+    pub prefix: Option<String>,
+    /// The base45 decoded bytes
+    pub base45_bytes: Option<Vec<u8>>,
+    /// Whether the base45 decoded bytes start with a zlib header
+    pub zlib: Option<bool>,
+    /// The decompressed COSE message
+    pub cose_bytes: Option<Vec<u8>>,
+    /// The protected header in CBOR diagnostic notation
+    pub protected_header: Option<String>,
+    /// The unprotected header in CBOR diagnostic notation
+    pub unprotected_header: Option<String>,
+    /// The payload in CBOR diagnostic notation
+    pub payload: Option<String>,
+    /// The decoded CWT, if every stage succeeded
+    pub cwt: Option<Cwt>,
+    /// The leniencies applied by [`decode_diagnostics_lenient`], in the order they were applied
+    pub leniencies: Vec<Leniency>,
+    /// The stage that failed, if any
+    pub failure: Option<DecodeFailure>,
+}
+
+impl DecodeDiagnostics {
+    fn new(input: &str) -> Self {
+        DecodeDiagnostics {
+            input: input.to_string(),
+            prefix: None,
+            base45_bytes: None,
+            zlib: None,
+            cose_bytes: None,
+            protected_header: None,
+            unprotected_header: None,
+            payload: None,
+            cwt: None,
+            leniencies: vec![],
+            failure: None,
+        }
+    }
+
+    /// Checks if the certificate was decoded successfully
+    pub fn is_ok(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Decodes a certificate like [`decode_cwt`](crate::decode_cwt), but instead of stopping at the
+/// first error it returns every intermediate artefact of the pipeline, together with the stage
+/// and the byte offset where decoding failed.
+///
+/// This is meant to explain why a certificate cannot be read, so it never returns an error.
+///
+/// ```rust
+/// let diagnostics = dgc::decode_diagnostics("HC1:NCF%");
+/// let failure = diagnostics.failure.unwrap();
+/// assert_eq!(failure.stage, dgc::DecodeStage::Base45);
+/// assert_eq!(failure.offset, Some(7));
+/// ```
+pub fn decode_diagnostics(data: &str) -> DecodeDiagnostics {
+    diagnose(data, false)
+}
+
+/// Decodes a certificate like [`decode_diagnostics`], tolerating the same deviations from the
+/// specification as [`decode_cwt_lenient`](crate::decode_cwt_lenient).
+///
+/// The leniencies that were applied are listed in
+/// [`leniencies`](DecodeDiagnostics::leniencies), and the offsets in the input of the
+/// [`Prefix`](DecodeStage::Prefix) and [`Base45`](DecodeStage::Base45) stages are relative to
+/// the input without the removed whitespace.
+pub fn decode_diagnostics_lenient(data: &str) -> DecodeDiagnostics {
+    diagnose(data, true)
+}
+
+fn diagnose(data: &str, lenient: bool) -> DecodeDiagnostics {
+    let (artefacts, result) = decode_stages(data, lenient);
+    let mut diagnostics = DecodeDiagnostics::new(data);
+    let input = &artefacts.input;
+
+    diagnostics.prefix = artefacts
+        .prefix_len
+        .map(|prefix_len| input[..prefix_len].to_string());
+    diagnostics.zlib = artefacts.base45_bytes.as_deref().map(has_zlib_header);
+    if let Some(cose) = &artefacts.cose_bytes {
+        describe_cose(&mut diagnostics, cose);
+    }
+    let offset = match &result {
+        Ok(_) => None,
+        Err((DecodeStage::Prefix, _)) => Some(
+            input
+                .bytes()
+                .zip(b"HC1:")
+                .position(|(found, expected)| found != *expected)
+                .unwrap_or(input.len()),
+        ),
+        Err((DecodeStage::Base45, _)) => {
+            let prefix_len = artefacts.prefix_len.unwrap_or_default();
+            base45_error_offset(&input[prefix_len..]).map(|offset| prefix_len + offset)
+        }
+        Err((DecodeStage::Decompression, _)) => {
+            inflate_error_offset(artefacts.base45_bytes.as_deref().unwrap_or_default())
+        }
+        Err((_, ParseError::CwtDecode(CwtParseError::CborError(error))))
+        | Err((_, ParseError::CwtDecode(CwtParseError::InvalidPayload(error)))) => {
+            cbor_error_offset(error)
+        }
+        Err(_) => None,
+    };
+    diagnostics.base45_bytes = artefacts.base45_bytes;
+    diagnostics.cose_bytes = artefacts.cose_bytes;
+    diagnostics.leniencies = artefacts.leniencies;
+
+    match result {
+        Ok(cwt) => diagnostics.cwt = Some(cwt),
+        Err((stage, error)) => {
+            diagnostics.failure = Some(DecodeFailure {
+                stage,
+                offset,
+                error,
+            })
+        }
+    }
+    diagnostics
+}
+
+/// Fills the diagnostic notation of the headers and the payload, parsing the COSE message as
+/// generic CBOR so that they are available even when the message is malformed.
+fn describe_cose(diagnostics: &mut DecodeDiagnostics, cose: &[u8]) {
+    let mut message: Value = match ciborium::de::from_reader(cose) {
+        Ok(message) => message,
+        Err(_) => return,
+    };
+    // the CWT and COSE tags
+    while let Value::Tag(_, content) = message {
+        message = *content;
+    }
+    let parts = match message {
+        Value::Array(parts) => parts,
+        _ => return,
+    };
+
+    let mut parts = parts.into_iter();
+    diagnostics.protected_header = parts.next().map(|header| embedded_notation(&header));
+    diagnostics.unprotected_header = parts.next().map(|header| diagnostic_notation(&header));
+    diagnostics.payload = parts.next().map(|payload| embedded_notation(&payload));
+}
+
+/// The diagnostic notation of the CBOR data embedded in a byte string, or of the value itself
+/// if it is not a byte string containing valid CBOR.
+fn embedded_notation(value: &Value) -> String {
+    value
+        .as_bytes()
+        .and_then(|bytes| ciborium::de::from_reader::<Value, _>(bytes.as_slice()).ok())
+        .map(|embedded| diagnostic_notation(&embedded))
+        .unwrap_or_else(|| diagnostic_notation(value))
+}
+
+/// Formats a CBOR value using the diagnostic notation defined by
+/// [RFC 8949](https://datatracker.ietf.org/doc/html/rfc8949#section-8).
+pub fn diagnostic_notation(value: &Value) -> String {
+    let mut notation = String::new();
+    write_diagnostic(&mut notation, value);
+    notation
+}
+
+fn write_diagnostic(out: &mut String, value: &Value) {
+    // writing to a String cannot fail
+    match value {
+        Value::Integer(integer) => {
+            let _ = write!(out, "{}", i128::from(*integer));
+        }
+        Value::Bytes(bytes) => {
+            out.push_str("h'");
+            for byte in bytes {
+                let _ = write!(out, "{:02x}", byte);
+            }
+            out.push('\'');
+        }
+        Value::Float(float) if float.is_nan() => out.push_str("NaN"),
+        Value::Float(float) if float.is_infinite() => out.push_str(if *float > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }),
+        Value::Float(float) => {
+            let _ = write!(out, "{:?}", float);
+        }
+        // JSON strings are valid in the diagnostic notation
+        Value::Text(text) => out.push_str(&serde_json::Value::from(text.as_str()).to_string()),
+        Value::Bool(boolean) => {
+            let _ = write!(out, "{}", boolean);
+        }
+        Value::Null => out.push_str("null"),
+        Value::Tag(tag, content) => {
+            let _ = write!(out, "{}(", tag);
+            write_diagnostic(out, content);
+            out.push(')');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_diagnostic(out, item);
+            }
+            out.push(']');
+        }
+        Value::Map(entries) => {
+            out.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_diagnostic(out, key);
+                out.push_str(": ");
+                write_diagnostic(out, value);
+            }
+            out.push('}');
+        }
+        _ => out.push_str("undefined"),
+    }
+}
+
+/// Finds the offset of the first invalid character or group of characters in a base45 string.
+fn base45_error_offset(data: &str) -> Option<usize> {
+    let digits = data
+        .bytes()
+        .map(|c| {
+            BASE45_ALPHABET
+                .iter()
+                .position(|d| *d == c)
+                .map(|d| d as u32)
+        })
+        .collect::<Vec<_>>();
+    if let Some(offset) = digits.iter().position(Option::is_none) {
+        return Some(offset);
+    }
+
+    // every group of 3 characters encodes 2 bytes, a trailing group of 2 characters 1 byte
+    for (i, group) in digits.chunks(3).enumerate() {
+        let value = group
+            .iter()
+            .rev()
+            .fold(0, |value, digit| value * 45 + digit.unwrap_or_default());
+        let max = match group.len() {
+            3 => 0xffff,
+            2 => 0xff,
+            _ => 0,
+        };
+        if group.len() == 1 || value > max {
+            return Some(i * 3);
+        }
+    }
+    None
+}
+
+/// Finds the offset of the byte where inflating zlib data fails, by feeding the data one byte
+/// at a time.
+fn inflate_error_offset(data: &[u8]) -> Option<usize> {
+    let mut stream = inflate::InflateStream::from_zlib();
+    let mut offset = 0;
+    while offset < data.len() {
+        match stream.update(&data[offset..=offset]) {
+            Ok((0, [])) => return None,
+            Ok((read, _)) => offset += read,
+            Err(_) => return Some(offset),
+        }
+    }
+    None
+}
+
+fn cbor_error_offset(error: &ciborium::de::Error<std::io::Error>) -> Option<usize> {
+    match error {
+        ciborium::de::Error::Syntax(offset) => Some(*offset),
+        ciborium::de::Error::Semantic(offset, _) => *offset,
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zlib::compress;
+
+    const DATA: &str = "HC1:NCFOXN%TS3DH3ZSUZK+.V0ETD%65NL-AH-R6IOO6+IDOEZ/18WAV$E3+3AT4V22F/8X*G3M9JUPY0BX/KR96R/S09T./0LWTKD33236J3TA3M*4VV2 73-E3GG396B-43O058YIB73A*G3W19UEBY5:PI0EGSP4*2DN43U*0CEBQ/GXQFY73CIBC:G 7376BXBJBAJ UNFMJCRN0H3PQN*E33H3OA70M3FMJIJN523.K5QZ4A+2XEN QT QTHC31M3+E32R44$28A9H0D3ZCL4JMYAZ+S-A5$XKX6T2YC 35H/ITX8GL2-LH/CJTK96L6SR9MU9RFGJA6Q3QR$P2OIC0JVLA8J3ET3:H3A+2+33U SAAUOT3TPTO4UBZIC0JKQTL*QDKBO.AI9BVYTOCFOPS4IJCOT0$89NT2V457U8+9W2KQ-7LF9-DF07U$B97JJ1D7WKP/HLIJLRKF1MFHJP7NVDEBU1J*Z222E.GJI77N IKXN9+6J5DG3VWU5ZXT$ZRWP7++KM5MMUN/7UTFEEZPBK8C 7KMBI.3ZDBDREY7IM*N1KS3UI$6JD.JKLKA3UBJM-SJ9:OHBURZEF50WAQ 3";
+
+    #[test]
+    fn it_returns_every_artefact() {
+        let diagnostics = decode_diagnostics(DATA);
+
+        assert!(diagnostics.is_ok());
+        assert_eq!(diagnostics.input, DATA);
+        assert_eq!(diagnostics.prefix.as_deref(), Some("HC1:"));
+        assert!(diagnostics.base45_bytes.is_some());
+        assert_eq!(diagnostics.zlib, Some(true));
+        assert!(diagnostics.cose_bytes.unwrap().starts_with(&[0xd2, 0x84]));
+        assert_eq!(
+            diagnostics.protected_header.as_deref(),
+            Some("{4: h'd919375fc1e7b6b2', 1: -7}")
+        );
+        assert_eq!(diagnostics.unprotected_header.as_deref(), Some("{}"));
+        let payload = diagnostics.payload.unwrap();
+        assert!(
+            payload.starts_with("{4: 1624879116, 6: 1624706316, 1: \"AT\", -260: {1: {\"v\": [")
+        );
+        assert!(diagnostics.cwt.is_some());
+    }
+
+    #[test]
+    fn it_reports_an_invalid_prefix() {
+        let diagnostics = decode_diagnostics("HC2:NCFOXN");
+        let failure = diagnostics.failure.unwrap();
+        assert_eq!(failure.stage, DecodeStage::Prefix);
+        assert_eq!(failure.offset, Some(2));
+        assert_eq!(diagnostics.prefix, None);
+
+        let failure = decode_diagnostics("HC1").failure.unwrap();
+        assert!(matches!(failure.error, ParseError::NotEnoughData(3)));
+        assert_eq!(failure.offset, Some(3));
+    }
+
+    #[test]
+    fn it_reports_the_offset_of_invalid_base45() {
+        let failure = decode_diagnostics("HC1:NCFOXNabc").failure.unwrap();
+        assert_eq!(failure.stage, DecodeStage::Base45);
+        assert_eq!(failure.offset, Some(10));
+
+        // ":::" is greater than 0xffff
+        let failure = decode_diagnostics("HC1:NCF:::").failure.unwrap();
+        assert_eq!(failure.offset, Some(7));
+
+        // a single trailing character
+        let failure = decode_diagnostics("HC1:NCFO").failure.unwrap();
+        assert_eq!(failure.offset, Some(7));
+    }
+
+    #[test]
+    fn it_reports_the_offset_of_invalid_zlib_data() {
+        let mut compressed = compress(b"not a certificate");
+        let last = compressed.len() - 1;
+        compressed[last] ^= 0xff;
+        let data = format!("HC1:{}", base45::encode(&compressed));
+
+        let diagnostics = decode_diagnostics(&data);
+        assert_eq!(diagnostics.zlib, Some(true));
+        assert_eq!(diagnostics.base45_bytes, Some(compressed));
+        let failure = diagnostics.failure.unwrap();
+        assert_eq!(failure.stage, DecodeStage::Decompression);
+        assert_eq!(failure.offset, Some(last));
+
+        let data = format!("HC1:{}", base45::encode(b"\xd2\x84"));
+        let diagnostics = decode_diagnostics(&data);
+        assert_eq!(diagnostics.zlib, Some(false));
+        assert_eq!(diagnostics.failure.unwrap().offset, Some(0));
+    }
+
+    #[test]
+    fn it_describes_malformed_cose_messages() {
+        // a COSE_Sign1 message whose payload is not a CWT
+        let cose = b"\xd2\x84\x43\xa1\x01\x26\xa0\x43\xa1\x01\x02\x40";
+        let data = format!("HC1:{}", base45::encode(compress(cose)));
+
+        let diagnostics = decode_diagnostics(&data);
+        assert_eq!(diagnostics.cose_bytes.as_deref(), Some(&cose[..]));
+        assert_eq!(diagnostics.protected_header.as_deref(), Some("{1: -7}"));
+        assert_eq!(diagnostics.unprotected_header.as_deref(), Some("{}"));
+        assert_eq!(diagnostics.payload.as_deref(), Some("{1: 2}"));
+        let failure = diagnostics.failure.unwrap();
+        assert_eq!(failure.stage, DecodeStage::Payload);
+        assert!(diagnostics.cwt.is_none());
+
+        // truncated CBOR
+        let data = format!("HC1:{}", base45::encode(compress(&cose[..4])));
+        let failure = decode_diagnostics(&data).failure.unwrap();
+        assert_eq!(failure.stage, DecodeStage::Cose);
+    }
+
+    #[test]
+    fn it_decodes_leniently() {
+        let wrapped = format!(" {}\n{}", &DATA[..40], &DATA[40..]);
+        let diagnostics = decode_diagnostics(&wrapped);
+        assert_eq!(diagnostics.failure.unwrap().stage, DecodeStage::Prefix);
+
+        let diagnostics = decode_diagnostics_lenient(&wrapped);
+        assert!(diagnostics.is_ok());
+        assert_eq!(diagnostics.input, wrapped);
+        assert_eq!(diagnostics.leniencies, vec![Leniency::StrippedWhitespace]);
+
+        let cose = b"\xd2\x84\x43\xa1\x01\x26\xa0\x43\xa1\x01\x02\x40";
+        let data = format!("HC1:{}\n", base45::encode(cose));
+        let diagnostics = decode_diagnostics_lenient(&data);
+        assert_eq!(diagnostics.zlib, Some(false));
+        assert_eq!(diagnostics.cose_bytes.as_deref(), Some(&cose[..]));
+        assert_eq!(
+            diagnostics.leniencies,
+            vec![Leniency::StrippedWhitespace, Leniency::Uncompressed]
+        );
+        let failure = diagnostics.failure.unwrap();
+        assert_eq!(failure.stage, DecodeStage::Payload);
+    }
+
+    #[test]
+    fn it_formats_the_diagnostic_notation() {
+        let value = Value::Tag(
+            1,
+            Box::new(Value::Array(vec![
+                Value::Bytes(vec![0x01, 0xab]),
+                Value::from("a \"quoted\" text"),
+                Value::Float(1.0),
+                Value::Float(f64::NEG_INFINITY),
+                Value::Bool(true),
+                Value::Null,
+                Value::Map(vec![(Value::from(-1), Value::Array(vec![]))]),
+            ])),
+        );
+        assert_eq!(
+            diagnostic_notation(&value),
+            r#"1([h'01ab', "a \"quoted\" text", 1.0, -Infinity, true, null, {-1: []}])"#
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    cwt::{make_sig_structure, COSE_HEADER_KEY_ALG, COSE_HEADER_KEY_KID, COSE_SIGN1_CBOR_TAG},
+    zlib, DgcContainer, EcAlg,
+};
+use ciborium::{ser::into_writer, value::Value};
+use ring::{
+    rand::SystemRandom,
+    signature::{self, EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair},
+};
+use std::{convert::TryInto, fmt};
+use thiserror::Error;
+
+/// Represents all the possible types of failures that can occur when encoding a certificate.
+#[derive(Error, Debug)]
+pub enum EncodeError {
+    /// The given key cannot be used with the given signing algorithm
+    #[error("Invalid signing key: {0}")]
+    InvalidKey(String),
+    /// The signing algorithm is not supported for signing by this library
+    #[error("The signature algorithm '{0:?}' is not supported for signing")]
+    UnsupportedSigningAlgorithm(EcAlg),
+    /// The data could not be serialized as CBOR
+    #[error("Cannot serialize the data as CBOR: {0}")]
+    CborError(String),
+    /// The signature could not be computed
+    #[error("Failed to sign the data")]
+    SigningError,
+}
+
+enum KeyPairKind {
+    Ecdsa(EcdsaKeyPair),
+    Rsa(RsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+}
+
+/// A private key that can be used to sign new certificates.
+pub struct SigningKey {
+    alg: EcAlg,
+    key_pair: KeyPairKind,
+    rng: SystemRandom,
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("alg", &self.alg)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    /// Loads a private key encoded as PKCS#8 (DER) to be used with the given signing algorithm.
+    ///
+    /// Supported algorithms are ES256, ES384, PS256, PS384, PS512 and EdDSA (Ed25519).
+    pub fn from_pkcs8(alg: EcAlg, pkcs8: &[u8]) -> Result<Self, EncodeError> {
+        let invalid_key = |e: ring::error::KeyRejected| EncodeError::InvalidKey(e.to_string());
+        let key_pair = match alg {
+            EcAlg::Es256 => KeyPairKind::Ecdsa(
+                EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8)
+                    .map_err(invalid_key)?,
+            ),
+            EcAlg::Es384 => KeyPairKind::Ecdsa(
+                EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P384_SHA384_FIXED_SIGNING, pkcs8)
+                    .map_err(invalid_key)?,
+            ),
+            EcAlg::Ps256 | EcAlg::Ps384 | EcAlg::Ps512 => {
+                KeyPairKind::Rsa(RsaKeyPair::from_pkcs8(pkcs8).map_err(invalid_key)?)
+            }
+            EcAlg::EdDsa => KeyPairKind::Ed25519(
+                Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8).map_err(invalid_key)?,
+            ),
+            EcAlg::Es512 | EcAlg::Unknown(_) => {
+                return Err(EncodeError::UnsupportedSigningAlgorithm(alg))
+            }
+        };
+
+        Ok(SigningKey {
+            alg,
+            key_pair,
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// The signing algorithm used by this key
+    pub fn alg(&self) -> EcAlg {
+        self.alg
+    }
+
+    /// Returns the raw public key matching this private key, in the same format
+    /// used by [`TrustList`](crate::TrustList).
+    pub fn public_key(&self) -> &[u8] {
+        match &self.key_pair {
+            KeyPairKind::Ecdsa(key_pair) => key_pair.public_key().as_ref(),
+            KeyPairKind::Rsa(key_pair) => key_pair.public_key().as_ref(),
+            KeyPairKind::Ed25519(key_pair) => key_pair.public_key().as_ref(),
+        }
+    }
+
+    pub(crate) fn sign(&self, data: &[u8]) -> Result<Vec<u8>, EncodeError> {
+        match &self.key_pair {
+            KeyPairKind::Ecdsa(key_pair) => key_pair
+                .sign(&self.rng, data)
+                .map(|signature| signature.as_ref().to_vec())
+                .map_err(|_| EncodeError::SigningError),
+            KeyPairKind::Rsa(key_pair) => {
+                let padding: &'static dyn signature::RsaEncoding = match self.alg {
+                    EcAlg::Ps384 => &signature::RSA_PSS_SHA384,
+                    EcAlg::Ps512 => &signature::RSA_PSS_SHA512,
+                    _ => &signature::RSA_PSS_SHA256,
+                };
+                let mut signature = vec![0; key_pair.public_modulus_len()];
+                key_pair
+                    .sign(padding, &self.rng, data, &mut signature)
+                    .map_err(|_| EncodeError::SigningError)?;
+                Ok(signature)
+            }
+            KeyPairKind::Ed25519(key_pair) => Ok(key_pair.sign(data).as_ref().to_vec()),
+        }
+    }
+}
+
+fn to_cbor(value: &impl serde::Serialize) -> Result<Vec<u8>, EncodeError> {
+    let mut data = vec![];
+    into_writer(value, &mut data).map_err(|e| EncodeError::CborError(e.to_string()))?;
+    Ok(data)
+}
+
+fn make_cose_sign1(
+    container: &DgcContainer,
+    key: &SigningKey,
+    kid: &[u8],
+) -> Result<Vec<u8>, EncodeError> {
+    let header_protected = Value::Map(vec![
+        (
+            Value::Integer(COSE_HEADER_KEY_ALG.try_into().unwrap()),
+            Value::Integer(i128::from(&key.alg).try_into().unwrap()),
+        ),
+        (
+            Value::Integer(COSE_HEADER_KEY_KID.try_into().unwrap()),
+            Value::Bytes(kid.to_vec()),
+        ),
+    ]);
+    let header_protected_raw = to_cbor(&header_protected)?;
+    let payload_raw = to_cbor(container)?;
+
+    let signature = key.sign(&make_sig_structure(&header_protected_raw, &payload_raw))?;
+
+    let cose_sign1 = Value::Tag(
+        COSE_SIGN1_CBOR_TAG,
+        Box::new(Value::Array(vec![
+            Value::Bytes(header_protected_raw),
+            Value::Map(vec![]),
+            Value::Bytes(payload_raw),
+            Value::Bytes(signature),
+        ])),
+    );
+    to_cbor(&cose_sign1)
+}
+
+/// Signs and encodes a certificate, producing the text that is usually embedded in a QR code.
+///
+/// This is the inverse of [`validate`](crate::validate): the container is serialized as CBOR,
+/// signed with the given key in a COSE_Sign1 structure (with `alg` and `kid` in the protected
+/// header), zlib compressed, base45 encoded and finally prefixed with `HC1:`.
+///
+/// ## Example
+///
+/// ```
+/// use ring::{rand::SystemRandom, signature};
+///
+/// let container = dgc::decode("HC1:NCF:603A0T9WTWGSLKC 4K694WJN.0J$6C-7WAB0XK3JCSGA2F3R8PP4V2F35VPP.EY50.FK8ZKO/EZKEZ96LF6/A6..DV%DZJC0/D5UA QELPCG/DYUCHY83UAGVC*JCNF6F463W5KF6VF6IECSHG4KCD3DX47B46IL6646H*6MWEWJDA6A:961A6Q47EM6B$DFOC0R63KCZPCNF6OF63W5$Q6+96/SA5R6NF61G73564KC*KETF6A46.96646B565WEC.D1$CKWEDZC6VCS446$C4WEUPC3JCUIA+ED$.EF$DMWE8$CBJEMVCB445$CBWER.CGPC4WEOPCE8FHZA1+9LZAZM81G72A62+8OG7J09U47AB8V59T%6ZHBO57X48RUIY03XQOK*FZUNM UFY4D5C S3R9UW-2R*4KZJT5M MIM:03RMZNA LKTO34PA.H51966PS0KAP-KLPH.Q6$KSTJ0-G658RL5HR1")
+///     .expect("Cannot parse certificate data");
+///
+/// // A freshly generated key, in a real scenario this would be loaded from a file
+/// let pkcs8 = signature::EcdsaKeyPair::generate_pkcs8(
+///     &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+///     &SystemRandom::new(),
+/// )
+/// .unwrap();
+/// let key = dgc::SigningKey::from_pkcs8(dgc::EcAlg::Es256, pkcs8.as_ref()).unwrap();
+/// let kid = [1, 2, 3, 4, 5, 6, 7, 8];
+///
+/// let raw_certificate_data = dgc::encode(&container, &key, &kid).unwrap();
+/// assert!(raw_certificate_data.starts_with("HC1:"));
+///
+/// let mut trustlist = dgc::TrustList::default();
+/// trustlist.add(&kid, key.public_key().to_vec());
+/// let (decoded, signature_validity) = dgc::validate(&raw_certificate_data, &trustlist).unwrap();
+/// assert!(signature_validity.is_valid());
+/// assert_eq!(decoded, container);
+/// ```
+pub fn encode(
+    container: &DgcContainer,
+    key: &SigningKey,
+    kid: &[u8],
+) -> Result<String, EncodeError> {
+    let cose = make_cose_sign1(container, key, kid)?;
+    let compressed = zlib::compress(&cose);
+    Ok(format!("HC1:{}", base45::encode(compressed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, decode_cwt, validate, SignatureValidity, TrustList};
+    use ring::signature::Ed25519KeyPair;
+
+    const DATA: &str = "HC1:NCFOXN%TS3DH3ZSUZK+.V0ETD%65NL-AH-R6IOO6+IDOEZ/18WAV$E3+3AT4V22F/8X*G3M9JUPY0BX/KR96R/S09T./0LWTKD33236J3TA3M*4VV2 73-E3GG396B-43O058YIB73A*G3W19UEBY5:PI0EGSP4*2DN43U*0CEBQ/GXQFY73CIBC:G 7376BXBJBAJ UNFMJCRN0H3PQN*E33H3OA70M3FMJIJN523.K5QZ4A+2XEN QT QTHC31M3+E32R44$28A9H0D3ZCL4JMYAZ+S-A5$XKX6T2YC 35H/ITX8GL2-LH/CJTK96L6SR9MU9RFGJA6Q3QR$P2OIC0JVLA8J3ET3:H3A+2+33U SAAUOT3TPTO4UBZIC0JKQTL*QDKBO.AI9BVYTOCFOPS4IJCOT0$89NT2V457U8+9W2KQ-7LF9-DF07U$B97JJ1D7WKP/HLIJLRKF1MFHJP7NVDEBU1J*Z222E.GJI77N IKXN9+6J5DG3VWU5ZXT$ZRWP7++KM5MMUN/7UTFEEZPBK8C 7KMBI.3ZDBDREY7IM*N1KS3UI$6JD.JKLKA3UBJM-SJ9:OHBURZEF50WAQ 3";
+
+    fn round_trip(key: SigningKey) {
+        let container = decode(DATA).unwrap();
+        let kid = b"testkid!";
+
+        let encoded = encode(&container, &key, kid).unwrap();
+
+        let mut trustlist = TrustList::new();
+        trustlist.add(kid, key.public_key().to_vec());
+        let (decoded, signature_validity) = validate(&encoded, &trustlist).unwrap();
+        assert!(matches!(signature_validity, SignatureValidity::Valid));
+        assert_eq!(decoded, container);
+
+        let cwt = decode_cwt(&encoded).unwrap();
+        assert_eq!(cwt.header.kid.as_deref(), Some(&kid[..]));
+        assert_eq!(cwt.header.alg, Some(key.alg()));
+    }
+
+    #[test]
+    fn it_encodes_and_validates_es256() {
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(
+            &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+            &SystemRandom::new(),
+        )
+        .unwrap();
+        round_trip(SigningKey::from_pkcs8(EcAlg::Es256, pkcs8.as_ref()).unwrap());
+    }
+
+    #[test]
+    fn it_encodes_and_validates_es384() {
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(
+            &signature::ECDSA_P384_SHA384_FIXED_SIGNING,
+            &SystemRandom::new(),
+        )
+        .unwrap();
+        round_trip(SigningKey::from_pkcs8(EcAlg::Es384, pkcs8.as_ref()).unwrap());
+    }
+
+    #[test]
+    fn it_encodes_and_validates_eddsa() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        round_trip(SigningKey::from_pkcs8(EcAlg::EdDsa, pkcs8.as_ref()).unwrap());
+    }
+
+    #[test]
+    fn it_rejects_keys_not_matching_the_algorithm() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        assert!(matches!(
+            SigningKey::from_pkcs8(EcAlg::Es256, pkcs8.as_ref()),
+            Err(EncodeError::InvalidKey(_))
+        ));
+        assert!(matches!(
+            SigningKey::from_pkcs8(EcAlg::Es512, pkcs8.as_ref()),
+            Err(EncodeError::UnsupportedSigningAlgorithm(EcAlg::Es512))
+        ));
+    }
+}
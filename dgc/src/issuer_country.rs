@@ -0,0 +1,164 @@
+use crate::{DgcContainer, TrustListEntry};
+use std::fmt;
+
+/// The outcome of comparing the countries declared in a certificate with the country of the
+/// key that signed it (see [`check_issuer_country`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssuerCountryValidity {
+    /// The issuer and all the entries are from the country of the signing key
+    Consistent,
+    /// The country of the signing key is not known, so the check cannot be performed
+    UnknownKeyCountry,
+    /// The issuer of the CWT (claim 1) is not the country of the signing key
+    IssuerMismatch {
+        /// The issuer declared in the CWT
+        issuer: String,
+        /// The country of the signing key
+        key_country: String,
+    },
+    /// The country (`co`) of a vaccination, test or recovery entry is not the country of the
+    /// signing key
+    EntryCountryMismatch {
+        /// The country declared in the entry
+        country: String,
+        /// The country of the signing key
+        key_country: String,
+    },
+}
+
+impl fmt::Display for IssuerCountryValidity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use IssuerCountryValidity::*;
+        match self {
+            Consistent => write!(f, "Issuer and entries match the country of the signing key"),
+            UnknownKeyCountry => write!(f, "The country of the signing key is unknown"),
+            IssuerMismatch {
+                issuer,
+                key_country,
+            } => write!(
+                f,
+                "Issuer '{}' does not match the country of the signing key '{}'",
+                issuer, key_country
+            ),
+            EntryCountryMismatch {
+                country,
+                key_country,
+            } => write!(
+                f,
+                "Entry country '{}' does not match the country of the signing key '{}'",
+                country, key_country
+            ),
+        }
+    }
+}
+
+impl IssuerCountryValidity {
+    /// Checks if the countries are consistent
+    pub fn is_consistent(&self) -> bool {
+        matches!(self, IssuerCountryValidity::Consistent)
+    }
+}
+
+/// Compares the issuer of the CWT and the country (`co`) of every vaccination, test and
+/// recovery entry with the country of the key that signed the certificate.
+///
+/// A mismatch does not make the signature invalid, but it is a red flag: a country should only
+/// sign the certificates it issues.
+pub fn check_issuer_country(
+    container: &DgcContainer,
+    entry: &TrustListEntry,
+) -> IssuerCountryValidity {
+    let key_country = match &entry.country {
+        Some(country) => country,
+        None => return IssuerCountryValidity::UnknownKeyCountry,
+    };
+
+    if !container.issuer.eq_ignore_ascii_case(key_country) {
+        return IssuerCountryValidity::IssuerMismatch {
+            issuer: container.issuer.to_string(),
+            key_country: key_country.clone(),
+        };
+    }
+
+    let entry_countries = container.eu_dccs().flat_map(|dgc| {
+        let vaccines = dgc.vaccines.iter().map(|v| &v.country);
+        let tests = dgc.tests.iter().map(|t| &t.country);
+        let recoveries = dgc.recoveries.iter().map(|r| &r.country);
+        vaccines.chain(tests).chain(recoveries)
+    });
+    for country in entry_countries {
+        if !country.eq_ignore_ascii_case(key_country) {
+            return IssuerCountryValidity::EntryCountryMismatch {
+                country: country.to_string(),
+                key_country: key_country.clone(),
+            };
+        }
+    }
+
+    IssuerCountryValidity::Consistent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_fixtures, KeySource};
+
+    fn container(issuer: &str, vaccination_country: &str) -> DgcContainer {
+        test_fixtures::container(serde_json::json!({
+            "1": issuer,
+            "4": 1630000000,
+            "-260": {
+                "1": {
+                    "ver": "1.3.0",
+                    "nam": { "fnt": "ROSSI", "gnt": "MARIO" },
+                    "dob": "1970-01-01",
+                    "v": [{
+                        "tg": "840539006",
+                        "vp": "1119349007",
+                        "mp": "EU/1/20/1528",
+                        "ma": "ORG-100030215",
+                        "dn": 2,
+                        "sd": 2,
+                        "dt": "2021-05-01",
+                        "co": vaccination_country,
+                        "is": "Ministero della Salute",
+                        "ci": "01ITE7300E1AB2A84C719004F103DCB1F70A#6"
+                    }]
+                }
+            }
+        }))
+    }
+
+    fn entry(country: Option<&str>) -> TrustListEntry {
+        TrustListEntry {
+            country: country.map(String::from),
+            ..TrustListEntry::new(vec![0x04; 65], KeySource::Raw)
+        }
+    }
+
+    #[test]
+    fn it_compares_the_countries_with_the_signing_key() {
+        assert_eq!(
+            check_issuer_country(&container("IT", "IT"), &entry(Some("it"))),
+            IssuerCountryValidity::Consistent
+        );
+        assert_eq!(
+            check_issuer_country(&container("IT", "IT"), &entry(None)),
+            IssuerCountryValidity::UnknownKeyCountry
+        );
+        assert_eq!(
+            check_issuer_country(&container("IT", "IT"), &entry(Some("DE"))),
+            IssuerCountryValidity::IssuerMismatch {
+                issuer: String::from("IT"),
+                key_country: String::from("DE")
+            }
+        );
+        assert_eq!(
+            check_issuer_country(&container("DE", "IT"), &entry(Some("DE"))),
+            IssuerCountryValidity::EntryCountryMismatch {
+                country: String::from("IT"),
+                key_country: String::from("DE")
+            }
+        );
+    }
+}
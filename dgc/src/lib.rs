@@ -1,21 +1,49 @@
 #![warn(missing_docs)]
 #![doc(html_logo_url = "https://github.com/rust-italia/dgc/raw/main/dgc-rust-logo.svg")]
 #![doc = include_str!("../README.md")]
+mod certlogic;
+mod cose_header;
 mod cwt;
+mod cwt_claims;
+mod dates;
+mod der;
 mod dgc;
 mod dgc_container;
+mod diagnostics;
+mod encode;
+mod issuer_country;
 mod parse;
 mod recovery;
+mod revocation;
 mod test;
+#[cfg(test)]
+mod test_fixtures;
 mod trustlist;
+mod trustlist_cache;
+mod trustlist_loaders;
 mod vaccination;
 mod valuesets;
+mod verifier;
+#[cfg(test)]
+mod x509_test_utils;
+mod zlib;
 pub use crate::dgc::*;
+pub use certlogic::*;
+pub use cose_header::*;
 pub use cwt::*;
+pub use cwt_claims::*;
+pub use dates::*;
 pub use dgc_container::*;
+pub use diagnostics::*;
+pub use encode::*;
+pub use issuer_country::*;
 pub use parse::*;
 pub use recovery::*;
+pub use revocation::*;
 pub use test::*;
 pub use trustlist::*;
+pub use trustlist_cache::*;
+pub use trustlist_loaders::*;
 pub use vaccination::*;
 pub use valuesets::*;
+pub use verifier::*;
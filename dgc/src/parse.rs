@@ -1,6 +1,10 @@
-use crate::{Cwt, CwtParseError, DgcContainer, EcAlg, TrustList};
+use crate::{
+    der, zlib::has_zlib_header, CertificateType, Cwt, CwtHeader, CwtParseError, DecodeStage,
+    DgcContainer, EcAlg, HeaderLabel, TrustList, TrustListEntry,
+};
+use chrono::{DateTime, Utc};
 use ring::signature;
-use std::{convert::TryInto, fmt::Display};
+use std::{borrow::Cow, convert::TryInto, fmt::Display};
 use thiserror::Error;
 
 /// Represents all the possible types of failures that can occure when parsing a certificate.
@@ -41,6 +45,29 @@ pub enum SignatureValidity {
     UnsupportedSigningAlgorithm(String),
     /// The signature could not be validated because the public key was not found in the given trustlist
     KeyNotInTrustList(Vec<u8>),
+    /// The signature is valid, but the certificate was signed before the signing certificate was valid
+    KeyNotYetValid {
+        /// The moment in time when the certificate was signed
+        signed_at: DateTime<Utc>,
+        /// The moment in time from which the signing certificate is valid
+        not_before: DateTime<Utc>,
+    },
+    /// The signature is valid, but the certificate was signed after the signing certificate expired
+    KeyExpired {
+        /// The moment in time when the certificate was signed
+        signed_at: DateTime<Utc>,
+        /// The moment in time when the signing certificate expired
+        not_after: DateTime<Utc>,
+    },
+    /// The signature is valid, but the extended key usage of the signing certificate does not
+    /// allow to sign this type of certificate
+    CertificateTypeNotAllowed(CertificateType),
+    /// The signature could not be validated because the certificate has critical headers that
+    /// are not understood by this library
+    UnknownCriticalHeaders(Vec<HeaderLabel>),
+    /// The signature could not be validated because the critical headers of the certificate
+    /// are malformed or not in the protected header
+    InvalidCriticalHeaders,
 }
 
 impl Display for SignatureValidity {
@@ -73,6 +100,47 @@ impl Display for SignatureValidity {
                     base64::encode(kid)
                 )
             }
+            KeyNotYetValid {
+                signed_at,
+                not_before,
+            } => {
+                write!(
+                    f,
+                    "The certificate was signed on {} but the signing key is not valid before {}",
+                    signed_at, not_before
+                )
+            }
+            KeyExpired {
+                signed_at,
+                not_after,
+            } => {
+                write!(
+                    f,
+                    "The certificate was signed on {} but the signing key expired on {}",
+                    signed_at, not_after
+                )
+            }
+            CertificateTypeNotAllowed(ty) => {
+                write!(
+                    f,
+                    "The signing key is not allowed to sign {} certificates",
+                    ty
+                )
+            }
+            UnknownCriticalHeaders(labels) => {
+                let labels: Vec<_> = labels.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "The critical headers {} are not supported by this library",
+                    labels.join(", ")
+                )
+            }
+            InvalidCriticalHeaders => {
+                write!(
+                    f,
+                    "The critical headers must be a non-empty array of labels in the protected header"
+                )
+            }
         }
     }
 }
@@ -82,9 +150,59 @@ impl SignatureValidity {
     pub fn is_valid(&self) -> bool {
         matches!(self, SignatureValidity::Valid)
     }
+
+    /// Checks if the signature matches the key in the trust list, even if the key
+    /// itself was not allowed to sign the certificate
+    pub fn is_signature_valid(&self) -> bool {
+        use SignatureValidity::*;
+        matches!(
+            self,
+            Valid | KeyNotYetValid { .. } | KeyExpired { .. } | CertificateTypeNotAllowed(_)
+        )
+    }
+}
+
+/// A deviation from the specification tolerated by lenient decoding (see [`decode_cwt_lenient`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Leniency {
+    /// Whitespace before the data or line breaks inside it (e.g. added by a QR scanner) were
+    /// removed
+    StrippedWhitespace,
+    /// The data was not zlib compressed, so it was parsed as a raw COSE message
+    Uncompressed,
+}
+
+impl Display for Leniency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Leniency::StrippedWhitespace => write!(f, "Removed whitespace from the data"),
+            Leniency::Uncompressed => write!(f, "The data is not compressed"),
+        }
+    }
+}
+
+/// The outcome of [`decode_cwt_lenient`].
+#[derive(Debug)]
+pub struct LenientDecoding {
+    /// The decoded CWT
+    pub cwt: Cwt,
+    /// The leniencies that were needed to decode the certificate, in the order they were applied
+    pub leniencies: Vec<Leniency>,
+}
+
+/// Removes the whitespace before the data and the line breaks (and any other whitespace but
+/// the space, which is a base45 character) inside it.
+pub(crate) fn strip_whitespace(data: &str) -> Cow<'_, str> {
+    let is_stripped = |c: char| c.is_whitespace() && c != ' ';
+    let data = data.trim_start();
+    if data.contains(is_stripped) {
+        Cow::Owned(data.chars().filter(|c| !is_stripped(*c)).collect())
+    } else {
+        Cow::Borrowed(data)
+    }
 }
 
-fn remove_prefix(data: &'_ str) -> Result<&'_ str, ParseError> {
+pub(crate) fn remove_prefix(data: &'_ str) -> Result<&'_ str, ParseError> {
     // check minimum data length
     if data.len() <= 4 {
         return Err(ParseError::NotEnoughData(data.len()));
@@ -98,17 +216,17 @@ fn remove_prefix(data: &'_ str) -> Result<&'_ str, ParseError> {
     Ok(&data[4..])
 }
 
-fn decode_base45(data: &str) -> Result<Vec<u8>, ParseError> {
+pub(crate) fn decode_base45(data: &str) -> Result<Vec<u8>, ParseError> {
     let decoded = base45::decode(data)?;
     Ok(decoded)
 }
 
-fn decompress(data: Vec<u8>) -> Result<Vec<u8>, ParseError> {
+pub(crate) fn decompress(data: Vec<u8>) -> Result<Vec<u8>, ParseError> {
     let decompressed = inflate::inflate_bytes_zlib(&data).map_err(ParseError::Deflate)?;
     Ok(decompressed)
 }
 
-fn parse_cwt_payload(data: Vec<u8>) -> Result<Cwt, ParseError> {
+pub(crate) fn parse_cwt_payload(data: Vec<u8>) -> Result<Cwt, ParseError> {
     let cwt: Cwt = data.try_into()?;
     Ok(cwt)
 }
@@ -156,68 +274,323 @@ pub fn validate(
     data: &str,
     trustlist: &TrustList,
 ) -> Result<(DgcContainer, SignatureValidity), ParseError> {
+    let validation = validate_detailed(data, trustlist)?;
+    Ok((validation.container, validation.signature_validity))
+}
+
+/// The detailed outcome of [`validate_detailed`].
+#[derive(Debug)]
+pub struct Validation {
+    /// The data contained in the certificate
+    pub container: DgcContainer,
+    /// The validity of the signature
+    pub signature_validity: SignatureValidity,
+    /// The key identifier of the trust list key that matched the signature, if any
+    pub matched_kid: Option<Vec<u8>>,
+    /// The entry of the trust list key that matched the signature, if any
+    pub matched_entry: Option<TrustListEntry>,
+}
+
+/// Parses and validates a given certificate like [`validate`], also reporting which key of the
+/// trustlist matched the signature.
+///
+/// The matching entry can be used, for instance, to compare the country that issued the key
+/// with the issuer of the certificate.
+pub fn validate_detailed(data: &str, trustlist: &TrustList) -> Result<Validation, ParseError> {
     let cwt = decode_cwt(data)?;
+    let (signature_validity, matched) = check_signature(&cwt, trustlist);
+    Ok(Validation {
+        matched_kid: matched.map(|matched| matched.kid.to_vec()),
+        matched_entry: matched.map(|matched| matched.entry.clone()),
+        container: cwt.payload,
+        signature_validity,
+    })
+}
 
-    let kid = match &cwt.header.kid {
-        None => return Ok((cwt.payload, SignatureValidity::MissingKid)),
-        Some(kid) => kid,
-    };
+/// The signer of a certificate and the key of the trust list that verified its signature.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SignatureMatch<'c, 'a> {
+    /// The headers of the signer: for COSE_Sign messages it is not necessarily the first one
+    pub(crate) header: &'c CwtHeader,
+    /// The key identifier of the signer
+    pub(crate) kid: &'c [u8],
+    /// The raw bytes of the signature
+    pub(crate) signature: &'c [u8],
+    /// The entry of the key that verified the signature
+    pub(crate) entry: &'a TrustListEntry,
+}
 
-    let key = match trustlist.get_key(kid) {
-        None => {
-            return Ok((
-                cwt.payload,
-                SignatureValidity::KeyNotInTrustList(kid.clone()),
-            ))
-        }
-        Some(key) => key,
-    };
+/// Validates the signature of a decoded [`Cwt`] against a given trustlist, returning the signer
+/// and the key that matched the signature, if any.
+///
+/// Every key with the KID of the certificate is tried, in the order they were added to the
+/// trustlist, until one of them verifies the signature and is allowed to sign the certificate.
+/// The signature is valid if any of the signers of a COSE_Sign message is valid.
+///
+/// When the only keys that verify the signature are not allowed to sign the certificate (e.g.
+/// because they are expired), the failure of the first one is returned with its match.
+pub(crate) fn check_signature<'c, 'a>(
+    cwt: &'c Cwt,
+    trustlist: &'a TrustList,
+) -> (SignatureValidity, Option<SignatureMatch<'c, 'a>>) {
+    if let Err(validity) = check_critical_headers(&cwt.header) {
+        return (validity, None);
+    }
 
-    let signature = &cwt.signature;
-    let data = cwt.make_sig_structure();
-    let result = match cwt.header.alg {
-        None => return Ok((cwt.payload, SignatureValidity::MissingSigningAlgorithm)),
-        Some(alg) => match alg {
-            EcAlg::Es256 => {
-                signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, key)
-                    .verify(&data, signature)
+    let mut failures = Vec::new();
+    let mut not_allowed = None;
+    for (header, signature, data) in cwt.signatures() {
+        match check_signer(header, signature, &data, &cwt.payload, trustlist) {
+            Ok((SignatureValidity::Valid, matched)) => {
+                return (SignatureValidity::Valid, Some(matched))
             }
-            EcAlg::Ps256 => {
-                signature::UnparsedPublicKey::new(&signature::RSA_PSS_2048_8192_SHA256, key)
-                    .verify(&data, signature)
+            Ok((validity, matched)) => {
+                not_allowed.get_or_insert((validity, matched));
             }
-            EcAlg::Unknown(alg) => {
-                return Ok((
-                    cwt.payload,
-                    SignatureValidity::UnsupportedSigningAlgorithm(format!("{:?}", alg)),
-                ))
+            Err(validity) => failures.push(validity),
+        }
+    }
+    if let Some((validity, matched)) = not_allowed {
+        return (validity, Some(matched));
+    }
+    // the failure of a signer whose key is trusted is more relevant than an unknown key
+    let index = failures
+        .iter()
+        .position(|failure| {
+            !matches!(
+                failure,
+                SignatureValidity::MissingKid | SignatureValidity::KeyNotInTrustList(_)
+            )
+        })
+        .unwrap_or(0);
+    // there is always at least one signature
+    (failures.swap_remove(index), None)
+}
+
+/// Checks that the critical headers are well formed and understood by this library.
+fn check_critical_headers(header: &CwtHeader) -> Result<(), SignatureValidity> {
+    if !header.has_valid_critical_headers() {
+        return Err(SignatureValidity::InvalidCriticalHeaders);
+    }
+    let unknown_critical_headers = header.unknown_critical_headers();
+    if !unknown_critical_headers.is_empty() {
+        let labels = unknown_critical_headers.into_iter().cloned().collect();
+        return Err(SignatureValidity::UnknownCriticalHeaders(labels));
+    }
+    Ok(())
+}
+
+/// Validates a single signature, returning its failure if no key of the trustlist verifies it.
+///
+/// A key that verifies the signature but is not allowed to sign the certificate is returned only
+/// if no other key with the same KID is allowed to.
+fn check_signer<'c, 'a>(
+    header: &'c CwtHeader,
+    signature: &'c [u8],
+    data: &[u8],
+    payload: &DgcContainer,
+    trustlist: &'a TrustList,
+) -> Result<(SignatureValidity, SignatureMatch<'c, 'a>), SignatureValidity> {
+    check_critical_headers(header)?;
+
+    let kid = header.kid.as_ref().ok_or(SignatureValidity::MissingKid)?;
+    let entries = trustlist.get_entries(kid);
+    if entries.is_empty() {
+        return Err(SignatureValidity::KeyNotInTrustList(kid.clone()));
+    }
+    let alg = header
+        .alg
+        .as_ref()
+        .ok_or(SignatureValidity::MissingSigningAlgorithm)?;
+
+    let mut first_failure = None;
+    let mut not_allowed = None;
+    for entry in entries {
+        let validity = verify_signature(alg, &entry.key, data, signature);
+        if !validity.is_valid() {
+            first_failure.get_or_insert(validity);
+            continue;
+        }
+        let validity = match &entry.metadata {
+            Some(metadata) => metadata.check(payload),
+            None => validity,
+        };
+        let matched = SignatureMatch {
+            header,
+            kid,
+            signature,
+            entry,
+        };
+        if validity.is_valid() {
+            return Ok((validity, matched));
+        }
+        // another key with the same kid could be allowed to sign the certificate
+        not_allowed.get_or_insert((validity, matched));
+    }
+    match not_allowed {
+        Some(not_allowed) => Ok(not_allowed),
+        // entries is not empty, so at least one verification failed
+        None => Err(first_failure.unwrap_or(SignatureValidity::Invalid)),
+    }
+}
+
+/// Length of an uncompressed P-384 public key (`0x04 || x || y`).
+pub(crate) const P384_PUBLIC_KEY_LEN: usize = 97;
+
+/// Verifies `signature` over `data` using the given algorithm and raw public key.
+fn verify_signature(alg: &EcAlg, key: &[u8], data: &[u8], signature: &[u8]) -> SignatureValidity {
+    // Some issuers sign with a P-384 key while declaring ES256, so in that case the curve is
+    // taken from the key and the hash function from the algorithm.
+    let is_p384_key = key.len() == P384_PUBLIC_KEY_LEN;
+    let result = match alg {
+        EcAlg::Es256 if is_p384_key => match ecdsa_fixed_to_asn1(signature) {
+            Some(signature) => {
+                signature::UnparsedPublicKey::new(&signature::ECDSA_P384_SHA256_ASN1, key)
+                    .verify(data, &signature)
             }
+            None => return SignatureValidity::SignatureMalformed,
         },
+        EcAlg::Es256 => signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, key)
+            .verify(data, signature),
+        EcAlg::Es384 => signature::UnparsedPublicKey::new(&signature::ECDSA_P384_SHA384_FIXED, key)
+            .verify(data, signature),
+        EcAlg::Ps256 => {
+            signature::UnparsedPublicKey::new(&signature::RSA_PSS_2048_8192_SHA256, key)
+                .verify(data, signature)
+        }
+        EcAlg::Ps384 => {
+            signature::UnparsedPublicKey::new(&signature::RSA_PSS_2048_8192_SHA384, key)
+                .verify(data, signature)
+        }
+        EcAlg::Ps512 => {
+            signature::UnparsedPublicKey::new(&signature::RSA_PSS_2048_8192_SHA512, key)
+                .verify(data, signature)
+        }
+        EcAlg::EdDsa => {
+            signature::UnparsedPublicKey::new(&signature::ED25519, key).verify(data, signature)
+        }
+        // P-521 is not supported by `ring`
+        EcAlg::Es512 => {
+            return SignatureValidity::UnsupportedSigningAlgorithm(String::from("ES512"))
+        }
+        EcAlg::Unknown(alg) => {
+            return SignatureValidity::UnsupportedSigningAlgorithm(format!("{:?}", alg))
+        }
     };
     match result {
-        Err(_) => Ok((cwt.payload, SignatureValidity::Invalid)),
-        Ok(_) => Ok((cwt.payload, SignatureValidity::Valid)),
+        Err(_) => SignatureValidity::Invalid,
+        Ok(_) => SignatureValidity::Valid,
     }
 }
 
+/// Converts an ECDSA signature from the fixed size `r || s` format used by COSE
+/// to the ASN.1 DER format.
+///
+/// Returns [`None`] if the signature cannot be split in two halves or if it is too long
+/// for the curves supported by this library.
+fn ecdsa_fixed_to_asn1(signature: &[u8]) -> Option<Vec<u8>> {
+    if signature.is_empty() || !signature.len().is_multiple_of(2) || signature.len() > 2 * 48 {
+        return None;
+    }
+    let (r, s) = signature.split_at(signature.len() / 2);
+    Some(der::sequence(&[der::integer(r), der::integer(s)]))
+}
+
+/// The intermediate artefacts of decoding a certificate with [`decode_stages`], up to the
+/// first stage that failed.
+#[derive(Debug)]
+pub(crate) struct DecodeArtefacts<'d> {
+    /// The input, without the whitespace removed by lenient decoding
+    pub(crate) input: Cow<'d, str>,
+    /// The length of the prefix at the start of the input
+    pub(crate) prefix_len: Option<usize>,
+    /// The base45 decoded bytes
+    pub(crate) base45_bytes: Option<Vec<u8>>,
+    /// The COSE message, decompressed unless lenient decoding found uncompressed data
+    pub(crate) cose_bytes: Option<Vec<u8>>,
+    /// The leniencies that were applied, in the order they were applied
+    pub(crate) leniencies: Vec<Leniency>,
+}
+
+/// Runs every stage of decoding a certificate, stopping at the first one that fails, and
+/// returns the artefacts of the stages together with the decoded CWT or the stage that failed.
+///
+/// With `lenient` decoding, whitespace before the data and line breaks inside it are removed,
+/// and the data is only decompressed if it starts with a zlib header (see
+/// [`decode_cwt_lenient`]).
+pub(crate) fn decode_stages(
+    data: &str,
+    lenient: bool,
+) -> (DecodeArtefacts<'_>, Result<Cwt, (DecodeStage, ParseError)>) {
+    let mut artefacts = DecodeArtefacts {
+        input: Cow::Borrowed(data),
+        prefix_len: None,
+        base45_bytes: None,
+        cose_bytes: None,
+        leniencies: vec![],
+    };
+    let result = run_stages(&mut artefacts, lenient);
+    (artefacts, result)
+}
+
+fn run_stages(
+    artefacts: &mut DecodeArtefacts<'_>,
+    lenient: bool,
+) -> Result<Cwt, (DecodeStage, ParseError)> {
+    if lenient {
+        let stripped = strip_whitespace(&artefacts.input);
+        if stripped != artefacts.input {
+            let stripped = stripped.into_owned();
+            artefacts.leniencies.push(Leniency::StrippedWhitespace);
+            artefacts.input = Cow::Owned(stripped);
+        }
+    }
+
+    let base45 = remove_prefix(&artefacts.input).map_err(|e| (DecodeStage::Prefix, e))?;
+    artefacts.prefix_len = Some(artefacts.input.len() - base45.len());
+
+    let decoded = decode_base45(base45).map_err(|e| (DecodeStage::Base45, e))?;
+    artefacts.base45_bytes = Some(decoded.clone());
+
+    let cose = if lenient && !has_zlib_header(&decoded) {
+        artefacts.leniencies.push(Leniency::Uncompressed);
+        decoded
+    } else {
+        decompress(decoded).map_err(|e| (DecodeStage::Decompression, e))?
+    };
+    artefacts.cose_bytes = Some(cose.clone());
+
+    parse_cwt_payload(cose).map_err(|e| {
+        let stage = match &e {
+            ParseError::CwtDecode(CwtParseError::InvalidPayload(_)) => DecodeStage::Payload,
+            _ => DecodeStage::Cose,
+        };
+        (stage, e)
+    })
+}
+
 /// Decodes the certificate and returns the [`Cwt`] data contained in it.
 ///
 /// You generally don't need to use this function unless you need to access
 /// the raw information contained in the [`Cwt`] structure.
 pub fn decode_cwt(data: &str) -> Result<Cwt, ParseError> {
-    // remove prefix
-    let data = remove_prefix(data)?;
-
-    // base45 decode
-    let decoded = decode_base45(data)?;
-
-    // decompress the data
-    let decompressed = decompress(decoded)?;
-
-    // parse cose payload
-    let cwt = parse_cwt_payload(decompressed)?;
+    decode_stages(data, false).1.map_err(|(_, e)| e)
+}
 
-    Ok(cwt)
+/// Decodes the certificate like [`decode_cwt`], tolerating some common deviations from the
+/// specification.
+///
+/// Whitespace before the data and line breaks inside it are removed, and the data is only
+/// decompressed if it starts with a zlib header: otherwise it is parsed as a raw COSE message,
+/// as the specification allows. The returned [`LenientDecoding`] lists the leniencies that
+/// were applied, so that they can be reported.
+pub fn decode_cwt_lenient(data: &str) -> Result<LenientDecoding, ParseError> {
+    let (artefacts, result) = decode_stages(data, true);
+    let cwt = result.map_err(|(_, e)| e)?;
+    Ok(LenientDecoding {
+        cwt,
+        leniencies: artefacts.leniencies,
+    })
 }
 
 /// Decodes the certificate and returns the [`DgcContainer`] data contained in it.
@@ -281,6 +654,35 @@ mod tests {
         assert_eq!(expected, sig_structure);
     }
 
+    #[test]
+    fn it_enforces_the_declared_ecdsa_algorithm() {
+        use ring::{rand::SystemRandom, signature::KeyPair};
+
+        let rng = SystemRandom::new();
+        let alg = &signature::ECDSA_P256_SHA256_FIXED_SIGNING;
+        let pkcs8 = signature::EcdsaKeyPair::generate_pkcs8(alg, &rng).unwrap();
+        let key_pair = signature::EcdsaKeyPair::from_pkcs8(alg, pkcs8.as_ref()).unwrap();
+        let key = key_pair.public_key().as_ref();
+        let signature = key_pair.sign(&rng, b"data").unwrap();
+
+        assert!(verify_signature(&EcAlg::Es256, key, b"data", signature.as_ref()).is_valid());
+        assert!(!verify_signature(&EcAlg::Es384, key, b"data", signature.as_ref()).is_valid());
+    }
+
+    #[test]
+    fn it_converts_fixed_ecdsa_signatures_to_asn1() {
+        let mut fixed = vec![0x00, 0x7f];
+        fixed.extend([0x80, 0x01]);
+        let asn1 = ecdsa_fixed_to_asn1(&fixed).unwrap();
+
+        assert_eq!(
+            asn1,
+            vec![0x30, 0x08, 0x02, 0x01, 0x7f, 0x02, 0x03, 0x00, 0x80, 0x01]
+        );
+        assert!(ecdsa_fixed_to_asn1(&[0x01, 0x02, 0x03]).is_none());
+        assert!(ecdsa_fixed_to_asn1(&[]).is_none());
+    }
+
     #[test]
     fn it_decodes() {
         let data = "HC1:NCFOXN%TS3DH3ZSUZK+.V0ETD%65NL-AH-R6IOO6+IDOEZ/18WAV$E3+3AT4V22F/8X*G3M9JUPY0BX/KR96R/S09T./0LWTKD33236J3TA3M*4VV2 73-E3GG396B-43O058YIB73A*G3W19UEBY5:PI0EGSP4*2DN43U*0CEBQ/GXQFY73CIBC:G 7376BXBJBAJ UNFMJCRN0H3PQN*E33H3OA70M3FMJIJN523.K5QZ4A+2XEN QT QTHC31M3+E32R44$28A9H0D3ZCL4JMYAZ+S-A5$XKX6T2YC 35H/ITX8GL2-LH/CJTK96L6SR9MU9RFGJA6Q3QR$P2OIC0JVLA8J3ET3:H3A+2+33U SAAUOT3TPTO4UBZIC0JKQTL*QDKBO.AI9BVYTOCFOPS4IJCOT0$89NT2V457U8+9W2KQ-7LF9-DF07U$B97JJ1D7WKP/HLIJLRKF1MFHJP7NVDEBU1J*Z222E.GJI77N IKXN9+6J5DG3VWU5ZXT$ZRWP7++KM5MMUN/7UTFEEZPBK8C 7KMBI.3ZDBDREY7IM*N1KS3UI$6JD.JKLKA3UBJM-SJ9:OHBURZEF50WAQ 3";
@@ -290,6 +692,33 @@ mod tests {
         assert_eq!(expected, dgc_cert_container);
     }
 
+    #[test]
+    fn it_decodes_leniently() {
+        let data = "HC1:NCFOXN%TS3DH3ZSUZK+.V0ETD%65NL-AH-R6IOO6+IDOEZ/18WAV$E3+3AT4V22F/8X*G3M9JUPY0BX/KR96R/S09T./0LWTKD33236J3TA3M*4VV2 73-E3GG396B-43O058YIB73A*G3W19UEBY5:PI0EGSP4*2DN43U*0CEBQ/GXQFY73CIBC:G 7376BXBJBAJ UNFMJCRN0H3PQN*E33H3OA70M3FMJIJN523.K5QZ4A+2XEN QT QTHC31M3+E32R44$28A9H0D3ZCL4JMYAZ+S-A5$XKX6T2YC 35H/ITX8GL2-LH/CJTK96L6SR9MU9RFGJA6Q3QR$P2OIC0JVLA8J3ET3:H3A+2+33U SAAUOT3TPTO4UBZIC0JKQTL*QDKBO.AI9BVYTOCFOPS4IJCOT0$89NT2V457U8+9W2KQ-7LF9-DF07U$B97JJ1D7WKP/HLIJLRKF1MFHJP7NVDEBU1J*Z222E.GJI77N IKXN9+6J5DG3VWU5ZXT$ZRWP7++KM5MMUN/7UTFEEZPBK8C 7KMBI.3ZDBDREY7IM*N1KS3UI$6JD.JKLKA3UBJM-SJ9:OHBURZEF50WAQ 3";
+        let expected = decode(data).unwrap();
+
+        let decoding = decode_cwt_lenient(data).unwrap();
+        assert_eq!(decoding.cwt.payload, expected);
+        assert!(decoding.leniencies.is_empty());
+
+        // wrapped by a scanner, keeping the spaces which are part of the base45 data
+        let wrapped = format!("\n {}\r\n{}\r\n", &data[..100], &data[100..]);
+        assert!(decode_cwt(&wrapped).is_err());
+        let decoding = decode_cwt_lenient(&wrapped).unwrap();
+        assert_eq!(decoding.cwt.payload, expected);
+        assert_eq!(decoding.leniencies, vec![Leniency::StrippedWhitespace]);
+
+        let cose = decompress(decode_base45(&data[4..]).unwrap()).unwrap();
+        let uncompressed = format!("HC1:{}", base45::encode(cose));
+        assert!(matches!(
+            decode_cwt(&uncompressed),
+            Err(ParseError::Deflate(_))
+        ));
+        let decoding = decode_cwt_lenient(&uncompressed).unwrap();
+        assert_eq!(decoding.cwt.payload, expected);
+        assert_eq!(decoding.leniencies, vec![Leniency::Uncompressed]);
+    }
+
     #[test]
     fn it_validates() {
         let data = "HC1:6BFOXN%TS3DH0YOJ58S S-W5HDC *M0II5XHC9B5G2+$N IOP-IA%NFQGRJPC%OQHIZC4.OI1RM8ZA.A5:S9MKN4NN3F85QNCY0O%0VZ001HOC9JU0D0HT0HB2PL/IB*09B9LW4T*8+DCMH0LDK2%K:XFE70*LP$V25$0Q:J:4MO1P0%0L0HD+9E/HY+4J6TH48S%4K.GJ2PT3QY:GQ3TE2I+-CPHN6D7LLK*2HG%89UV-0LZ 2ZJJ524-LH/CJTK96L6SR9MU9DHGZ%P WUQRENS431T1XCNCF+47AY0-IFO0500TGPN8F5G.41Q2E4T8ALW.INSV$ 07UV5SR+BNQHNML7 /KD3TU 4V*CAT3ZGLQMI/XI%ZJNSBBXK2:UG%UJMI:TU+MMPZ5$/PMX19UE:-PSR3/$NU44CBE6DQ3D7B0FBOFX0DV2DGMB$YPF62I$60/F$Z2I6IFX21XNI-LM%3/DF/U6Z9FEOJVRLVW6K$UG+BKK57:1+D10%4K83F+1VWD1NE";
@@ -303,5 +732,150 @@ mod tests {
 
         let (_, signature_validity) = validate(data, &trustlist).unwrap();
         assert!(matches!(signature_validity, SignatureValidity::Valid));
+
+        let validation = validate_detailed(data, &trustlist).unwrap();
+        assert_eq!(validation.matched_kid, Some(kid));
+
+        let validation = validate_detailed(data, &TrustList::new()).unwrap();
+        assert_eq!(validation.matched_kid, None);
+    }
+
+    #[test]
+    fn it_tries_every_key_with_the_same_kid() {
+        use crate::x509_test_utils::TestKey;
+
+        let data = "HC1:6BFOXN%TS3DH0YOJ58S S-W5HDC *M0II5XHC9B5G2+$N IOP-IA%NFQGRJPC%OQHIZC4.OI1RM8ZA.A5:S9MKN4NN3F85QNCY0O%0VZ001HOC9JU0D0HT0HB2PL/IB*09B9LW4T*8+DCMH0LDK2%K:XFE70*LP$V25$0Q:J:4MO1P0%0L0HD+9E/HY+4J6TH48S%4K.GJ2PT3QY:GQ3TE2I+-CPHN6D7LLK*2HG%89UV-0LZ 2ZJJ524-LH/CJTK96L6SR9MU9DHGZ%P WUQRENS431T1XCNCF+47AY0-IFO0500TGPN8F5G.41Q2E4T8ALW.INSV$ 07UV5SR+BNQHNML7 /KD3TU 4V*CAT3ZGLQMI/XI%ZJNSBBXK2:UG%UJMI:TU+MMPZ5$/PMX19UE:-PSR3/$NU44CBE6DQ3D7B0FBOFX0DV2DGMB$YPF62I$60/F$Z2I6IFX21XNI-LM%3/DF/U6Z9FEOJVRLVW6K$UG+BKK57:1+D10%4K83F+1VWD1NE";
+        let kid: Vec<u8> = vec![57, 48, 23, 104, 205, 218, 5, 19];
+        let key = base64::decode("BDSp7t86JxAmjZFobmmu0wkii53snRuwqVWe3/g/wVz9i306XA5iXpHkRPZVUkSZmYhutMDrheg6sfwMRdql3aY=").unwrap();
+
+        // another country published a key with the same kid
+        let mut trustlist = TrustList::new();
+        trustlist.push_key(&kid, TestKey::generate().public_key().to_vec());
+        trustlist.push_key(&kid, key.clone());
+        assert_eq!(trustlist.get_entries(&kid).len(), 2);
+
+        let validation = validate_detailed(data, &trustlist).unwrap();
+        assert!(validation.signature_validity.is_valid());
+        assert_eq!(validation.matched_kid, Some(kid.clone()));
+        assert_eq!(validation.matched_entry.unwrap().key, key);
+
+        let mut trustlist = TrustList::new();
+        trustlist.push_key(&kid, TestKey::generate().public_key().to_vec());
+        trustlist.push_key(&kid, TestKey::generate().public_key().to_vec());
+        let validation = validate_detailed(data, &trustlist).unwrap();
+        assert!(matches!(
+            validation.signature_validity,
+            SignatureValidity::Invalid
+        ));
+        assert!(validation.matched_entry.is_none());
+    }
+
+    #[test]
+    fn it_checks_the_validity_and_usage_of_the_signing_key() {
+        use crate::x509_test_utils::{CertificateParams, TestKey};
+        use ring::digest;
+
+        // a vaccination issued at 2021-06-14
+        let data = "HC1:NCFOXN%TS3DH3ZSUZK+.V0ETD%65NL-AH-R6IOO6+IDOEZ/18WAV$E3+3AT4V22F/8X*G3M9JUPY0BX/KR96R/S09T./0LWTKD33236J3TA3M*4VV2 73-E3GG396B-43O058YIB73A*G3W19UEBY5:PI0EGSP4*2DN43U*0CEBQ/GXQFY73CIBC:G 7376BXBJBAJ UNFMJCRN0H3PQN*E33H3OA70M3FMJIJN523.K5QZ4A+2XEN QT QTHC31M3+E32R44$28A9H0D3ZCL4JMYAZ+S-A5$XKX6T2YC 35H/ITX8GL2-LH/CJTK96L6SR9MU9RFGJA6Q3QR$P2OIC0JVLA8J3ET3:H3A+2+33U SAAUOT3TPTO4UBZIC0JKQTL*QDKBO.AI9BVYTOCFOPS4IJCOT0$89NT2V457U8+9W2KQ-7LF9-DF07U$B97JJ1D7WKP/HLIJLRKF1MFHJP7NVDEBU1J*Z222E.GJI77N IKXN9+6J5DG3VWU5ZXT$ZRWP7++KM5MMUN/7UTFEEZPBK8C 7KMBI.3ZDBDREY7IM*N1KS3UI$6JD.JKLKA3UBJM-SJ9:OHBURZEF50WAQ 3";
+        let container = decode(data).unwrap();
+        let csca_key = TestKey::generate();
+        let dsc_key = TestKey::generate();
+        let signing_key = crate::SigningKey::from_pkcs8(EcAlg::Es256, &dsc_key.pkcs8).unwrap();
+
+        let validate_with = |params: CertificateParams| {
+            let certificate = params.sign(&dsc_key, &csca_key);
+            let kid = digest::digest(&digest::SHA256, &certificate).as_ref()[0..8].to_vec();
+            let mut trustlist = TrustList::new();
+            trustlist
+                .add_key_from_certificate(&base64::encode(&certificate))
+                .unwrap();
+            let data = crate::encode(&container, &signing_key, &kid).unwrap();
+            validate(&data, &trustlist).unwrap().1
+        };
+
+        let params = CertificateParams::new("DSC", "CSCA");
+        assert!(validate_with(params).is_valid());
+
+        let params = CertificateParams {
+            not_before: "210701000000Z",
+            ..CertificateParams::new("DSC", "CSCA")
+        };
+        assert!(matches!(
+            validate_with(params),
+            SignatureValidity::KeyNotYetValid { .. }
+        ));
+
+        let params = CertificateParams {
+            not_after: "210601000000Z",
+            ..CertificateParams::new("DSC", "CSCA")
+        };
+        assert!(matches!(
+            validate_with(params),
+            SignatureValidity::KeyExpired { .. }
+        ));
+
+        let params = CertificateParams {
+            extended_key_usages: &["1.3.6.1.4.1.1847.2021.1.1", "1.3.6.1.4.1.1847.2021.1.3"],
+            ..CertificateParams::new("DSC", "CSCA")
+        };
+        assert!(matches!(
+            validate_with(params),
+            SignatureValidity::CertificateTypeNotAllowed(CertificateType::Vaccination)
+        ));
+
+        let params = CertificateParams {
+            extended_key_usages: &["0.4.0.127.0.7.5.3.2"],
+            ..CertificateParams::new("DSC", "CSCA")
+        };
+        assert!(validate_with(params).is_valid());
+    }
+
+    #[test]
+    fn it_tries_the_other_signers_when_a_signing_key_is_not_allowed() {
+        use crate::test_fixtures::{alg, cose_sign, kid, signer};
+        use crate::x509_test_utils::{CertificateParams, TestKey};
+        use crate::KeySource;
+
+        let csca_key = TestKey::generate();
+        let dsc_key = TestKey::generate();
+        let signing_key = crate::SigningKey::from_pkcs8(EcAlg::Es256, &dsc_key.pkcs8).unwrap();
+        let mut cwt = cose_sign(vec![
+            signer(vec![kid(b"expired"), alg(-7)], vec![]),
+            signer(vec![kid(b"valid"), alg(-7)], vec![]),
+        ])
+        .unwrap();
+        for index in 0..cwt.signers.len() {
+            let sig_structure = cwt.make_signer_sig_structure(&cwt.signers[index]);
+            cwt.signers[index].signature = signing_key.sign(&sig_structure).unwrap();
+        }
+
+        // the container was signed at 2021-05-03
+        let expired = CertificateParams {
+            not_after: "210401000000Z",
+            ..CertificateParams::new("DSC", "CSCA")
+        };
+        let mut trustlist = TrustList::new();
+        trustlist
+            .add_key_from_certificate_with_kid(
+                b"expired",
+                &base64::encode(expired.sign(&dsc_key, &csca_key)),
+                KeySource::Certificate,
+            )
+            .unwrap();
+        let (validity, matched) = check_signature(&cwt, &trustlist);
+        assert!(matches!(validity, SignatureValidity::KeyExpired { .. }));
+        assert_eq!(matched.unwrap().kid, b"expired");
+
+        let valid = CertificateParams::new("DSC", "CSCA");
+        trustlist
+            .add_key_from_certificate_with_kid(
+                b"valid",
+                &base64::encode(valid.sign(&dsc_key, &csca_key)),
+                KeySource::Certificate,
+            )
+            .unwrap();
+        let (validity, matched) = check_signature(&cwt, &trustlist);
+        assert!(validity.is_valid());
+        assert_eq!(matched.unwrap().kid, b"valid");
     }
 }
@@ -1,4 +1,5 @@
-use crate::lookup_value;
+use crate::{dates::parse_date, lookup_value, DateParseError};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::fmt;
@@ -38,6 +39,21 @@ impl Recovery {
         lookup_value(&mut self.targeted_disease);
         lookup_value(&mut self.country);
     }
+
+    /// The date of the first positive test result as a typed value
+    pub fn parsed_result_date(&self) -> Result<NaiveDate, DateParseError> {
+        parse_date(&self.result_date)
+    }
+
+    /// The first day of validity of the certificate as a typed value
+    pub fn parsed_valid_from(&self) -> Result<NaiveDate, DateParseError> {
+        parse_date(&self.valid_from)
+    }
+
+    /// The last day of validity of the certificate as a typed value
+    pub fn parsed_valid_until(&self) -> Result<NaiveDate, DateParseError> {
+        parse_date(&self.valid_until)
+    }
 }
 
 impl fmt::Display for Recovery {
@@ -0,0 +1,709 @@
+use crate::{
+    parse::{check_signature, SignatureMatch},
+    Cwt, EcAlg, TrustList,
+};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+use thiserror::Error;
+
+/// The number of bytes of a revocation hash.
+///
+/// Revocation hashes are SHA-256 digests truncated to the first 128 bits.
+pub const REVOCATION_HASH_LEN: usize = 16;
+
+/// The kind of data a revocation hash is computed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RevocationHashType {
+    /// The hash of the COSE signature.
+    ///
+    /// For ECDSA signatures only the `r` value (the first half of the signature) is hashed.
+    Signature,
+    /// The hash of the Unique Certificate Identifier (the `ci` field of an entry).
+    Uci,
+    /// The hash of the issuing country code followed by the Unique Certificate Identifier.
+    #[serde(rename = "COUNTRYCODEUCI")]
+    CountryCodeUci,
+}
+
+impl fmt::Display for RevocationHashType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            RevocationHashType::Signature => "SIGNATURE",
+            RevocationHashType::Uci => "UCI",
+            RevocationHashType::CountryCodeUci => "COUNTRYCODEUCI",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A truncated SHA-256 hash identifying a revoked certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RevocationHash(pub [u8; REVOCATION_HASH_LEN]);
+
+impl RevocationHash {
+    /// Computes the truncated SHA-256 hash of the given data.
+    pub fn of(data: &[u8]) -> Self {
+        let digest = digest::digest(&digest::SHA256, data);
+        let mut hash = [0; REVOCATION_HASH_LEN];
+        hash.copy_from_slice(&digest.as_ref()[..REVOCATION_HASH_LEN]);
+        RevocationHash(hash)
+    }
+}
+
+impl fmt::Display for RevocationHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&base64::encode(self.0))
+    }
+}
+
+/// Computes the revocation hashes of a certificate for the given hash type.
+///
+/// [`RevocationHashType::Signature`] produces the hash of the signature verified by a key of
+/// the trust list (for COSE_Sign messages, not necessarily the one of the first signer), or no
+/// hash if the signature is not verified. The other types produce a hash for every vaccination,
+/// test and recovery entry.
+///
+/// The hashes must be computed before calling
+/// [`DgcContainer::expand_values`](crate::DgcContainer::expand_values), because the expanded
+/// values are not the ones hashed by the issuer.
+pub fn revocation_hashes(
+    cwt: &Cwt,
+    trustlist: &TrustList,
+    hash_type: RevocationHashType,
+) -> Vec<RevocationHash> {
+    let (_, matched) = check_signature(cwt, trustlist);
+    hashes_of(cwt, matched.as_ref(), hash_type)
+}
+
+/// Computes the revocation hashes of a certificate whose signature was verified by `matched`.
+fn hashes_of(
+    cwt: &Cwt,
+    matched: Option<&SignatureMatch>,
+    hash_type: RevocationHashType,
+) -> Vec<RevocationHash> {
+    match hash_type {
+        RevocationHashType::Signature => matched
+            .map(|matched| {
+                let signature = match matched.header.alg {
+                    Some(EcAlg::Es256) | Some(EcAlg::Es384) | Some(EcAlg::Es512) => {
+                        &matched.signature[..matched.signature.len() / 2]
+                    }
+                    _ => matched.signature,
+                };
+                RevocationHash::of(signature)
+            })
+            .into_iter()
+            .collect(),
+        RevocationHashType::Uci | RevocationHashType::CountryCodeUci => {
+            let country = cwt.payload.issuer.to_uppercase();
+            cwt.payload
+                .eu_dccs()
+                .flat_map(|dgc| {
+                    dgc.vaccines
+                        .iter()
+                        .map(|vaccination| &vaccination.id)
+                        .chain(dgc.tests.iter().map(|test| &test.id))
+                        .chain(dgc.recoveries.iter().map(|recovery| &recovery.id))
+                })
+                .map(|uci| match hash_type {
+                    RevocationHashType::CountryCodeUci => {
+                        RevocationHash::of(format!("{}{}", country, uci).as_bytes())
+                    }
+                    _ => RevocationHash::of(uci.as_bytes()),
+                })
+                .collect()
+        }
+    }
+}
+
+/// Error struct that represents all the possible errors that can occur
+/// while building a revocation list.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RevocationError {
+    /// A hash list must be a sequence of 16 bytes hashes
+    #[error("A hash list of {0} bytes is not a sequence of {REVOCATION_HASH_LEN} bytes hashes")]
+    InvalidHashListLength(usize),
+    /// A bloom filter must have at least one bit, one hash function and enough data for all its bits
+    #[error("Invalid bloom filter with {num_bits} bits, {num_hashes} hash functions and {data_len} bytes of data")]
+    InvalidBloomFilter {
+        /// The number of bits of the filter
+        num_bits: u32,
+        /// The number of hash functions of the filter
+        num_hashes: u8,
+        /// The length of the data of the filter
+        data_len: usize,
+    },
+    /// Only version 1 of the serialized bloom filters is supported
+    #[error("Unsupported bloom filter version {0}")]
+    UnsupportedBloomFilterVersion(u16),
+    /// The length of a serialized bloom filter does not match its header
+    #[error("A serialized bloom filter of {0} bytes does not match the length in its header")]
+    InvalidBloomFilterLength(usize),
+    /// The partitions of a kid and hash type must all use the same mode
+    #[error("Partition mode {found:?} does not match mode {expected:?} used for kid '{kid}' and type {hash_type}")]
+    PartitionModeMismatch {
+        /// The base64 encoded kid
+        kid: String,
+        /// The hash type
+        hash_type: RevocationHashType,
+        /// The mode used by the previous partitions
+        expected: PartitionMode,
+        /// The mode of the partition
+        found: PartitionMode,
+    },
+    /// The coordinates of a partition do not match its mode
+    #[error("Partition coordinates x = {x:?}, y = {y:?} are not valid for mode {mode:?}")]
+    InvalidPartitionCoordinates {
+        /// The partition mode
+        mode: PartitionMode,
+        /// The first coordinate
+        x: Option<u8>,
+        /// The second coordinate
+        y: Option<u8>,
+    },
+}
+
+/// A sorted list of revocation hashes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HashList(Vec<RevocationHash>);
+
+impl HashList {
+    /// Parses a hash list from the concatenation of 16 bytes hashes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, RevocationError> {
+        if !data.len().is_multiple_of(REVOCATION_HASH_LEN) {
+            return Err(RevocationError::InvalidHashListLength(data.len()));
+        }
+        Ok(data
+            .chunks_exact(REVOCATION_HASH_LEN)
+            .map(|chunk| {
+                let mut hash = [0; REVOCATION_HASH_LEN];
+                hash.copy_from_slice(chunk);
+                RevocationHash(hash)
+            })
+            .collect())
+    }
+
+    /// Checks if the list contains the given hash.
+    pub fn contains(&self, hash: &RevocationHash) -> bool {
+        self.0.binary_search(hash).is_ok()
+    }
+
+    /// The number of hashes in the list.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks if the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::iter::FromIterator<RevocationHash> for HashList {
+    fn from_iter<I: IntoIterator<Item = RevocationHash>>(iter: I) -> Self {
+        let mut hashes: Vec<_> = iter.into_iter().collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        HashList(hashes)
+    }
+}
+
+/// A bloom filter of revocation hashes, in the format distributed by the EU DCC gateway.
+///
+/// The `i`-th bit index of a hash (with `i` from `0` to `num_hashes - 1`) is the SHA-256 digest
+/// of the hash followed by the byte `i`, interpreted as a big endian unsigned integer, modulo
+/// the number of bits. Bit `n` is stored in byte `n / 8`, starting from the most significant
+/// bit (the gateway stores the bits in big endian 32 bits words).
+///
+/// A bloom filter can give false positives, but never false negatives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    data: Vec<u8>,
+    num_bits: u32,
+    num_hashes: u8,
+}
+
+impl BloomFilter {
+    const VERSION: u16 = 1;
+    const HEADER_LEN: usize = 2 + 1 + 4 + 4 + 4 + 4;
+
+    /// Creates an empty bloom filter with the given number of bits and hash functions.
+    pub fn new(num_bits: u32, num_hashes: u8) -> Result<Self, RevocationError> {
+        let data = vec![0; (num_bits as usize).div_ceil(32) * 4];
+        Self::from_parts(data, num_bits, num_hashes)
+    }
+
+    /// Creates a bloom filter from its data, the number of bits and hash functions.
+    pub fn from_parts(
+        data: Vec<u8>,
+        num_bits: u32,
+        num_hashes: u8,
+    ) -> Result<Self, RevocationError> {
+        if num_bits == 0 || num_hashes == 0 || data.len() * 8 < num_bits as usize {
+            return Err(RevocationError::InvalidBloomFilter {
+                num_bits,
+                num_hashes,
+                data_len: data.len(),
+            });
+        }
+        Ok(BloomFilter {
+            data,
+            num_bits,
+            num_hashes,
+        })
+    }
+
+    /// Parses a bloom filter serialized by the gateway.
+    ///
+    /// The filter starts with a header made of the version (`1`, 16 bits), the number of hash
+    /// functions (8 bits), the false positive rate (a 32 bits float), the number of hashes the
+    /// filter was sized for and the number of hashes it contains (32 bits each) and the number
+    /// of 32 bits words of data, followed by the data. Everything is big endian.
+    ///
+    /// The number of bits is not serialized: like the gateway, it is computed from the number
+    /// of hashes `n` and the false positive rate `p` as `ceil(n * ln(p) / ln(1 / 2^ln(2)))`.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, RevocationError> {
+        if data.len() < Self::HEADER_LEN {
+            return Err(RevocationError::InvalidBloomFilterLength(data.len()));
+        }
+        let u32_at = |offset: usize| {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&data[offset..offset + 4]);
+            u32::from_be_bytes(bytes)
+        };
+        let version = u16::from_be_bytes([data[0], data[1]]);
+        if version != Self::VERSION {
+            return Err(RevocationError::UnsupportedBloomFilterVersion(version));
+        }
+        let num_hashes = data[2];
+        let probability = f64::from(f32::from_bits(u32_at(3)));
+        let num_elements = f64::from(u32_at(7));
+        // the number of hashes the filter contains (at offset 11) is not needed to check it
+        let words = u32_at(15) as usize;
+        let bits = &data[Self::HEADER_LEN..];
+        if words.checked_mul(4) != Some(bits.len()) {
+            return Err(RevocationError::InvalidBloomFilterLength(data.len()));
+        }
+
+        let ln_2 = std::f64::consts::LN_2;
+        // a NaN or a negative number of bits becomes 0, and is rejected by from_parts
+        let num_bits =
+            (num_elements * probability.ln() / (1.0 / 2f64.powf(ln_2)).ln()).ceil() as u32;
+        Self::from_parts(bits.to_vec(), num_bits, num_hashes)
+    }
+
+    fn indexes<'a>(&'a self, element: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        (0..self.num_hashes).map(move |i| {
+            let mut context = digest::Context::new(&digest::SHA256);
+            context.update(element);
+            context.update(&[i]);
+            let digest = context.finish();
+            let index = digest.as_ref().iter().fold(0u64, |acc, byte| {
+                ((acc << 8) | u64::from(*byte)) % u64::from(self.num_bits)
+            });
+            index as usize
+        })
+    }
+
+    /// Adds a hash to the filter.
+    pub fn insert(&mut self, hash: &RevocationHash) {
+        let indexes: Vec<_> = self.indexes(&hash.0).collect();
+        for index in indexes {
+            self.data[index / 8] |= 0x80 >> (index % 8);
+        }
+    }
+
+    /// Checks if the filter might contain the given hash.
+    pub fn might_contain(&self, hash: &RevocationHash) -> bool {
+        self.indexes(&hash.0)
+            .all(|index| self.data[index / 8] & (0x80 >> (index % 8)) != 0)
+    }
+}
+
+/// The data of a revocation partition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevocationFilter {
+    /// An exact list of hashes
+    HashList(HashList),
+    /// A bloom filter of hashes
+    BloomFilter(BloomFilter),
+}
+
+impl RevocationFilter {
+    /// Checks if the filter contains the given hash.
+    pub fn contains(&self, hash: &RevocationHash) -> bool {
+        match self {
+            RevocationFilter::HashList(list) => list.contains(hash),
+            RevocationFilter::BloomFilter(filter) => filter.might_contain(hash),
+        }
+    }
+}
+
+/// How the hashes of a kid and hash type are split into partitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum PartitionMode {
+    /// A single partition contains all the hashes
+    Point,
+    /// Hashes are partitioned by their first byte (`x`)
+    Vector,
+    /// Hashes are partitioned by their first (`x`) and second (`y`) byte
+    Coordinate,
+}
+
+impl PartitionMode {
+    fn coordinates(self, hash: &RevocationHash) -> (Option<u8>, Option<u8>) {
+        match self {
+            PartitionMode::Point => (None, None),
+            PartitionMode::Vector => (Some(hash.0[0]), None),
+            PartitionMode::Coordinate => (Some(hash.0[0]), Some(hash.0[1])),
+        }
+    }
+}
+
+/// A partition of a revocation batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevocationPartition {
+    /// The first coordinate of the partition, required by [`PartitionMode::Vector`] and
+    /// [`PartitionMode::Coordinate`]
+    pub x: Option<u8>,
+    /// The second coordinate of the partition, required by [`PartitionMode::Coordinate`]
+    pub y: Option<u8>,
+    /// The hashes in the partition
+    pub filter: RevocationFilter,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RevocationBatch {
+    mode: PartitionMode,
+    partitions: HashMap<(Option<u8>, Option<u8>), Vec<RevocationFilter>>,
+}
+
+/// A collection of revoked certificates, organised in batches keyed by kid and hash type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RevocationList {
+    batches: HashMap<(Vec<u8>, RevocationHashType), RevocationBatch>,
+}
+
+impl RevocationList {
+    /// Creates an empty revocation list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a partition of revoked hashes for the given kid and hash type.
+    ///
+    /// All the partitions of a kid and hash type must use the same mode.
+    pub fn add_partition(
+        &mut self,
+        kid: &[u8],
+        hash_type: RevocationHashType,
+        mode: PartitionMode,
+        partition: RevocationPartition,
+    ) -> Result<(), RevocationError> {
+        let valid_coordinates = match mode {
+            PartitionMode::Point => partition.x.is_none() && partition.y.is_none(),
+            PartitionMode::Vector => partition.x.is_some() && partition.y.is_none(),
+            PartitionMode::Coordinate => partition.x.is_some() && partition.y.is_some(),
+        };
+        if !valid_coordinates {
+            return Err(RevocationError::InvalidPartitionCoordinates {
+                mode,
+                x: partition.x,
+                y: partition.y,
+            });
+        }
+
+        let batch = self
+            .batches
+            .entry((kid.to_vec(), hash_type))
+            .or_insert_with(|| RevocationBatch {
+                mode,
+                partitions: HashMap::new(),
+            });
+        if batch.mode != mode {
+            return Err(RevocationError::PartitionModeMismatch {
+                kid: base64::encode(kid),
+                hash_type,
+                expected: batch.mode,
+                found: mode,
+            });
+        }
+        batch
+            .partitions
+            .entry((partition.x, partition.y))
+            .or_default()
+            .push(partition.filter);
+        Ok(())
+    }
+
+    /// Checks if the given hash has been revoked for the given kid and hash type.
+    pub fn contains_hash(
+        &self,
+        kid: &[u8],
+        hash_type: RevocationHashType,
+        hash: &RevocationHash,
+    ) -> bool {
+        self.batches
+            .get(&(kid.to_vec(), hash_type))
+            .and_then(|batch| batch.partitions.get(&batch.mode.coordinates(hash)))
+            .is_some_and(|filters| filters.iter().any(|filter| filter.contains(hash)))
+    }
+
+    /// Checks if the certificate has been revoked, using all the hash types for which the
+    /// kid of the certificate has revocation batches.
+    ///
+    /// The kid and the signature are the ones of the signer verified by a key of the trust
+    /// list: a certificate whose signature is not verified is not checked, as it must be
+    /// rejected anyway.
+    pub fn is_revoked(&self, cwt: &Cwt, trustlist: &TrustList) -> bool {
+        let matched = match check_signature(cwt, trustlist) {
+            (_, Some(matched)) => matched,
+            (_, None) => return false,
+        };
+        let kid = matched.kid;
+        [
+            RevocationHashType::Signature,
+            RevocationHashType::Uci,
+            RevocationHashType::CountryCodeUci,
+        ]
+        .iter()
+        .filter(|hash_type| self.batches.contains_key(&(kid.to_vec(), **hash_type)))
+        .any(|hash_type| {
+            hashes_of(cwt, Some(&matched), *hash_type)
+                .iter()
+                .any(|hash| self.contains_hash(kid, *hash_type, hash))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decode_cwt,
+        test_fixtures::{alg, cose_sign, kid, signer},
+        x509_test_utils::TestKey,
+        SigningKey,
+    };
+
+    // IT/2DCode/raw/1.json
+    const DATA: &str = "HC1:6BFOXN%TS3DH0YOJ58S S-W5HDC *M0II5XHC9B5G2+$N IOP-IA%NFQGRJPC%OQHIZC4.OI1RM8ZA.A5:S9MKN4NN3F85QNCY0O%0VZ001HOC9JU0D0HT0HB2PL/IB*09B9LW4T*8+DCMH0LDK2%K:XFE70*LP$V25$0Q:J:4MO1P0%0L0HD+9E/HY+4J6TH48S%4K.GJ2PT3QY:GQ3TE2I+-CPHN6D7LLK*2HG%89UV-0LZ 2ZJJ524-LH/CJTK96L6SR9MU9DHGZ%P WUQRENS431T1XCNCF+47AY0-IFO0500TGPN8F5G.41Q2E4T8ALW.INSV$ 07UV5SR+BNQHNML7 /KD3TU 4V*CAT3ZGLQMI/XI%ZJNSBBXK2:UG%UJMI:TU+MMPZ5$/PMX19UE:-PSR3/$NU44CBE6DQ3D7B0FBOFX0DV2DGMB$YPF62I$60/F$Z2I6IFX21XNI-LM%3/DF/U6Z9FEOJVRLVW6K$UG+BKK57:1+D10%4K83F+1VWD1NE";
+    const UCI: &str = "01ITE7300E1AB2A84C719004F103DCB1F70A#6";
+
+    fn trustlist() -> TrustList {
+        let mut trustlist = TrustList::new();
+        trustlist
+            .add_key_from_base64(
+                &[57, 48, 23, 104, 205, 218, 5, 19],
+                "BDSp7t86JxAmjZFobmmu0wkii53snRuwqVWe3/g/wVz9i306XA5iXpHkRPZVUkSZmYhutMDrheg6sfwMRdql3aY=",
+            )
+            .unwrap();
+        trustlist
+    }
+
+    #[test]
+    fn it_computes_revocation_hashes() {
+        let cwt = decode_cwt(DATA).unwrap();
+        let trustlist = trustlist();
+
+        assert_eq!(
+            revocation_hashes(&cwt, &trustlist, RevocationHashType::Uci),
+            vec![RevocationHash::of(UCI.as_bytes())]
+        );
+        assert_eq!(
+            revocation_hashes(&cwt, &trustlist, RevocationHashType::CountryCodeUci),
+            vec![RevocationHash::of(format!("IT{}", UCI).as_bytes())]
+        );
+        // ES256 signature: only the r value is hashed
+        assert_eq!(
+            revocation_hashes(&cwt, &trustlist, RevocationHashType::Signature),
+            vec![RevocationHash::of(&cwt.signature[..32])]
+        );
+        // the signature is not verified
+        assert!(
+            revocation_hashes(&cwt, &TrustList::new(), RevocationHashType::Signature).is_empty()
+        );
+        assert_eq!(
+            RevocationHash::of(UCI.as_bytes()).to_string(),
+            "DyJmCv7CNbMRx3IA0WIvtA=="
+        );
+    }
+
+    #[test]
+    fn it_parses_hash_lists() {
+        let first = RevocationHash::of(b"first");
+        let second = RevocationHash::of(b"second");
+        let mut data = second.0.to_vec();
+        data.extend_from_slice(&first.0);
+
+        let list = HashList::from_bytes(&data).unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(list.contains(&first));
+        assert!(list.contains(&second));
+        assert!(!list.contains(&RevocationHash::of(b"third")));
+
+        assert_eq!(
+            HashList::from_bytes(&data[1..]),
+            Err(RevocationError::InvalidHashListLength(31))
+        );
+    }
+
+    #[test]
+    fn it_checks_bloom_filters() {
+        let mut filter = BloomFilter::new(1024, 7).unwrap();
+        let hashes: Vec<_> = (0..20u8).map(|i| RevocationHash::of(&[i])).collect();
+        hashes.iter().for_each(|hash| filter.insert(hash));
+
+        assert!(hashes.iter().all(|hash| filter.might_contain(hash)));
+        assert!(!filter.might_contain(&RevocationHash::of(b"not revoked")));
+
+        assert_eq!(
+            BloomFilter::from_parts(vec![0; 2], 17, 1),
+            Err(RevocationError::InvalidBloomFilter {
+                num_bits: 17,
+                num_hashes: 1,
+                data_len: 2
+            })
+        );
+    }
+
+    #[test]
+    fn it_parses_gateway_bloom_filters() {
+        // version 1, 7 hash functions, a false positive rate of 0.01 and 3 hashes (29 bits in
+        // a single word)
+        let data = hex::decode("0001073c23d70a0000000300000003000000019665dae0").unwrap();
+        let filter = BloomFilter::from_bytes(&data).unwrap();
+
+        for i in 0..3 {
+            let revoked = RevocationHash::of(format!("URN:UVCI:01:IT:REVOKED#{}", i).as_bytes());
+            assert!(filter.might_contain(&revoked));
+        }
+        let valid = RevocationHash::of(b"URN:UVCI:01:IT:VALID#0");
+        assert!(!filter.might_contain(&valid));
+
+        let mut version_2 = data.clone();
+        version_2[1] = 2;
+        assert_eq!(
+            BloomFilter::from_bytes(&version_2),
+            Err(RevocationError::UnsupportedBloomFilterVersion(2))
+        );
+        assert_eq!(
+            BloomFilter::from_bytes(&data[..data.len() - 1]),
+            Err(RevocationError::InvalidBloomFilterLength(22))
+        );
+    }
+
+    #[test]
+    fn it_checks_revoked_certificates() {
+        let cwt = decode_cwt(DATA).unwrap();
+        let trustlist = trustlist();
+        let kid = cwt.header.kid.clone().unwrap();
+        let uci_hash = RevocationHash::of(UCI.as_bytes());
+
+        let mut list = RevocationList::new();
+        list.add_partition(
+            &kid,
+            RevocationHashType::Signature,
+            PartitionMode::Point,
+            RevocationPartition {
+                x: None,
+                y: None,
+                filter: RevocationFilter::HashList(HashList::from_bytes(&[0; 16]).unwrap()),
+            },
+        )
+        .unwrap();
+        assert!(!list.is_revoked(&cwt, &trustlist));
+
+        let mut bloom_filter = BloomFilter::new(256, 3).unwrap();
+        bloom_filter.insert(&uci_hash);
+        list.add_partition(
+            &kid,
+            RevocationHashType::Uci,
+            PartitionMode::Coordinate,
+            RevocationPartition {
+                x: Some(uci_hash.0[0]),
+                y: Some(uci_hash.0[1]),
+                filter: RevocationFilter::BloomFilter(bloom_filter),
+            },
+        )
+        .unwrap();
+        assert!(list.is_revoked(&cwt, &trustlist));
+        assert!(!list.is_revoked(&cwt, &TrustList::new()));
+        assert!(!list.contains_hash(b"other kid", RevocationHashType::Uci, &uci_hash));
+
+        assert_eq!(
+            list.add_partition(
+                &kid,
+                RevocationHashType::Uci,
+                PartitionMode::Vector,
+                RevocationPartition {
+                    x: Some(0),
+                    y: None,
+                    filter: RevocationFilter::HashList(HashList::default()),
+                },
+            ),
+            Err(RevocationError::PartitionModeMismatch {
+                kid: base64::encode(&kid),
+                hash_type: RevocationHashType::Uci,
+                expected: PartitionMode::Coordinate,
+                found: PartitionMode::Vector,
+            })
+        );
+        assert_eq!(
+            list.add_partition(
+                &kid,
+                RevocationHashType::CountryCodeUci,
+                PartitionMode::Vector,
+                RevocationPartition {
+                    x: None,
+                    y: None,
+                    filter: RevocationFilter::HashList(HashList::default()),
+                },
+            ),
+            Err(RevocationError::InvalidPartitionCoordinates {
+                mode: PartitionMode::Vector,
+                x: None,
+                y: None,
+            })
+        );
+    }
+
+    #[test]
+    fn it_checks_the_signer_that_matched_the_signature() {
+        let key = TestKey::generate();
+        let signing_key = SigningKey::from_pkcs8(EcAlg::Es256, &key.pkcs8).unwrap();
+        // a bogus signer is put before the trusted one
+        let mut cwt = cose_sign(vec![
+            signer(vec![kid(b"bogus"), alg(-7)], vec![]),
+            signer(vec![kid(b"trusted"), alg(-7)], vec![]),
+        ])
+        .unwrap();
+        let sig_structure = cwt.make_signer_sig_structure(&cwt.signers[1]);
+        cwt.signers[1].signature = signing_key.sign(&sig_structure).unwrap();
+        let mut trustlist = TrustList::new();
+        trustlist.add(b"trusted", key.public_key().to_vec());
+
+        let revoked = RevocationHash::of(&cwt.signers[1].signature[..32]);
+        assert_eq!(
+            revocation_hashes(&cwt, &trustlist, RevocationHashType::Signature),
+            vec![revoked]
+        );
+
+        let mut list = RevocationList::new();
+        list.add_partition(
+            b"trusted",
+            RevocationHashType::Signature,
+            PartitionMode::Point,
+            RevocationPartition {
+                x: None,
+                y: None,
+                filter: RevocationFilter::HashList(vec![revoked].into_iter().collect()),
+            },
+        )
+        .unwrap();
+        assert!(list.is_revoked(&cwt, &trustlist));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::lookup_value;
+use crate::{dates::parse_datetime, lookup_value, DateParseError};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::fmt;
@@ -57,6 +58,19 @@ impl Test {
         lookup_value(&mut self.country);
         lookup_value(&mut self.issuer);
     }
+
+    /// The date and time of the sample collection as a typed value, converted to UTC
+    pub fn parsed_date_of_collection(&self) -> Result<DateTime<Utc>, DateParseError> {
+        parse_datetime(&self.date_of_collection)
+    }
+
+    /// The date and time of the test result as a typed value (if present), converted to UTC
+    pub fn parsed_date_of_result(&self) -> Result<Option<DateTime<Utc>>, DateParseError> {
+        self.date_of_result
+            .as_deref()
+            .map(parse_datetime)
+            .transpose()
+    }
 }
 
 impl fmt::Display for Test {
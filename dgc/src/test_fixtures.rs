@@ -0,0 +1,99 @@
+//! Payloads and COSE messages shared by the tests.
+
+use crate::{
+    cwt::{COSE_HEADER_KEY_ALG, COSE_HEADER_KEY_KID, COSE_SIGN1_CBOR_TAG, COSE_SIGN_CBOR_TAG},
+    Cwt, CwtParseError, DgcContainer,
+};
+use ciborium::{ser::into_writer, value::Value};
+use std::convert::TryInto;
+
+/// The claims of a container issued by Italy without certificates, with the given `claims`
+/// added to them or replacing the default ones.
+pub(crate) fn payload(claims: serde_json::Value) -> serde_json::Value {
+    let mut payload = serde_json::json!({
+        "1": "IT",
+        "6": 1620000000,
+        "-260": {}
+    });
+    if let (Some(payload), serde_json::Value::Object(claims)) = (payload.as_object_mut(), claims) {
+        payload.extend(claims);
+    }
+    payload
+}
+
+/// Parses the container with the claims of [`payload`].
+pub(crate) fn container(claims: serde_json::Value) -> DgcContainer {
+    serde_json::from_value(payload(claims)).unwrap()
+}
+
+/// The `kid` header parameter.
+pub(crate) fn kid(kid: &[u8]) -> (Value, Value) {
+    (
+        Value::from(COSE_HEADER_KEY_KID as i64),
+        Value::Bytes(kid.to_vec()),
+    )
+}
+
+/// The `alg` header parameter.
+pub(crate) fn alg(alg: i64) -> (Value, Value) {
+    (Value::from(COSE_HEADER_KEY_ALG as i64), Value::from(alg))
+}
+
+/// Encodes a protected header, using an empty byte string for an empty map.
+fn protected_header(protected: Vec<(Value, Value)>) -> Value {
+    let mut protected_raw = Vec::new();
+    if !protected.is_empty() {
+        into_writer(&Value::Map(protected), &mut protected_raw).unwrap();
+    }
+    Value::Bytes(protected_raw)
+}
+
+/// Encodes a COSE message signing the minimal [`payload`], whose last element is either the
+/// signature (COSE_Sign1) or the signers (COSE_Sign).
+fn cose(
+    tag: u64,
+    protected: Vec<(Value, Value)>,
+    unprotected: Vec<(Value, Value)>,
+    signatures: Value,
+) -> Vec<u8> {
+    let mut payload_raw = Vec::new();
+    into_writer(&container(serde_json::json!({})), &mut payload_raw).unwrap();
+    let cose = Value::Tag(
+        tag,
+        Box::new(Value::Array(vec![
+            protected_header(protected),
+            Value::Map(unprotected),
+            Value::Bytes(payload_raw),
+            signatures,
+        ])),
+    );
+    let mut data = Vec::new();
+    into_writer(&cose, &mut data).unwrap();
+    data
+}
+
+/// A COSE_Sign1 message with the given headers and a dummy signature.
+pub(crate) fn cose_sign1(protected: Vec<(Value, Value)>, unprotected: Vec<(Value, Value)>) -> Cwt {
+    let data = cose(
+        COSE_SIGN1_CBOR_TAG,
+        protected,
+        unprotected,
+        Value::Bytes(vec![0; 64]),
+    );
+    data.try_into().unwrap()
+}
+
+/// A COSE_Sign message with empty headers and the given signers (see [`signer`]).
+pub(crate) fn cose_sign(signers: Vec<Value>) -> Result<Cwt, CwtParseError> {
+    let data = cose(COSE_SIGN_CBOR_TAG, vec![], vec![], Value::Array(signers));
+    data.try_into()
+}
+
+/// A signer of a COSE_Sign message with the given headers and a dummy signature.
+pub(crate) fn signer(protected: Vec<(Value, Value)>, unprotected: Vec<(Value, Value)>) -> Value {
+    Value::Array(vec![
+        protected_header(protected),
+        Value::Map(unprotected),
+        Value::Bytes(vec![0; 64]),
+    ])
+}
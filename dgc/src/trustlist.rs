@@ -1,6 +1,18 @@
+use crate::{DgcContainer, SignatureValidity};
+use chrono::{DateTime, TimeZone, Utc};
 use ring::digest;
-use std::{collections::HashMap, convert::TryFrom, ops::Deref};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, convert::TryFrom, fmt, ops::Deref};
 use thiserror::Error;
+use x509_parser::certificate::X509Certificate;
+
+/// The prefixes of the extended key usage OIDs restricting the types of certificates a DSC can
+/// sign. They are followed by `.1` for tests, `.2` for vaccinations and `.3` for recoveries.
+const EKU_CERTIFICATE_TYPE_PREFIXES: [&str; 3] = [
+    "1.3.6.1.4.1.1847.2021.1",
+    "1.3.6.1.4.1.0.1847.2021.1",
+    "0.4.0.127.0.7.5.3",
+];
 
 /// Error struct that represents all the possible errors that can occur
 /// while trying to parse a public key.
@@ -15,6 +27,58 @@ pub enum KeyParseError {
     /// The given certificate did not contain a public key
     #[error("Cannot extract a valid public key from certificate: {0}")]
     PublicKeyParseError(String),
+    /// The given certificate is not signed by any of the trust anchors
+    #[error("Certificate chain validation failed: {0}")]
+    UntrustedCertificate(#[from] ChainValidationError),
+    /// The given certificate cannot be used as a trust anchor
+    #[error("Invalid trust anchor: {0}")]
+    InvalidTrustAnchor(#[from] TrustAnchorError),
+    /// The validity period of the given certificate cannot be represented
+    #[error("Invalid certificate validity period: timestamp {0} is out of range")]
+    InvalidValidityPeriod(i64),
+}
+
+/// Error struct that represents all the possible errors that can occur
+/// while validating the chain of a Document Signer Certificate (DSC).
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ChainValidationError {
+    /// There is no trust anchor whose subject is the issuer of the certificate
+    #[error("No trust anchor found for issuer '{0}'")]
+    NoMatchingAnchor(String),
+    /// The trust anchors whose subject is the issuer of the certificate did not sign it
+    #[error("The certificate is not signed by any trust anchor with subject '{0}'")]
+    InvalidSignature(String),
+}
+
+/// Error struct that represents all the reasons why a certificate cannot be used as a trust
+/// anchor.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TrustAnchorError {
+    /// The certificate does not have the basic constraints of a CA certificate
+    #[error("The certificate is not a CA certificate")]
+    NotCa,
+    /// The key usage of the certificate does not allow to sign certificates
+    #[error("The key usage of the certificate does not include keyCertSign")]
+    MissingKeyCertSign,
+    /// The certificate is expired or not yet valid
+    #[error("The certificate is only valid from {not_before} to {not_after}")]
+    OutsideValidityPeriod {
+        /// The moment in time from which the certificate is valid
+        not_before: DateTime<Utc>,
+        /// The moment in time after which the certificate is expired
+        not_after: DateTime<Utc>,
+    },
+}
+
+/// The trust anchor (a CSCA certificate) that signed a Document Signer Certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustAnchorMatch {
+    /// The subject of the trust anchor
+    pub subject: String,
+    /// The country of the trust anchor, if present in its subject
+    pub country: Option<String>,
+    /// The SHA-256 fingerprint of the trust anchor
+    pub fingerprint: Vec<u8>,
 }
 
 /// Error struct that represents all the possible errors that can occur
@@ -70,69 +134,531 @@ pub enum TrustListFromJsonError {
     KeyParseError(String, #[source] KeyParseError),
 }
 
+/// The types of certificate entries contained in a DGC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CertificateType {
+    /// A test entry
+    Test,
+    /// A vaccination entry
+    Vaccination,
+    /// A recovery entry
+    Recovery,
+}
+
+impl fmt::Display for CertificateType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CertificateType::Test => "test",
+            CertificateType::Vaccination => "vaccination",
+            CertificateType::Recovery => "recovery",
+        };
+        f.write_str(s)
+    }
+}
+
+impl CertificateType {
+    /// Returns the types of the entries contained in the given container.
+    pub fn of(container: &DgcContainer) -> Vec<CertificateType> {
+        let mut types = Vec::new();
+        for dgc in container.eu_dccs() {
+            let entries = [
+                (CertificateType::Test, dgc.tests.is_empty()),
+                (CertificateType::Vaccination, dgc.vaccines.is_empty()),
+                (CertificateType::Recovery, dgc.recoveries.is_empty()),
+            ];
+            for (ty, is_empty) in entries.iter() {
+                if !is_empty && !types.contains(ty) {
+                    types.push(*ty);
+                }
+            }
+        }
+        types
+    }
+
+    fn from_extended_key_usage(oid: &str) -> Option<Self> {
+        let suffix = EKU_CERTIFICATE_TYPE_PREFIXES
+            .iter()
+            .find_map(|prefix| oid.strip_prefix(prefix)?.strip_prefix('.'))?;
+        match suffix {
+            "1" => Some(CertificateType::Test),
+            "2" => Some(CertificateType::Vaccination),
+            "3" => Some(CertificateType::Recovery),
+            _ => None,
+        }
+    }
+}
+
+fn subject_country(certificate: &X509Certificate) -> Option<String> {
+    certificate
+        .subject()
+        .iter_country()
+        .next()
+        .and_then(|country| country.as_str().ok())
+        .map(String::from)
+}
+
+/// Checks that a certificate can be used as a trust anchor.
+fn check_trust_anchor(certificate: &X509Certificate) -> Result<(), KeyParseError> {
+    let tbs_certificate = &certificate.tbs_certificate;
+    if !tbs_certificate
+        .basic_constraints()
+        .is_some_and(|(_, constraints)| constraints.ca)
+    {
+        return Err(TrustAnchorError::NotCa.into());
+    }
+    if !tbs_certificate
+        .key_usage()
+        .is_some_and(|(_, usage)| usage.key_cert_sign())
+    {
+        return Err(TrustAnchorError::MissingKeyCertSign.into());
+    }
+    let validity = certificate.validity();
+    if !validity.is_valid() {
+        return Err(TrustAnchorError::OutsideValidityPeriod {
+            not_before: timestamp_to_utc(validity.not_before.timestamp())?,
+            not_after: timestamp_to_utc(validity.not_after.timestamp())?,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Converts a certificate timestamp to a date, failing instead of panicking when it is out of
+/// the range supported by [`chrono`].
+fn timestamp_to_utc(seconds: i64) -> Result<DateTime<Utc>, KeyParseError> {
+    Utc.timestamp_opt(seconds, 0)
+        .single()
+        .ok_or(KeyParseError::InvalidValidityPeriod(seconds))
+}
+
+/// The algorithm of a public key in a [`TrustList`], as inferred from its encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyAlgorithm {
+    /// An uncompressed ECDSA P-256 point
+    EcdsaP256,
+    /// An uncompressed ECDSA P-384 point
+    EcdsaP384,
+    /// A DER encoded RSA public key
+    Rsa,
+    /// A key with an unknown encoding
+    Unknown,
+}
+
+impl KeyAlgorithm {
+    /// Infers the algorithm of a raw public key.
+    pub fn of(key: &[u8]) -> Self {
+        match key {
+            [0x04, ..] if key.len() == 65 => KeyAlgorithm::EcdsaP256,
+            [0x04, ..] if key.len() == 97 => KeyAlgorithm::EcdsaP384,
+            [0x30, ..] => KeyAlgorithm::Rsa,
+            _ => KeyAlgorithm::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            KeyAlgorithm::EcdsaP256 => "ECDSA P-256",
+            KeyAlgorithm::EcdsaP384 => "ECDSA P-384",
+            KeyAlgorithm::Rsa => "RSA",
+            KeyAlgorithm::Unknown => "unknown",
+        };
+        f.write_str(s)
+    }
+}
+
+/// The metadata of a public key added from a Document Signer Certificate (DSC).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMetadata {
+    /// The moment in time from which the certificate is valid
+    pub not_before: DateTime<Utc>,
+    /// The moment in time after which the certificate is expired
+    pub not_after: DateTime<Utc>,
+    /// The OIDs (in dotted notation) of the extended key usages of the certificate
+    pub extended_key_usages: Vec<String>,
+    /// The subject of the certificate
+    pub subject: String,
+}
+
+impl KeyMetadata {
+    fn from_certificate(certificate: &X509Certificate) -> Result<Self, KeyParseError> {
+        let validity = certificate.validity();
+        let extended_key_usages = certificate
+            .tbs_certificate
+            .extended_key_usage()
+            .map(|(_, eku)| eku.other.iter().map(|oid| oid.to_id_string()).collect())
+            .unwrap_or_default();
+        Ok(KeyMetadata {
+            not_before: timestamp_to_utc(validity.not_before.timestamp())?,
+            not_after: timestamp_to_utc(validity.not_after.timestamp())?,
+            extended_key_usages,
+            subject: certificate.subject().to_string(),
+        })
+    }
+
+    /// Returns the types of certificates the key is allowed to sign, or [`None`] if the
+    /// extended key usages do not restrict them.
+    pub fn allowed_certificate_types(&self) -> Option<Vec<CertificateType>> {
+        let types: Vec<_> = self
+            .extended_key_usages
+            .iter()
+            .filter_map(|oid| CertificateType::from_extended_key_usage(oid))
+            .collect();
+        if types.is_empty() {
+            None
+        } else {
+            Some(types)
+        }
+    }
+
+    /// Checks that a container signed with this key was signed while the certificate was valid
+    /// and that it only contains types of certificates allowed by the extended key usages.
+    ///
+    /// The signing time is the issue date of the container: when it is malformed the validity
+    /// period is not checked.
+    pub(crate) fn check(&self, container: &DgcContainer) -> SignatureValidity {
+        if let Ok(signed_at) = container.parsed_issued_at() {
+            if signed_at < self.not_before {
+                return SignatureValidity::KeyNotYetValid {
+                    signed_at,
+                    not_before: self.not_before,
+                };
+            }
+            if signed_at > self.not_after {
+                return SignatureValidity::KeyExpired {
+                    signed_at,
+                    not_after: self.not_after,
+                };
+            }
+        }
+        if let Some(allowed) = self.allowed_certificate_types() {
+            if let Some(ty) = CertificateType::of(container)
+                .into_iter()
+                .find(|ty| !allowed.contains(ty))
+            {
+                return SignatureValidity::CertificateTypeNotAllowed(ty);
+            }
+        }
+        SignatureValidity::Valid
+    }
+}
+
+/// Where a key of a [`TrustList`] was loaded from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeySource {
+    /// A raw public key added with [`TrustList::add`] or [`TrustList::add_key_from_base64`]
+    Raw,
+    /// A X509 certificate added with [`TrustList::add_key_from_certificate`]
+    Certificate,
+    /// The JSON trust list parsed by `TryFrom<serde_json::Value>`
+    Json,
+    /// A signed DSC list (see [`TrustList::load_signed_dsc_list`])
+    SignedDscList,
+    /// The trust list of the EU gateway (see [`TrustList::load_dccg_trust_list`])
+    Dccg,
+    /// The Swiss trust list (see [`TrustList::load_swiss_trust_list`])
+    Swiss,
+    /// Any other source, identified by name
+    Other(String),
+}
+
+impl fmt::Display for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeySource::Raw => f.write_str("raw key"),
+            KeySource::Certificate => f.write_str("certificate"),
+            KeySource::Json => f.write_str("JSON trust list"),
+            KeySource::SignedDscList => f.write_str("signed DSC list"),
+            KeySource::Dccg => f.write_str("EU gateway trust list"),
+            KeySource::Swiss => f.write_str("Swiss trust list"),
+            KeySource::Other(name) => f.write_str(name),
+        }
+    }
+}
+
+/// A public key of a [`TrustList`], together with what is known about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustListEntry {
+    /// The raw public key
+    pub key: Vec<u8>,
+    /// The algorithm of the key
+    pub algorithm: KeyAlgorithm,
+    /// The country that issued the key, if known
+    pub country: Option<String>,
+    /// The metadata of the certificate the key was extracted from, if any
+    pub metadata: Option<KeyMetadata>,
+    /// Where the key was loaded from
+    pub source: KeySource,
+}
+
+impl TrustListEntry {
+    /// Creates an entry for a raw public key, with no country and no metadata.
+    pub fn new(key: Vec<u8>, source: KeySource) -> Self {
+        TrustListEntry {
+            algorithm: KeyAlgorithm::of(&key),
+            key,
+            country: None,
+            metadata: None,
+            source,
+        }
+    }
+}
+
 /// Struct used to index all the available public keys which
 /// can be used to validate the signature on a given certificate.
 ///
 /// Keys are indexed by their `kid` (Key ID) which is an arbitrary sequence of bytes.
+/// The KIDs of the DSCs are only 8 bytes long, so different keys can share the same KID: the
+/// certificates, the loaders, [`add_entry`](TrustList::add_entry) and
+/// [`push_key`](TrustList::push_key) keep all of them, and each one is tried when validating a
+/// signature, while [`add`](TrustList::add) replaces them.
+///
+/// When trust anchors (the CSCA certificates of the issuing countries) are added, the trust list
+/// works in trust-anchor mode: every certificate added with
+/// [`add_key_from_certificate`](TrustList::add_key_from_certificate) or by the loaders must be
+/// signed by one of them.
+///
+/// Trust-anchor mode only covers certificates: raw public keys have no issuer to check, so the
+/// keys added with [`add`](TrustList::add), [`push_key`](TrustList::push_key),
+/// [`add_key_from_base64`](TrustList::add_key_from_base64) and
+/// [`add_entry`](TrustList::add_entry), parsed from a JSON trust list or loaded from the Swiss
+/// trust list are trusted as they are.
 #[derive(Debug)]
 pub struct TrustList {
-    keys: HashMap<Vec<u8>, Vec<u8>>,
+    pub(crate) entries: HashMap<Vec<u8>, Vec<TrustListEntry>>,
+    pub(crate) anchors: Vec<Vec<u8>>,
 }
 
 impl TrustList {
-    /// Returns the public key with the specified key identifier or
+    /// Returns the first public key with the specified key identifier or
     /// [`None`] if there is no key with that key ID.
     pub fn get_key(&self, kid: &[u8]) -> Option<&[u8]> {
-        self.keys.get(kid).map(Vec::deref)
+        self.get_entry(kid).map(|entry| entry.key.deref())
+    }
+
+    /// Returns the metadata of the first key with the specified key identifier or
+    /// [`None`] if the key was not added from a certificate.
+    pub fn get_metadata(&self, kid: &[u8]) -> Option<&KeyMetadata> {
+        self.get_entry(kid)?.metadata.as_ref()
+    }
+
+    /// Returns the first entry with the specified key identifier or
+    /// [`None`] if there is no key with that key ID.
+    pub fn get_entry(&self, kid: &[u8]) -> Option<&TrustListEntry> {
+        self.get_entries(kid).first()
+    }
+
+    /// Returns all the entries with the specified key identifier, in the order they were added.
+    pub fn get_entries(&self, kid: &[u8]) -> &[TrustListEntry] {
+        self.entries.get(kid).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Iterates over all the key identifiers and their entries, in no particular order.
+    ///
+    /// A key identifier shared by several keys is returned once for each of them.
+    pub fn entries(&self) -> impl Iterator<Item = (&[u8], &TrustListEntry)> {
+        self.entries
+            .iter()
+            .flat_map(|(kid, entries)| entries.iter().map(move |entry| (kid.deref(), entry)))
+    }
+
+    /// Iterates over the key identifiers and the entries of the keys issued by the given
+    /// country (ISO 3166 alpha-2 code).
+    pub fn keys_by_country<'a>(
+        &'a self,
+        country: &'a str,
+    ) -> impl Iterator<Item = (&'a [u8], &'a TrustListEntry)> {
+        self.entries().filter(move |(_, entry)| {
+            entry
+                .country
+                .as_deref()
+                .is_some_and(|c| c.eq_ignore_ascii_case(country))
+        })
+    }
+
+    /// Removes all the keys with the specified key identifier, returning their entries.
+    pub fn remove(&mut self, kid: &[u8]) -> Vec<TrustListEntry> {
+        self.entries.remove(kid).unwrap_or_default()
+    }
+
+    /// Returns the number of keys in the trust list.
+    pub fn len(&self) -> usize {
+        self.entries.values().map(Vec::len).sum()
+    }
+
+    /// Checks if the trust list contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
 
     /// Creates a new empty trustlist
     pub fn new() -> Self {
         TrustList {
-            keys: HashMap::new(),
+            entries: HashMap::new(),
+            anchors: Vec::new(),
+        }
+    }
+
+    /// Adds an entry to the [`TrustList`], without checking it against the trust anchors.
+    ///
+    /// If the same key was already added with this key identifier, its entry is replaced,
+    /// otherwise the key is added next to the other keys with the same key identifier.
+    pub fn add_entry(&mut self, kid: &[u8], entry: TrustListEntry) {
+        let entries = self.entries.entry(kid.to_vec()).or_default();
+        match entries.iter_mut().find(|added| added.key == entry.key) {
+            Some(added) => *added = entry,
+            None => entries.push(entry),
         }
     }
 
-    /// Adds a raw public key to the [`TrustList`]
+    /// Adds a raw public key to the [`TrustList`], replacing all the keys with the same key
+    /// identifier. The key is not checked against the trust anchors.
+    ///
+    /// Use [`push_key`](TrustList::push_key) to keep the other keys with the same key identifier.
     pub fn add(&mut self, kid: &[u8], key: Vec<u8>) {
-        self.keys.insert(kid.to_vec(), key);
+        let entry = TrustListEntry::new(key, KeySource::Raw);
+        self.entries.insert(kid.to_vec(), vec![entry]);
+    }
+
+    /// Adds a raw public key to the [`TrustList`] next to the other keys with the same key
+    /// identifier (see [`add_entry`](TrustList::add_entry)). The key is not checked against the
+    /// trust anchors.
+    pub fn push_key(&mut self, kid: &[u8], key: Vec<u8>) {
+        self.add_entry(kid, TrustListEntry::new(key, KeySource::Raw));
     }
 
     /// Adds a public key from a X509 certificate encoded in Base64 (certificate data only, without delimiters).
     /// When using a certificate the KID are the first 8 bytes of the SHA256
     /// hash of the certificate data ([source](https://github.com/eu-digital-green-certificates/dgc-testdata/issues/76#issuecomment-841037329)).
+    ///
+    /// In trust-anchor mode the certificate is added only if it is signed by one of the trust
+    /// anchors, which is returned.
     pub fn add_key_from_certificate(
         &mut self,
         base64_x509_cert: &str,
-    ) -> Result<(), KeyParseError> {
+    ) -> Result<Option<TrustAnchorMatch>, KeyParseError> {
         let decoded = base64::decode(base64_x509_cert)?;
         let certificate_digest = digest::digest(&digest::SHA256, &decoded);
         let kid = &certificate_digest.as_ref()[0..8];
+        let (entry, anchor) = self.entry_from_certificate(&decoded, KeySource::Certificate)?;
+        self.add_entry(kid, entry);
+        Ok(anchor)
+    }
 
-        let certificate = x509_parser::parse_x509_certificate(&decoded)?.1;
-        // TODO: find a way to do this, or make it a warning
-        // certificate
-        //     .verify_signature(None)
-        //     .map_err(|e| KeyParseError::PublicKeyParseError(e.to_string()))?;
+    /// Adds a public key from a X509 certificate encoded in Base64 (certificate data only, without delimiters)
+    /// using the given KID instead of the one derived from the certificate data, and recording
+    /// the source it was loaded from.
+    ///
+    /// In trust-anchor mode the certificate is added only if it is signed by one of the trust
+    /// anchors, which is returned.
+    pub fn add_key_from_certificate_with_kid(
+        &mut self,
+        kid: &[u8],
+        base64_x509_cert: &str,
+        source: KeySource,
+    ) -> Result<Option<TrustAnchorMatch>, KeyParseError> {
+        let decoded = base64::decode(base64_x509_cert)?;
+        let (entry, anchor) = self.entry_from_certificate(&decoded, source)?;
+        self.add_entry(kid, entry);
+        Ok(anchor)
+    }
+
+    /// Creates the entry of the key of a DER encoded X509 certificate, checking that the
+    /// certificate is signed by one of the trust anchors in trust-anchor mode.
+    pub(crate) fn entry_from_certificate(
+        &self,
+        der: &[u8],
+        source: KeySource,
+    ) -> Result<(TrustListEntry, Option<TrustAnchorMatch>), KeyParseError> {
+        let certificate = x509_parser::parse_x509_certificate(der)?.1;
+        let anchor = if self.has_trust_anchors() {
+            Some(self.find_trust_anchor(&certificate)?)
+        } else {
+            None
+        };
         let raw_key = certificate
             .tbs_certificate
             .subject_pki
             .subject_public_key
             .data;
 
-        self.keys.insert(kid.to_vec(), raw_key.to_owned());
+        let entry = TrustListEntry {
+            country: subject_country(&certificate),
+            metadata: Some(KeyMetadata::from_certificate(&certificate)?),
+            ..TrustListEntry::new(raw_key.to_owned(), source)
+        };
+        Ok((entry, anchor))
+    }
 
+    /// Adds a trust anchor (a CSCA certificate) from a X509 certificate encoded in Base64
+    /// (certificate data only, without delimiters), switching the trust list to trust-anchor mode.
+    ///
+    /// The certificate must be a CA certificate allowed to sign certificates, and it must be
+    /// valid now.
+    pub fn add_trust_anchor(&mut self, base64_x509_cert: &str) -> Result<(), KeyParseError> {
+        let decoded = base64::decode(base64_x509_cert)?;
+        let certificate = x509_parser::parse_x509_certificate(&decoded)?.1;
+        check_trust_anchor(&certificate)?;
+        self.anchors.push(decoded);
         Ok(())
     }
 
-    /// Adds a base64 encoded raw key with the specified kid to the trust list
+    /// Checks if the trust list is in trust-anchor mode.
+    pub fn has_trust_anchors(&self) -> bool {
+        !self.anchors.is_empty()
+    }
+
+    /// Verifies that a X509 certificate encoded in Base64 (certificate data only, without
+    /// delimiters) is signed by one of the trust anchors, and returns the matching anchor.
+    pub fn verify_certificate_chain(
+        &self,
+        base64_x509_cert: &str,
+    ) -> Result<TrustAnchorMatch, KeyParseError> {
+        let decoded = base64::decode(base64_x509_cert)?;
+        let certificate = x509_parser::parse_x509_certificate(&decoded)?.1;
+        Ok(self.find_trust_anchor(&certificate)?)
+    }
+
+    fn find_trust_anchor(
+        &self,
+        certificate: &X509Certificate,
+    ) -> Result<TrustAnchorMatch, ChainValidationError> {
+        let issuer = certificate.issuer();
+        let mut candidates = self
+            .anchors
+            .iter()
+            // anchors are validated when added
+            .filter_map(|der| Some((der, x509_parser::parse_x509_certificate(der).ok()?.1)))
+            .filter(|(_, anchor)| anchor.subject().as_raw() == issuer.as_raw())
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(ChainValidationError::NoMatchingAnchor(issuer.to_string()));
+        }
+
+        candidates
+            .find(|(_, anchor)| {
+                certificate
+                    .verify_signature(Some(anchor.public_key()))
+                    .is_ok()
+            })
+            .map(|(der, anchor)| TrustAnchorMatch {
+                subject: anchor.subject().to_string(),
+                country: subject_country(&anchor),
+                fingerprint: digest::digest(&digest::SHA256, der).as_ref().to_vec(),
+            })
+            .ok_or_else(|| ChainValidationError::InvalidSignature(issuer.to_string()))
+    }
+
+    /// Adds a base64 encoded raw key with the specified kid to the trust list, replacing all
+    /// the keys with the same key identifier. The key is not checked against the trust anchors.
     pub fn add_key_from_base64(
         &mut self,
         kid: &[u8],
         base64_key: &str,
     ) -> Result<(), KeyParseError> {
         let raw_data = base64::decode(base64_key)?;
-        self.keys.insert(kid.to_vec(), raw_data);
+        self.add(kid, raw_data);
         Ok(())
     }
 }
@@ -143,6 +669,8 @@ impl Default for TrustList {
     }
 }
 
+/// Creates a trust list from a JSON object of raw public keys indexed by their base64 key
+/// identifiers. The trust list has no trust anchors.
 impl TryFrom<serde_json::Value> for TrustList {
     type Error = TrustListFromJsonError;
 
@@ -201,9 +729,13 @@ impl TryFrom<serde_json::Value> for TrustList {
 
             let decoded_kid =
                 base64::decode(kid).map_err(|e| KidBase64DecodeError(kid.clone(), e))?;
-            trustlist
-                .add_key_from_base64(decoded_kid.as_slice(), base64_der_public_key)
-                .map_err(|e| KeyParseError(kid.clone(), e))?;
+            let raw_key = base64::decode(base64_der_public_key)
+                .map_err(|e| KeyParseError(kid.clone(), e.into()))?;
+            let entry = TrustListEntry {
+                algorithm: KeyAlgorithm::EcdsaP256,
+                ..TrustListEntry::new(raw_key, KeySource::Json)
+            };
+            trustlist.add_entry(&decoded_kid, entry);
         }
 
         Ok(trustlist)
@@ -213,6 +745,7 @@ impl TryFrom<serde_json::Value> for TrustList {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::x509_test_utils::{CertificateParams, TestKey};
     use serde_json::json;
     use std::convert::TryInto;
 
@@ -225,6 +758,18 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn it_rejects_out_of_range_validity_timestamps() {
+        assert_eq!(
+            timestamp_to_utc(1620000000).unwrap(),
+            Utc.timestamp_opt(1620000000, 0).unwrap()
+        );
+        assert!(matches!(
+            timestamp_to_utc(i64::MAX),
+            Err(KeyParseError::InvalidValidityPeriod(i64::MAX))
+        ));
+    }
+
     #[test]
     fn it_adds_a_public_key() {
         let base64_der_public_key = "MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEt5hwD0cJUB5TeQIAaE7nLjeef0vV5mamR30kjErGOcReGe37dDrmFAeOqILajQTiBXzcnPaMxWUd9SK9ZRexzQ==";
@@ -232,7 +777,7 @@ mod tests {
         trustlist
             .add_key_from_base64(&[1, 2, 3], base64_der_public_key)
             .unwrap();
-        assert_eq!(trustlist.keys.len(), 1);
+        assert_eq!(trustlist.len(), 1);
         assert!(trustlist.get_key(&[1, 2, 3]).is_some())
     }
 
@@ -276,8 +821,221 @@ mod tests {
         });
 
         let trustlist: TrustList = data.try_into().unwrap();
-        assert_eq!(trustlist.keys.len(), 2);
+        assert_eq!(trustlist.len(), 2);
         let first_key = trustlist.get_key(&base64::decode("25QCxBrBJvA=").unwrap());
         assert!(first_key.is_some());
     }
+
+    #[test]
+    fn it_queries_the_entries() {
+        let key = TestKey::generate();
+        let it_certificate = CertificateParams::new("DSC", "CSCA").sign(&key, &key);
+        let de_certificate = CertificateParams {
+            country: "DE",
+            ..CertificateParams::new("DSC", "CSCA")
+        }
+        .sign(&key, &key);
+
+        let mut trustlist = TrustList::new();
+        trustlist
+            .add_key_from_certificate_with_kid(
+                b"it",
+                &base64::encode(&it_certificate),
+                KeySource::Dccg,
+            )
+            .unwrap();
+        trustlist
+            .add_key_from_certificate_with_kid(
+                b"de",
+                &base64::encode(&de_certificate),
+                KeySource::Other(String::from("DE list")),
+            )
+            .unwrap();
+        trustlist.add(b"raw", key.public_key().to_vec());
+        assert_eq!(trustlist.len(), 3);
+
+        let mut kids: Vec<_> = trustlist.entries().map(|(kid, _)| kid).collect();
+        kids.sort();
+        assert_eq!(kids, vec![&b"de"[..], &b"it"[..], &b"raw"[..]]);
+
+        let italian: Vec<_> = trustlist.keys_by_country("it").collect();
+        assert_eq!(italian.len(), 1);
+        assert_eq!(italian[0].0, b"it");
+        assert_eq!(italian[0].1.source, KeySource::Dccg);
+
+        let raw = trustlist.get_entry(b"raw").unwrap();
+        assert_eq!(raw.country, None);
+        assert_eq!(raw.source, KeySource::Raw);
+        assert_eq!(raw.algorithm, KeyAlgorithm::EcdsaP256);
+
+        let removed = trustlist.remove(b"de").pop().unwrap();
+        assert_eq!(removed.country.as_deref(), Some("DE"));
+        assert_eq!(removed.source.to_string(), "DE list");
+        assert!(trustlist.get_key(b"de").is_none());
+        assert_eq!(trustlist.keys_by_country("DE").count(), 0);
+        assert_eq!(trustlist.len(), 2);
+    }
+
+    #[test]
+    fn it_keeps_the_keys_sharing_a_kid() {
+        let first = TestKey::generate();
+        let second = TestKey::generate();
+
+        let mut trustlist = TrustList::new();
+        trustlist.push_key(b"kid", first.public_key().to_vec());
+        trustlist.push_key(b"kid", second.public_key().to_vec());
+        // adding the same key again replaces its entry
+        trustlist.add_entry(
+            b"kid",
+            TrustListEntry {
+                country: Some(String::from("DE")),
+                ..TrustListEntry::new(first.public_key().to_vec(), KeySource::Dccg)
+            },
+        );
+
+        assert_eq!(trustlist.len(), 2);
+        assert_eq!(trustlist.entries().count(), 2);
+        let entries = trustlist.get_entries(b"kid");
+        assert_eq!(entries[0].key, first.public_key());
+        assert_eq!(entries[0].source, KeySource::Dccg);
+        assert_eq!(entries[1].key, second.public_key());
+        assert_eq!(trustlist.get_key(b"kid"), Some(first.public_key()));
+        assert!(trustlist.get_entries(b"other").is_empty());
+
+        // a raw key added with `add` replaces the keys with the same kid
+        trustlist.add(b"kid", second.public_key().to_vec());
+        assert_eq!(trustlist.get_entries(b"kid").len(), 1);
+        assert_eq!(trustlist.get_entry(b"kid").unwrap().source, KeySource::Raw);
+
+        assert_eq!(trustlist.remove(b"kid").len(), 1);
+        assert!(trustlist.is_empty());
+    }
+
+    #[test]
+    fn it_validates_certificate_chains() {
+        let csca_key = TestKey::generate();
+        let other_csca_key = TestKey::generate();
+        let dsc_key = TestKey::generate();
+        let csca = CertificateParams::csca("CSCA").sign(&csca_key, &csca_key);
+        let fake_csca = CertificateParams::csca("CSCA").sign(&other_csca_key, &other_csca_key);
+        let other_csca =
+            CertificateParams::csca("Other CSCA").sign(&other_csca_key, &other_csca_key);
+        let dsc = base64::encode(CertificateParams::new("DSC", "CSCA").sign(&dsc_key, &csca_key));
+
+        // without trust anchors every certificate is accepted
+        let mut trustlist = TrustList::new();
+        assert_eq!(trustlist.add_key_from_certificate(&dsc).unwrap(), None);
+
+        let mut trustlist = TrustList::new();
+        trustlist
+            .add_trust_anchor(&base64::encode(&other_csca))
+            .unwrap();
+        assert!(matches!(
+            trustlist.add_key_from_certificate(&dsc),
+            Err(KeyParseError::UntrustedCertificate(
+                ChainValidationError::NoMatchingAnchor(issuer)
+            )) if issuer == "C=IT, CN=CSCA"
+        ));
+
+        trustlist
+            .add_trust_anchor(&base64::encode(&fake_csca))
+            .unwrap();
+        assert!(matches!(
+            trustlist.add_key_from_certificate(&dsc),
+            Err(KeyParseError::UntrustedCertificate(
+                ChainValidationError::InvalidSignature(_)
+            ))
+        ));
+        assert!(trustlist.is_empty());
+
+        trustlist.add_trust_anchor(&base64::encode(&csca)).unwrap();
+        let anchor = trustlist.add_key_from_certificate(&dsc).unwrap().unwrap();
+        assert_eq!(
+            anchor,
+            TrustAnchorMatch {
+                subject: "C=IT, CN=CSCA".to_string(),
+                country: Some("IT".to_string()),
+                fingerprint: digest::digest(&digest::SHA256, &csca).as_ref().to_vec(),
+            }
+        );
+        assert_eq!(trustlist.len(), 1);
+        assert_eq!(trustlist.verify_certificate_chain(&dsc).unwrap(), anchor);
+    }
+
+    #[test]
+    fn it_rejects_invalid_trust_anchors() {
+        let key = TestKey::generate();
+        let add_anchor = |params: CertificateParams| {
+            TrustList::new()
+                .add_trust_anchor(&base64::encode(params.sign(&key, &key)))
+                .map_err(|e| match e {
+                    KeyParseError::InvalidTrustAnchor(e) => e,
+                    e => panic!("unexpected error {}", e),
+                })
+        };
+
+        assert!(add_anchor(CertificateParams::csca("CSCA")).is_ok());
+        assert_eq!(
+            add_anchor(CertificateParams::new("CSCA", "CSCA")),
+            Err(TrustAnchorError::NotCa)
+        );
+        assert_eq!(
+            add_anchor(CertificateParams {
+                key_cert_sign: false,
+                ..CertificateParams::csca("CSCA")
+            }),
+            Err(TrustAnchorError::MissingKeyCertSign)
+        );
+        assert_eq!(
+            add_anchor(CertificateParams {
+                not_before: "100101000000Z",
+                not_after: "201231235959Z",
+                ..CertificateParams::csca("CSCA")
+            }),
+            Err(TrustAnchorError::OutsideValidityPeriod {
+                not_before: Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap(),
+                not_after: Utc.with_ymd_and_hms(2020, 12, 31, 23, 59, 59).unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_metadata_of_certificates() {
+        let key = TestKey::generate();
+        let certificate = CertificateParams {
+            not_before: "210601000000Z",
+            not_after: "230601000000Z",
+            extended_key_usages: &["1.3.6.1.4.1.1847.2021.1.1", "1.3.6.1.4.1.0.1847.2021.1.3"],
+            ..CertificateParams::new("DSC", "CSCA")
+        }
+        .sign(&key, &key);
+        let kid = digest::digest(&digest::SHA256, &certificate).as_ref()[0..8].to_vec();
+
+        let mut trustlist = TrustList::new();
+        trustlist
+            .add_key_from_certificate(&base64::encode(&certificate))
+            .unwrap();
+        let metadata = trustlist.get_metadata(&kid).unwrap();
+        assert_eq!(
+            metadata.not_before,
+            Utc.with_ymd_and_hms(2021, 6, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            metadata.not_after,
+            Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            metadata.allowed_certificate_types(),
+            Some(vec![CertificateType::Test, CertificateType::Recovery])
+        );
+        assert_eq!(metadata.subject, "C=IT, CN=DSC");
+        let entry = trustlist.get_entry(&kid).unwrap();
+        assert_eq!(entry.country.as_deref(), Some("IT"));
+        assert_eq!(entry.algorithm, KeyAlgorithm::EcdsaP256);
+        assert_eq!(entry.source, KeySource::Certificate);
+
+        // replacing the key discards the metadata
+        trustlist.add(&kid, key.public_key().to_vec());
+        assert!(trustlist.get_metadata(&kid).is_none());
+    }
 }
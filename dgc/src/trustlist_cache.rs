@@ -0,0 +1,256 @@
+use crate::{KeyAlgorithm, KeyMetadata, KeySource, TrustList, TrustListEntry};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use thiserror::Error;
+
+/// The version of the format of the cache files written by [`TrustList::write_cache`].
+pub const TRUST_LIST_CACHE_VERSION: u32 = 2;
+
+/// Error struct that represents all the possible errors that can occur
+/// while writing or reading a trust list cache.
+#[derive(Error, Debug)]
+pub enum TrustListCacheError {
+    /// The cache could not be encoded
+    #[error("Cannot encode trust list cache: {0}")]
+    Encode(#[from] ciborium::ser::Error<std::io::Error>),
+    /// The cache could not be decoded
+    #[error("Cannot decode trust list cache: {0}")]
+    Decode(#[from] ciborium::de::Error<std::io::Error>),
+    /// The cache was written with an unsupported version of the format
+    #[error(
+        "Unsupported trust list cache version {0}, expected {}",
+        TRUST_LIST_CACHE_VERSION
+    )]
+    UnsupportedVersion(u32),
+    /// The cache contains a timestamp that cannot be represented
+    #[error("Invalid timestamp {0} in trust list cache")]
+    InvalidTimestamp(i64),
+}
+
+/// A [`TrustList`] read from a cache, together with the moment in time the cache was written.
+#[derive(Debug)]
+pub struct CachedTrustList {
+    /// The trust list
+    pub trust_list: TrustList,
+    /// The moment in time the cache was written
+    pub created_at: DateTime<Utc>,
+}
+
+impl CachedTrustList {
+    /// Checks if the cache is older than `max_age` at the given moment in time.
+    pub fn is_stale(&self, now: DateTime<Utc>, max_age: Duration) -> bool {
+        now - self.created_at > max_age
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    created_at: i64,
+    keys: Vec<CacheEntry>,
+    anchors: Vec<Bytes>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    kid: Bytes,
+    key: Bytes,
+    algorithm: KeyAlgorithm,
+    country: Option<String>,
+    metadata: Option<CacheMetadata>,
+    source: KeySource,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheMetadata {
+    not_before: i64,
+    not_after: i64,
+    extended_key_usages: Vec<String>,
+    subject: String,
+}
+
+/// A binary string, encoded as CBOR bytes instead of an array of integers.
+struct Bytes(Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+            type Value = Bytes;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a byte string")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(Bytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(Bytes(v))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+fn timestamp(seconds: i64) -> Result<DateTime<Utc>, TrustListCacheError> {
+    Utc.timestamp_opt(seconds, 0)
+        .single()
+        .ok_or(TrustListCacheError::InvalidTimestamp(seconds))
+}
+
+impl TrustList {
+    /// Writes the trust list to a compact CBOR cache, marked with the given creation time.
+    ///
+    /// The cache keeps the whole entries of the keys, including the metadata of the certificates
+    /// they were added from, as well as the trust anchors.
+    pub fn write_cache<W: Write>(
+        &self,
+        writer: W,
+        created_at: DateTime<Utc>,
+    ) -> Result<(), TrustListCacheError> {
+        let mut keys: Vec<_> = self
+            .entries()
+            .map(|(kid, entry)| CacheEntry {
+                kid: Bytes(kid.to_vec()),
+                key: Bytes(entry.key.clone()),
+                algorithm: entry.algorithm,
+                country: entry.country.clone(),
+                metadata: entry.metadata.as_ref().map(|metadata| CacheMetadata {
+                    not_before: metadata.not_before.timestamp(),
+                    not_after: metadata.not_after.timestamp(),
+                    extended_key_usages: metadata.extended_key_usages.clone(),
+                    subject: metadata.subject.clone(),
+                }),
+                source: entry.source.clone(),
+            })
+            .collect();
+        // makes the cache reproducible, keeping the order of the keys sharing a kid
+        keys.sort_by(|a, b| a.kid.0.cmp(&b.kid.0));
+
+        let file = CacheFile {
+            version: TRUST_LIST_CACHE_VERSION,
+            created_at: created_at.timestamp(),
+            keys,
+            anchors: self.anchors.iter().cloned().map(Bytes).collect(),
+        };
+        ciborium::ser::into_writer(&file, writer)?;
+        Ok(())
+    }
+
+    /// Reads a trust list from a cache written by [`TrustList::write_cache`].
+    pub fn read_cache<R: Read>(reader: R) -> Result<CachedTrustList, TrustListCacheError> {
+        let file: CacheFile = ciborium::de::from_reader(reader)?;
+        if file.version != TRUST_LIST_CACHE_VERSION {
+            return Err(TrustListCacheError::UnsupportedVersion(file.version));
+        }
+
+        let mut trust_list = TrustList::new();
+        for entry in file.keys {
+            let metadata = match entry.metadata {
+                Some(metadata) => Some(KeyMetadata {
+                    not_before: timestamp(metadata.not_before)?,
+                    not_after: timestamp(metadata.not_after)?,
+                    extended_key_usages: metadata.extended_key_usages,
+                    subject: metadata.subject,
+                }),
+                None => None,
+            };
+            let cached = TrustListEntry {
+                key: entry.key.0,
+                algorithm: entry.algorithm,
+                country: entry.country,
+                metadata,
+                source: entry.source,
+            };
+            trust_list.add_entry(&entry.kid.0, cached);
+        }
+        trust_list.anchors = file.anchors.into_iter().map(|anchor| anchor.0).collect();
+
+        Ok(CachedTrustList {
+            trust_list,
+            created_at: timestamp(file.created_at)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x509_test_utils::{CertificateParams, TestKey};
+
+    #[test]
+    fn it_writes_and_reads_a_cache() {
+        let csca = TestKey::generate();
+        let dsc = TestKey::generate();
+        let anchor = CertificateParams::csca("CSCA").sign(&csca, &csca);
+        let certificate = CertificateParams {
+            extended_key_usages: &["1.3.6.1.4.1.1847.2021.1.2"],
+            ..CertificateParams::new("DSC", "CSCA")
+        }
+        .sign(&dsc, &csca);
+
+        let mut trust_list = TrustList::new();
+        trust_list
+            .add_trust_anchor(&base64::encode(&anchor))
+            .unwrap();
+        trust_list
+            .add_key_from_certificate_with_kid(
+                b"dsc",
+                &base64::encode(&certificate),
+                KeySource::Dccg,
+            )
+            .unwrap();
+        trust_list.push_key(b"raw", vec![0x30, 0x03, 0x02, 0x01, 0x02]);
+        trust_list.push_key(b"raw", vec![0x30, 0x03, 0x02, 0x01, 0x01]);
+
+        let created_at = Utc.with_ymd_and_hms(2021, 6, 1, 12, 0, 0).unwrap();
+        let mut cache = Vec::new();
+        trust_list.write_cache(&mut cache, created_at).unwrap();
+
+        let cached = TrustList::read_cache(cache.as_slice()).unwrap();
+        assert_eq!(cached.created_at, created_at);
+        assert!(!cached.is_stale(created_at + Duration::days(1), Duration::days(2)));
+        assert!(cached.is_stale(created_at + Duration::days(3), Duration::days(2)));
+
+        let read = cached.trust_list;
+        assert_eq!(read.entries, trust_list.entries);
+        assert_eq!(read.anchors, trust_list.anchors);
+        let entry = read.get_entry(b"dsc").unwrap();
+        assert_eq!(entry.country.as_deref(), Some("IT"));
+        assert_eq!(entry.source, KeySource::Dccg);
+        assert!(read.get_metadata(b"raw").is_none());
+
+        // the same trust list always produces the same cache
+        let mut other_cache = Vec::new();
+        read.write_cache(&mut other_cache, created_at).unwrap();
+        assert_eq!(cache, other_cache);
+    }
+
+    #[test]
+    fn it_rejects_caches_with_other_versions() {
+        let file = CacheFile {
+            version: TRUST_LIST_CACHE_VERSION + 1,
+            created_at: 0,
+            keys: Vec::new(),
+            anchors: Vec::new(),
+        };
+        let mut cache = Vec::new();
+        ciborium::ser::into_writer(&file, &mut cache).unwrap();
+
+        assert!(matches!(
+            TrustList::read_cache(cache.as_slice()),
+            Err(TrustListCacheError::UnsupportedVersion(3))
+        ));
+    }
+}
@@ -0,0 +1,510 @@
+use crate::{der, KeyAlgorithm, KeyParseError, KeySource, TrustList, TrustListEntry};
+use ring::signature;
+use serde_json::Value;
+use thiserror::Error;
+
+/// Error struct that represents all the possible errors that can occur
+/// while trying to load a whole trust list document.
+#[derive(Error, Debug)]
+pub enum TrustListLoadError {
+    /// The given document is not valid JSON
+    #[error("Cannot parse trust list JSON: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    /// The given document does not have the expected structure
+    #[error("Invalid trust list structure: {0}")]
+    InvalidStructure(&'static str),
+    /// The given signed document does not start with a signature line
+    #[error("The trust list does not start with a signature line")]
+    MissingSignature,
+    /// The signature line of the given signed document could not be decoded using base64
+    #[error("Cannot base64 decode the trust list signature: {0}")]
+    SignatureDecodeError(#[source] base64::DecodeError),
+    /// The signature of the given signed document does not match the signing key
+    #[error("The trust list signature is not valid")]
+    InvalidSignature,
+    /// The signing key is not an ECDSA P-256 or P-384 key
+    #[error("Cannot verify the trust list signature with a {0} key")]
+    UnsupportedSigningKey(KeyAlgorithm),
+}
+
+/// Error struct that represents all the possible errors that can occur
+/// while trying to load a single entry of a trust list.
+///
+/// The first field identifies the entry: it is the KID as found in the document or,
+/// when the KID is missing, the position of the entry (e.g. `#3`).
+#[derive(Error, Debug)]
+pub enum TrustListEntryError {
+    /// The entry is not an object
+    #[error("Entry '{0}' is not an object")]
+    NotAnObject(String),
+    /// The entry does not contain a required field
+    #[error("Entry '{0}' does not contain '{1}'")]
+    MissingField(String, &'static str),
+    /// A field of the entry does not have the expected type
+    #[error("'{1}' for entry '{0}' is not a string")]
+    InvalidField(String, &'static str),
+    /// A field of the entry could not be decoded using base64
+    #[error("Cannot base64 decode '{1}' for entry '{0}': {2}")]
+    Base64DecodeError(String, &'static str, #[source] base64::DecodeError),
+    /// The entry uses a key algorithm that is not supported
+    #[error("Entry '{0}' uses the unsupported key algorithm '{1}'")]
+    UnsupportedAlgorithm(String, String),
+    /// The key of the entry could not be added to the trust list
+    #[error("Cannot add the key for entry '{0}': {1}")]
+    KeyParseError(String, #[source] KeyParseError),
+}
+
+/// The outcome of loading a trust list document.
+///
+/// Invalid entries do not prevent the other entries from being loaded: their errors are
+/// collected here instead.
+#[derive(Debug, Default)]
+pub struct TrustListLoadReport {
+    /// The number of keys added to the trust list
+    pub loaded: usize,
+    /// The errors of the entries that could not be loaded
+    pub errors: Vec<TrustListEntryError>,
+}
+
+impl TrustListLoadReport {
+    /// Checks if all the entries were loaded
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Records the outcome of loading a single entry
+    pub fn record(&mut self, outcome: Result<(), TrustListEntryError>) {
+        match outcome {
+            Ok(()) => self.loaded += 1,
+            Err(e) => self.errors.push(e),
+        }
+    }
+}
+
+/// Splits a signed trust list into its signature line and its content.
+pub(crate) fn split_signed_trust_list(data: &str) -> Result<(&str, &str), TrustListLoadError> {
+    let (signature, content) = data
+        .trim_start()
+        .split_once('\n')
+        .ok_or(TrustListLoadError::MissingSignature)?;
+    let signature = signature.trim();
+    if signature.is_empty() || signature.starts_with('{') {
+        return Err(TrustListLoadError::MissingSignature);
+    }
+    Ok((signature, content))
+}
+
+/// Verifies the signature of a signed trust list against the pinned key of the trust list
+/// signer, and returns the signed content.
+///
+/// The signing key is an uncompressed P-256 or P-384 point, and the signature line contains
+/// the base64 encoded ECDSA signature of the content, either ASN.1 DER encoded or as the
+/// concatenation of `r` and `s`. The content is hashed with SHA-256 for P-256 keys and with
+/// SHA-384 for P-384 keys, whatever the encoding of the signature.
+pub fn verify_signed_trust_list<'a>(
+    data: &'a str,
+    signing_key: &[u8],
+) -> Result<&'a str, TrustListLoadError> {
+    let (encoded_signature, content) = split_signed_trust_list(data)?;
+    let signature =
+        base64::decode(encoded_signature).map_err(TrustListLoadError::SignatureDecodeError)?;
+
+    let algorithm = match (KeyAlgorithm::of(signing_key), signature.len()) {
+        (KeyAlgorithm::EcdsaP256, 64) => &signature::ECDSA_P256_SHA256_FIXED,
+        (KeyAlgorithm::EcdsaP256, _) => &signature::ECDSA_P256_SHA256_ASN1,
+        (KeyAlgorithm::EcdsaP384, 96) => &signature::ECDSA_P384_SHA384_FIXED,
+        (KeyAlgorithm::EcdsaP384, _) => &signature::ECDSA_P384_SHA384_ASN1,
+        (algorithm, _) => return Err(TrustListLoadError::UnsupportedSigningKey(algorithm)),
+    };
+    signature::UnparsedPublicKey::new(algorithm, signing_key)
+        .verify(content.as_bytes(), &signature)
+        .map_err(|_| TrustListLoadError::InvalidSignature)?;
+    Ok(content)
+}
+
+fn entry_id(entry: &Value, key: &str, index: usize) -> String {
+    entry
+        .get(key)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .unwrap_or_else(|| format!("#{}", index))
+}
+
+fn get_str<'a>(
+    entry: &'a Value,
+    id: &str,
+    field: &'static str,
+) -> Result<&'a str, TrustListEntryError> {
+    entry
+        .get(field)
+        .ok_or_else(|| TrustListEntryError::MissingField(id.to_string(), field))?
+        .as_str()
+        .ok_or_else(|| TrustListEntryError::InvalidField(id.to_string(), field))
+}
+
+fn decode_field(
+    entry: &Value,
+    id: &str,
+    field: &'static str,
+) -> Result<Vec<u8>, TrustListEntryError> {
+    base64::decode(get_str(entry, id, field)?)
+        .map_err(|e| TrustListEntryError::Base64DecodeError(id.to_string(), field, e))
+}
+
+impl TrustList {
+    /// Loads the keys of a signed DSC list, as published by the German and the Austrian
+    /// trust list services, without verifying its signature.
+    ///
+    /// The document starts with a line containing the signature of the list, followed by a
+    /// JSON object whose `certificates` are entries with a base64 `kid` and the base64 X509
+    /// certificate in `rawData`. Use [`TrustList::load_verified_dsc_list`] to refuse lists that
+    /// are not signed by a trusted key.
+    pub fn load_signed_dsc_list(
+        &mut self,
+        data: &str,
+    ) -> Result<TrustListLoadReport, TrustListLoadError> {
+        let (_, content) = split_signed_trust_list(data)?;
+        self.load_dsc_list_content(content)
+    }
+
+    /// Loads the keys of a signed DSC list after verifying its signature against the pinned key
+    /// of the trust list signer (see [`verify_signed_trust_list`]).
+    ///
+    /// When the signature does not match, no key is loaded.
+    pub fn load_verified_dsc_list(
+        &mut self,
+        data: &str,
+        signing_key: &[u8],
+    ) -> Result<TrustListLoadReport, TrustListLoadError> {
+        let content = verify_signed_trust_list(data, signing_key)?;
+        self.load_dsc_list_content(content)
+    }
+
+    fn load_dsc_list_content(
+        &mut self,
+        content: &str,
+    ) -> Result<TrustListLoadReport, TrustListLoadError> {
+        let document: Value = serde_json::from_str(content)?;
+        let entries = document
+            .get("certificates")
+            .and_then(Value::as_array)
+            .ok_or(TrustListLoadError::InvalidStructure(
+                "'certificates' is not an array",
+            ))?;
+        Ok(self.load_dsc_entries(entries, KeySource::SignedDscList))
+    }
+
+    /// Loads the keys of the JSON trust list exposed by the `/trustList/DSC` endpoint of the
+    /// EU Digital Green Certificate Gateway (DCCG).
+    ///
+    /// The document is an array of entries with a base64 `kid` and the base64 X509
+    /// certificate in `rawData`.
+    pub fn load_dccg_trust_list(
+        &mut self,
+        data: &str,
+    ) -> Result<TrustListLoadReport, TrustListLoadError> {
+        let document: Value = serde_json::from_str(data)?;
+        let entries = document
+            .as_array()
+            .ok_or(TrustListLoadError::InvalidStructure(
+                "the document is not an array",
+            ))?;
+        Ok(self.load_dsc_entries(entries, KeySource::Dccg))
+    }
+
+    /// Loads the keys of the Swiss trust list, as exposed by its `keys/updates` endpoint.
+    ///
+    /// The document is an object whose `certs` are JSON Web Keys with a base64 `keyId`:
+    /// `ES256` keys carry the coordinates of the point in `x` and `y`, while `RS256` keys carry
+    /// the modulus in `n` and the exponent in `e`.
+    ///
+    /// The list only contains raw public keys, so they are not checked against the trust
+    /// anchors.
+    pub fn load_swiss_trust_list(
+        &mut self,
+        data: &str,
+    ) -> Result<TrustListLoadReport, TrustListLoadError> {
+        let document: Value = serde_json::from_str(data)?;
+        let entries = document.get("certs").and_then(Value::as_array).ok_or(
+            TrustListLoadError::InvalidStructure("'certs' is not an array"),
+        )?;
+
+        let mut report = TrustListLoadReport::default();
+        for (index, entry) in entries.iter().enumerate() {
+            report.record(self.load_swiss_entry(entry, index));
+        }
+        Ok(report)
+    }
+
+    fn load_dsc_entries(&mut self, entries: &[Value], source: KeySource) -> TrustListLoadReport {
+        let mut report = TrustListLoadReport::default();
+        for (index, entry) in entries.iter().enumerate() {
+            report.record(self.load_dsc_entry(entry, index, source.clone()));
+        }
+        report
+    }
+
+    fn load_dsc_entry(
+        &mut self,
+        entry: &Value,
+        index: usize,
+        source: KeySource,
+    ) -> Result<(), TrustListEntryError> {
+        let id = entry_id(entry, "kid", index);
+        if !entry.is_object() {
+            return Err(TrustListEntryError::NotAnObject(id));
+        }
+        let kid = decode_field(entry, &id, "kid")?;
+        let raw_data = get_str(entry, &id, "rawData")?;
+        let der = base64::decode(raw_data)
+            .map_err(|e| TrustListEntryError::KeyParseError(id.clone(), e.into()))?;
+        let (mut added, _) = self
+            .entry_from_certificate(&der, source)
+            .map_err(|e| TrustListEntryError::KeyParseError(id, e))?;
+
+        // the country of the entry is used when the certificate does not declare one
+        if added.country.is_none() {
+            added.country = entry
+                .get("country")
+                .and_then(Value::as_str)
+                .map(String::from);
+        }
+        self.add_entry(&kid, added);
+        Ok(())
+    }
+
+    fn load_swiss_entry(&mut self, entry: &Value, index: usize) -> Result<(), TrustListEntryError> {
+        let id = entry_id(entry, "keyId", index);
+        if !entry.is_object() {
+            return Err(TrustListEntryError::NotAnObject(id));
+        }
+        let kid = decode_field(entry, &id, "keyId")?;
+        let key = match get_str(entry, &id, "alg")? {
+            "ES256" => {
+                let x = decode_field(entry, &id, "x")?;
+                let y = decode_field(entry, &id, "y")?;
+                // uncompressed point
+                [&[0x04], x.as_slice(), y.as_slice()].concat()
+            }
+            "RS256" => {
+                let n = decode_field(entry, &id, "n")?;
+                let e = decode_field(entry, &id, "e")?;
+                // RSAPublicKey (PKCS#1)
+                der::sequence(&[der::integer(&n), der::integer(&e)])
+            }
+            alg => {
+                return Err(TrustListEntryError::UnsupportedAlgorithm(
+                    id,
+                    alg.to_string(),
+                ))
+            }
+        };
+        self.add_entry(&kid, TrustListEntry::new(key, KeySource::Swiss));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x509_test_utils::{CertificateParams, TestKey};
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair},
+    };
+    use serde_json::json;
+
+    fn certificate() -> (Vec<u8>, String) {
+        let key = TestKey::generate();
+        let certificate = CertificateParams::new("DSC", "CSCA").sign(&key, &key);
+        (key.public_key().to_vec(), base64::encode(certificate))
+    }
+
+    #[test]
+    fn it_loads_a_dccg_trust_list() {
+        let (public_key, raw_data) = certificate();
+        let data = json!([
+            {
+                "kid": "AAECAwQFBgc=",
+                "country": "IT",
+                "certificateType": "DSC",
+                "rawData": raw_data,
+            },
+            { "kid": "AQIDBAUGBwg=", "rawData": "not a certificate" },
+            { "country": "IT", "rawData": raw_data },
+            "invalid",
+        ])
+        .to_string();
+
+        let mut trustlist = TrustList::new();
+        let report = trustlist.load_dccg_trust_list(&data).unwrap();
+
+        assert_eq!(report.loaded, 1);
+        assert!(!report.is_complete());
+        assert!(matches!(
+            &report.errors[..],
+            [
+                TrustListEntryError::KeyParseError(kid, KeyParseError::Base64DecodeError(_)),
+                TrustListEntryError::MissingField(index, "kid"),
+                TrustListEntryError::NotAnObject(last),
+            ] if kid == "AQIDBAUGBwg=" && index == "#2" && last == "#3"
+        ));
+        assert_eq!(
+            trustlist.get_key(&[0, 1, 2, 3, 4, 5, 6, 7]),
+            Some(public_key.as_slice())
+        );
+        let entry = trustlist.get_entry(&[0, 1, 2, 3, 4, 5, 6, 7]).unwrap();
+        assert!(entry.metadata.is_some());
+        assert_eq!(entry.country.as_deref(), Some("IT"));
+        assert_eq!(entry.source, KeySource::Dccg);
+    }
+
+    #[test]
+    fn it_loads_a_signed_dsc_list() {
+        let (public_key, raw_data) = certificate();
+        let data = format!(
+            "MEUCIQDh6i0dRuL6Bq9n6Lnd7aVybWNR0eVxnrdGQ4DHmaHTsAIgW0YFZ8l4w2tw6Qsy8SKmfP1FfVuv1fGvQbrRBrP+T2s=\n{}",
+            json!({
+                "certificates": [{
+                    "certificateType": "DSC",
+                    "country": "DE",
+                    "kid": "AAECAwQFBgc=",
+                    "rawData": raw_data,
+                }]
+            })
+        );
+
+        let mut trustlist = TrustList::new();
+        let report = trustlist.load_signed_dsc_list(&data).unwrap();
+
+        assert_eq!(report.loaded, 1);
+        assert!(report.is_complete());
+        assert_eq!(
+            trustlist.get_key(&[0, 1, 2, 3, 4, 5, 6, 7]),
+            Some(public_key.as_slice())
+        );
+
+        let unsigned = json!({ "certificates": [] }).to_string();
+        assert!(matches!(
+            trustlist.load_signed_dsc_list(&unsigned),
+            Err(TrustListLoadError::MissingSignature)
+        ));
+        assert!(matches!(
+            trustlist.load_signed_dsc_list("signature\n[]"),
+            Err(TrustListLoadError::InvalidStructure(_))
+        ));
+    }
+
+    #[test]
+    fn it_verifies_the_signature_of_a_dsc_list() {
+        let (public_key, raw_data) = certificate();
+        let content = json!({
+            "certificates": [{ "kid": "AAECAwQFBgc=", "rawData": raw_data }]
+        })
+        .to_string();
+        let signer = TestKey::generate();
+        let data = format!(
+            "{}\n{}",
+            base64::encode(signer.sign(content.as_bytes())),
+            content
+        );
+
+        let mut trustlist = TrustList::new();
+        let report = trustlist
+            .load_verified_dsc_list(&data, signer.public_key())
+            .unwrap();
+        assert_eq!(report.loaded, 1);
+        assert_eq!(
+            trustlist.get_key(&[0, 1, 2, 3, 4, 5, 6, 7]),
+            Some(public_key.as_slice())
+        );
+
+        let mut trustlist = TrustList::new();
+        let other_signer = TestKey::generate();
+        assert!(matches!(
+            trustlist.load_verified_dsc_list(&data, other_signer.public_key()),
+            Err(TrustListLoadError::InvalidSignature)
+        ));
+        let tampered = data.replace("AAECAwQFBgc=", "AQIDBAUGBwg=");
+        assert!(matches!(
+            trustlist.load_verified_dsc_list(&tampered, signer.public_key()),
+            Err(TrustListLoadError::InvalidSignature)
+        ));
+        assert!(matches!(
+            trustlist.load_verified_dsc_list(&format!("!!!\n{}", content), signer.public_key()),
+            Err(TrustListLoadError::SignatureDecodeError(_))
+        ));
+        assert!(trustlist.get_key(&[0, 1, 2, 3, 4, 5, 6, 7]).is_none());
+        assert!(trustlist.get_key(&[1, 2, 3, 4, 5, 6, 7, 8]).is_none());
+    }
+
+    #[test]
+    fn it_hashes_with_sha384_for_p384_signing_keys() {
+        let rng = SystemRandom::new();
+        let content = "{\"certificates\": []}";
+        for signing_algorithm in [
+            &signature::ECDSA_P384_SHA384_ASN1_SIGNING,
+            &signature::ECDSA_P384_SHA384_FIXED_SIGNING,
+        ] {
+            let pkcs8 = EcdsaKeyPair::generate_pkcs8(signing_algorithm, &rng).unwrap();
+            let key_pair = EcdsaKeyPair::from_pkcs8(signing_algorithm, pkcs8.as_ref()).unwrap();
+            let signature = key_pair.sign(&rng, content.as_bytes()).unwrap();
+            let data = format!("{}\n{}", base64::encode(signature.as_ref()), content);
+            assert_eq!(
+                verify_signed_trust_list(&data, key_pair.public_key().as_ref()).unwrap(),
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn it_rejects_rsa_signing_keys() {
+        let data = format!("{}\n{{}}", base64::encode([0u8; 256]));
+        assert!(matches!(
+            verify_signed_trust_list(&data, &[0x30, 0x82, 0x01, 0x0a]),
+            Err(TrustListLoadError::UnsupportedSigningKey(KeyAlgorithm::Rsa))
+        ));
+    }
+
+    #[test]
+    fn it_loads_a_swiss_trust_list() {
+        let key = TestKey::generate();
+        let point = key.public_key();
+        let data = json!({
+            "certs": [
+                {
+                    "keyId": "AAECAwQFBgc=",
+                    "use": "sig",
+                    "alg": "ES256",
+                    "crv": "P-256",
+                    "x": base64::encode(&point[1..33]),
+                    "y": base64::encode(&point[33..]),
+                },
+                {
+                    "keyId": "AQIDBAUGBwg=",
+                    "use": "sig",
+                    "alg": "RS256",
+                    "n": base64::encode([0x80, 0x01]),
+                    "e": base64::encode([0x01, 0x00, 0x01]),
+                },
+                { "keyId": "AgMEBQYHCAk=", "alg": "EdDSA" },
+            ]
+        })
+        .to_string();
+
+        let mut trustlist = TrustList::new();
+        let report = trustlist.load_swiss_trust_list(&data).unwrap();
+
+        assert_eq!(report.loaded, 2);
+        assert!(matches!(
+            &report.errors[..],
+            [TrustListEntryError::UnsupportedAlgorithm(kid, alg)]
+                if kid == "AgMEBQYHCAk=" && alg == "EdDSA"
+        ));
+        assert_eq!(trustlist.get_key(&[0, 1, 2, 3, 4, 5, 6, 7]), Some(point));
+        assert_eq!(
+            trustlist.get_key(&[1, 2, 3, 4, 5, 6, 7, 8]),
+            Some(&[0x30, 0x0a, 0x02, 0x03, 0x00, 0x80, 0x01, 0x02, 0x03, 0x01, 0x00, 0x01][..])
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use crate::lookup_value;
+use crate::{dates::parse_date, lookup_value, DateParseError};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::fmt;
@@ -50,6 +51,11 @@ impl Vaccination {
         lookup_value(&mut self.manufacturer);
         lookup_value(&mut self.country);
     }
+
+    /// The date of vaccination as a typed value
+    pub fn parsed_date(&self) -> Result<NaiveDate, DateParseError> {
+        parse_date(&self.date)
+    }
 }
 
 impl fmt::Display for Vaccination {
@@ -0,0 +1,691 @@
+use crate::{
+    check_issuer_country,
+    parse::{check_signature, decode_stages},
+    Cwt, DecodeStage, DgcContainer, IssuerCountryValidity, Leniency, TimeValidity, TrustList,
+};
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+
+/// The checks performed by a [`Verifier`], in the order in which they are executed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    /// The data starts with the `HC1:` prefix
+    Prefix,
+    /// The data can be decoded using base45
+    Base45Decode,
+    /// The data can be decompressed using zlib
+    Decompression,
+    /// The data contains a valid CWT
+    CwtDecode,
+    /// The signature is valid for one of the keys in the trustlist
+    Signature,
+    /// The container is neither expired nor not yet valid
+    TimeValidity,
+    /// The issuer and the entries of the container are from the country of the signing key
+    /// (only when enabled with [`Verifier::with_issuer_country_check`])
+    IssuerCountry,
+    /// A custom [`VerificationRule`], identified by its name
+    Rule(String),
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Check::Prefix => write!(f, "Prefix"),
+            Check::Base45Decode => write!(f, "Base45 decoding"),
+            Check::Decompression => write!(f, "Decompression"),
+            Check::CwtDecode => write!(f, "CWT decoding"),
+            Check::Signature => write!(f, "Signature"),
+            Check::TimeValidity => write!(f, "Time validity"),
+            Check::IssuerCountry => write!(f, "Issuer country"),
+            Check::Rule(name) => write!(f, "Rule '{}'", name),
+        }
+    }
+}
+
+/// The outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// The check succeeded
+    Passed,
+    /// The check failed, making the certificate invalid
+    Failed,
+    /// The check found something suspicious that does not make the certificate invalid
+    Warning,
+    /// The check was not performed (e.g. because a previous check failed)
+    /// or it could not reach a conclusion
+    Skipped,
+}
+
+/// The outcome of a check together with a human readable explanation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    /// The outcome of the check
+    pub outcome: CheckOutcome,
+    /// Why the check had the given outcome
+    pub reason: String,
+}
+
+impl CheckResult {
+    /// Creates a successful result
+    pub fn passed(reason: impl Into<String>) -> Self {
+        CheckResult {
+            outcome: CheckOutcome::Passed,
+            reason: reason.into(),
+        }
+    }
+
+    /// Creates a failed result
+    pub fn failed(reason: impl Into<String>) -> Self {
+        CheckResult {
+            outcome: CheckOutcome::Failed,
+            reason: reason.into(),
+        }
+    }
+
+    /// Creates a warning result
+    pub fn warning(reason: impl Into<String>) -> Self {
+        CheckResult {
+            outcome: CheckOutcome::Warning,
+            reason: reason.into(),
+        }
+    }
+
+    /// Creates a skipped result
+    pub fn skipped(reason: impl Into<String>) -> Self {
+        CheckResult {
+            outcome: CheckOutcome::Skipped,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A check performed by a [`Verifier`], with its result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckReport {
+    /// The check that was performed
+    pub check: Check,
+    /// The result of the check
+    pub result: CheckResult,
+}
+
+/// The final status of a verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationStatus {
+    /// None of the checks failed
+    Valid,
+    /// At least one of the checks failed
+    Invalid,
+}
+
+impl fmt::Display for VerificationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationStatus::Valid => write!(f, "Valid"),
+            VerificationStatus::Invalid => write!(f, "Invalid"),
+        }
+    }
+}
+
+/// The result of verifying a certificate with a [`Verifier`].
+#[derive(Debug, Clone)]
+pub struct VerificationReport {
+    /// The final status of the verification
+    pub status: VerificationStatus,
+    /// All the checks, in the order in which they were performed
+    pub checks: Vec<CheckReport>,
+    /// The data contained in the certificate, if it could be decoded
+    pub container: Option<DgcContainer>,
+    /// The key identifier of the trust list key that matched the signature, if any
+    pub matched_kid: Option<Vec<u8>>,
+}
+
+impl VerificationReport {
+    /// Checks if the certificate passed the verification
+    pub fn is_valid(&self) -> bool {
+        self.status == VerificationStatus::Valid
+    }
+
+    /// Returns the result of the given check, if it was performed
+    pub fn get(&self, check: &Check) -> Option<&CheckResult> {
+        self.checks
+            .iter()
+            .find(|report| &report.check == check)
+            .map(|report| &report.result)
+    }
+
+    /// Returns the checks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &CheckReport> {
+        self.checks
+            .iter()
+            .filter(|report| report.result.outcome == CheckOutcome::Failed)
+    }
+
+    /// Returns the checks that reported a warning
+    pub fn warnings(&self) -> impl Iterator<Item = &CheckReport> {
+        self.checks
+            .iter()
+            .filter(|report| report.result.outcome == CheckOutcome::Warning)
+    }
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.status)?;
+        for report in &self.checks {
+            writeln!(
+                f,
+                "  {}: {:?} ({})",
+                report.check, report.result.outcome, report.result.reason
+            )?;
+        }
+        Ok(())
+    }
+}
+
+const DECODING_CHECKS: [Check; 4] = [
+    Check::Prefix,
+    Check::Base45Decode,
+    Check::Decompression,
+    Check::CwtDecode,
+];
+
+/// Runs the decoding checks, stopping at the first failure.
+///
+/// With lenient decoding, the leniencies that were applied are reported as warnings.
+fn decode(data: &str, lenient: bool, checks: &mut Vec<CheckReport>) -> Option<Cwt> {
+    let (artefacts, result) = decode_stages(data, lenient);
+    let passed = |leniency, warning: &str, passed: &str| {
+        if artefacts.leniencies.contains(&leniency) {
+            CheckResult::warning(warning)
+        } else {
+            CheckResult::passed(passed)
+        }
+    };
+    let passed_checks = [
+        (
+            Check::Prefix,
+            passed(
+                Leniency::StrippedWhitespace,
+                "Found 'HC1:' prefix after removing whitespace",
+                "Found 'HC1:' prefix",
+            ),
+        ),
+        (
+            Check::Base45Decode,
+            CheckResult::passed("Valid base45 data"),
+        ),
+        (
+            Check::Decompression,
+            passed(
+                Leniency::Uncompressed,
+                "The data is not compressed, parsed as raw COSE",
+                "Valid zlib data",
+            ),
+        ),
+        (Check::CwtDecode, CheckResult::passed("Valid CWT data")),
+    ];
+
+    let failure = result
+        .as_ref()
+        .err()
+        .map(|(stage, error)| (decoding_check(*stage), error.to_string()));
+    for (check, passed) in passed_checks {
+        match &failure {
+            Some((failed_check, error)) if *failed_check == check => {
+                let result = CheckResult::failed(error);
+                checks.push(CheckReport { check, result });
+                return None;
+            }
+            _ => checks.push(CheckReport {
+                check,
+                result: passed,
+            }),
+        }
+    }
+    result.ok()
+}
+
+/// The check reporting the outcome of a decoding stage.
+fn decoding_check(stage: DecodeStage) -> Check {
+    match stage {
+        DecodeStage::Prefix => Check::Prefix,
+        DecodeStage::Base45 => Check::Base45Decode,
+        DecodeStage::Decompression => Check::Decompression,
+        DecodeStage::Cose | DecodeStage::Payload => Check::CwtDecode,
+    }
+}
+
+/// A custom check that can be added to a [`Verifier`].
+///
+/// Rules are evaluated only when the certificate could be decoded, after all the
+/// built-in checks.
+pub trait VerificationRule {
+    /// A name that identifies the rule in the [`VerificationReport`]
+    fn name(&self) -> String;
+
+    /// Checks the content of the certificate at the given moment in time
+    fn check(&self, container: &DgcContainer, validation_clock: DateTime<Utc>) -> CheckResult;
+}
+
+/// Verifies a certificate running all the checks needed to establish if it can be trusted:
+/// decoding, signature, time validity and any number of custom [`VerificationRule`]s.
+///
+/// ## Example
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+///
+/// let raw_certificate_data = "HC1:NCF:603A0T9WTWGSLKC 4K694WJN.0J$6C-7WAB0XK3JCSGA2F3R8PP4V2F35VPP.EY50.FK8ZKO/EZKEZ96LF6/A6..DV%DZJC0/D5UA QELPCG/DYUCHY83UAGVC*JCNF6F463W5KF6VF6IECSHG4KCD3DX47B46IL6646H*6MWEWJDA6A:961A6Q47EM6B$DFOC0R63KCZPCNF6OF63W5$Q6+96/SA5R6NF61G73564KC*KETF6A46.96646B565WEC.D1$CKWEDZC6VCS446$C4WEUPC3JCUIA+ED$.EF$DMWE8$CBJEMVCB445$CBWER.CGPC4WEOPCE8FHZA1+9LZAZM81G72A62+8OG7J09U47AB8V59T%6ZHBO57X48RUIY03XQOK*FZUNM UFY4D5C S3R9UW-2R*4KZJT5M MIM:03RMZNA LKTO34PA.H51966PS0KAP-KLPH.Q6$KSTJ0-G658RL5HR1";
+/// let signature_certificate = "MIIDujCCAaKgAwIBAgIIKUgZWBL1pnMwDQYJKoZIhvcNAQELBQAwZjELMAkGA1UEBhMCRlIxHTAbBgNVBAoTFElNUFJJTUVSSUUgTkFUSU9OQUxFMR4wHAYDVQQLExVGT1IgVEVTVCBQVVJQT1NFIE9OTFkxGDAWBgNVBAMTD0lOR1JPVVBFIERTYyBDQTAeFw0yMTA2MDIxMjE0MDBaFw0yMTA5MDIxMjE0MDBaMEAxCzAJBgNVBAYTAkZSMREwDwYDVQQKDAhDRVJUSUdOQTEeMBwGA1UEAwwVQ0VSVElHTkEgLSBURVNUIERHQyAxMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAETdygPqv/l6tWFqHFEIEZxfdhtbrBpDgVjmUN4CKOu/EQFwkVVQ/4N0BamwtI0hSnSZP72byk6XqpMErYWRTCbKNdMFswCQYDVR0TBAIwADAdBgNVHQ4EFgQUUjXs7mCY2ZgROQSsw1CN0qM4Zj8wHwYDVR0jBBgwFoAUYLoYTllzE2jOy3VMAuU4OJjOingwDgYDVR0PAQH/BAQDAgeAMA0GCSqGSIb3DQEBCwUAA4ICAQAvxuSBWNOrk+FRIbU42tnwZBllUeNH7cWcrYHV0O+1k3RbpvYa0YE2J0du301/a+0+pqlatR8o8Coe/NFt4/KSu+To+i8uZXiHJn2XrAZgwPqqTvsMUVwFPWhwJpLMCejmU0A8JEhXH7s0BN6orqIH0JKLpl0/MdVviIUksnxPnP2wdCtz6dL5zKhi+Qt8BFr55PL1dvuWxnuFOsKr89MqaexQVe/WvKhG5GXBaJFDbp4USVX9Z8vwp4SfEs5nh0ti0M2fyGrpfPvWWFra/qoRGAUJEPHHPMqZT45c1rXo12+cpme2CYM4rsliQsaqdH462p7YNNI5reBC+WHhzGr9FGq9yZ1gu/yhz1cJxNwE5gsBTWnJmSnRE75lYj1a/GAb+9wfABd1Vx68Fnww3Ngp8lG2T1vEWhwQusj/OmloVbqjJiCi6PcZ1/OSTbx58Zv9ySwDd3QGxPygfMy87FuhT6iWlPv57qTMrgtEjq89J8v3WnReAhp12ru5ehN2Zv0ZkO1Of0H3yxNBsvfHUgpgwsRn4zjLVbkU+a3hr4famOThmB1X0tuikY0mbNtVejPGS0qCgeLgj8ILlUrRtsW4R6WzZdIsz7H9AYnpyZbdMPsa856xBR9s0+AzguJI9kkJxvVcpR//GiXMhs0EdgWj2rouOEPZiFNdWpVRrxv/kw==";
+///
+/// let mut trustlist = dgc::TrustList::default();
+/// trustlist
+///     .add_key_from_certificate(signature_certificate)
+///     .expect("Failed to add key from certificate");
+///
+/// let report = dgc::Verifier::new(&trustlist)
+///     .with_validation_clock(Utc.with_ymd_and_hms(2021, 6, 15, 0, 0, 0).unwrap())
+///     .with_clock_skew(chrono::Duration::minutes(5))
+///     .verify(raw_certificate_data);
+///
+/// assert!(report.is_valid());
+/// println!("{}", report);
+/// ```
+pub struct Verifier<'a> {
+    trustlist: &'a TrustList,
+    validation_clock: Option<DateTime<Utc>>,
+    clock_skew: Duration,
+    check_issuer_country: bool,
+    lenient_decoding: bool,
+    rules: Vec<Box<dyn VerificationRule + 'a>>,
+}
+
+impl<'a> fmt::Debug for Verifier<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Verifier")
+            .field("trustlist", &self.trustlist)
+            .field("validation_clock", &self.validation_clock)
+            .field("clock_skew", &self.clock_skew)
+            .field("check_issuer_country", &self.check_issuer_country)
+            .field("lenient_decoding", &self.lenient_decoding)
+            .field(
+                "rules",
+                &self
+                    .rules
+                    .iter()
+                    .map(|rule| rule.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<'a> Verifier<'a> {
+    /// Creates a verifier that checks signatures against the given trustlist.
+    ///
+    /// By default the current time is used to check the time validity, without any clock skew.
+    pub fn new(trustlist: &'a TrustList) -> Self {
+        Verifier {
+            trustlist,
+            validation_clock: None,
+            clock_skew: Duration::zero(),
+            check_issuer_country: false,
+            lenient_decoding: false,
+            rules: vec![],
+        }
+    }
+
+    /// Uses the given moment in time, instead of the current time, to check the time validity
+    /// and to evaluate the rules
+    pub fn with_validation_clock(mut self, validation_clock: DateTime<Utc>) -> Self {
+        self.validation_clock = Some(validation_clock);
+        self
+    }
+
+    /// Sets the tolerance used when checking the time validity of the certificate
+    pub fn with_clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Compares the issuer and the country of the entries of the certificate with the country of
+    /// the key that signed it (see [`check_issuer_country`]), reporting a mismatch as a warning
+    pub fn with_issuer_country_check(mut self) -> Self {
+        self.check_issuer_country = true;
+        self
+    }
+
+    /// Tolerates whitespace and uncompressed data when decoding the certificate (see
+    /// [`decode_cwt_lenient`](crate::decode_cwt_lenient)), reporting the leniencies that were
+    /// applied as warnings
+    pub fn with_lenient_decoding(mut self) -> Self {
+        self.lenient_decoding = true;
+        self
+    }
+
+    /// Adds a custom rule to be evaluated after the built-in checks
+    pub fn with_rule(mut self, rule: impl VerificationRule + 'a) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Runs all the checks on the given certificate data.
+    ///
+    /// This never fails: decoding errors are reported as failed checks and all the
+    /// checks that depend on them are reported as skipped.
+    pub fn verify(&self, data: &str) -> VerificationReport {
+        let mut checks = vec![];
+        let (container, matched_kid) = self.run_checks(data, &mut checks);
+
+        let status = if checks
+            .iter()
+            .any(|report| report.result.outcome == CheckOutcome::Failed)
+        {
+            VerificationStatus::Invalid
+        } else {
+            VerificationStatus::Valid
+        };
+
+        VerificationReport {
+            status,
+            checks,
+            container,
+            matched_kid,
+        }
+    }
+
+    fn run_checks(
+        &self,
+        data: &str,
+        checks: &mut Vec<CheckReport>,
+    ) -> (Option<DgcContainer>, Option<Vec<u8>>) {
+        let cwt = match decode(data, self.lenient_decoding, checks) {
+            Some(cwt) => cwt,
+            None => {
+                let skipped = DECODING_CHECKS[checks.len()..]
+                    .iter()
+                    .cloned()
+                    .chain(vec![Check::Signature, Check::TimeValidity])
+                    .chain(self.check_issuer_country.then_some(Check::IssuerCountry))
+                    .chain(self.rules.iter().map(|rule| Check::Rule(rule.name())));
+                for check in skipped {
+                    checks.push(CheckReport {
+                        check,
+                        result: CheckResult::skipped("The certificate could not be decoded"),
+                    });
+                }
+                return (None, None);
+            }
+        };
+        let mut report = |check: Check, result: CheckResult| {
+            checks.push(CheckReport { check, result });
+        };
+
+        let (signature_validity, matched) = check_signature(&cwt, self.trustlist);
+        let matched_kid = matched.map(|matched| matched.kid.to_vec());
+        let matched_entry = matched.map(|matched| matched.entry);
+        let result = if signature_validity.is_valid() {
+            CheckResult::passed(signature_validity.to_string())
+        } else {
+            CheckResult::failed(signature_validity.to_string())
+        };
+        report(Check::Signature, result);
+
+        let validation_clock = self.validation_clock.unwrap_or_else(Utc::now);
+        let time_validity = cwt.payload.time_validity(validation_clock, self.clock_skew);
+        let result = match time_validity {
+            TimeValidity::Valid => CheckResult::passed(time_validity.to_string()),
+            _ => CheckResult::failed(time_validity.to_string()),
+        };
+        report(Check::TimeValidity, result);
+
+        if self.check_issuer_country {
+            let result = match matched_entry.map(|entry| check_issuer_country(&cwt.payload, entry))
+            {
+                None => CheckResult::skipped("No key of the trustlist matched the signature"),
+                Some(validity @ IssuerCountryValidity::Consistent) => {
+                    CheckResult::passed(validity.to_string())
+                }
+                Some(validity @ IssuerCountryValidity::UnknownKeyCountry) => {
+                    CheckResult::skipped(validity.to_string())
+                }
+                Some(validity) => CheckResult::warning(validity.to_string()),
+            };
+            report(Check::IssuerCountry, result);
+        }
+
+        for rule in &self.rules {
+            report(
+                Check::Rule(rule.name()),
+                rule.check(&cwt.payload, validation_clock),
+            );
+        }
+
+        (Some(cwt.payload), matched_kid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{decode_base45, decompress};
+    use chrono::TimeZone;
+
+    // test data from https://dgc.a-sit.at/ehn/generate
+    const DATA: &str = "HC1:NCFOXN%TS3DH3ZSUZK+.V0ETD%65NL-AH-R6IOO6+IDOEZ/18WAV$E3+3AT4V22F/8X*G3M9JUPY0BX/KR96R/S09T./0LWTKD33236J3TA3M*4VV2 73-E3GG396B-43O058YIB73A*G3W19UEBY5:PI0EGSP4*2DN43U*0CEBQ/GXQFY73CIBC:G 7376BXBJBAJ UNFMJCRN0H3PQN*E33H3OA70M3FMJIJN523.K5QZ4A+2XEN QT QTHC31M3+E32R44$28A9H0D3ZCL4JMYAZ+S-A5$XKX6T2YC 35H/ITX8GL2-LH/CJTK96L6SR9MU9RFGJA6Q3QR$P2OIC0JVLA8J3ET3:H3A+2+33U SAAUOT3TPTO4UBZIC0JKQTL*QDKBO.AI9BVYTOCFOPS4IJCOT0$89NT2V457U8+9W2KQ-7LF9-DF07U$B97JJ1D7WKP/HLIJLRKF1MFHJP7NVDEBU1J*Z222E.GJI77N IKXN9+6J5DG3VWU5ZXT$ZRWP7++KM5MMUN/7UTFEEZPBK8C 7KMBI.3ZDBDREY7IM*N1KS3UI$6JD.JKLKA3UBJM-SJ9:OHBURZEF50WAQ 3";
+
+    /// Signs the test data again with a freshly generated key, to have a matching trustlist
+    fn signed_data() -> (TrustList, String) {
+        use ring::{rand::SystemRandom, signature};
+
+        let pkcs8 = signature::EcdsaKeyPair::generate_pkcs8(
+            &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+            &SystemRandom::new(),
+        )
+        .unwrap();
+        let key = crate::SigningKey::from_pkcs8(crate::EcAlg::Es256, pkcs8.as_ref()).unwrap();
+        let kid = [1, 2, 3, 4, 5, 6, 7, 8];
+        let data = crate::encode(&crate::decode(DATA).unwrap(), &key, &kid).unwrap();
+
+        let mut trustlist = TrustList::default();
+        trustlist.add(&kid, key.public_key().to_vec());
+        (trustlist, data)
+    }
+
+    fn outcomes(report: &VerificationReport) -> Vec<(Check, CheckOutcome)> {
+        report
+            .checks
+            .iter()
+            .map(|report| (report.check.clone(), report.result.outcome))
+            .collect()
+    }
+
+    struct IssuerRule(&'static str);
+
+    impl VerificationRule for IssuerRule {
+        fn name(&self) -> String {
+            format!("issuer is {}", self.0)
+        }
+
+        fn check(&self, container: &DgcContainer, _: DateTime<Utc>) -> CheckResult {
+            if container.issuer == self.0 {
+                CheckResult::passed("Issuer matches")
+            } else {
+                CheckResult::failed(format!("Unexpected issuer {}", container.issuer))
+            }
+        }
+    }
+
+    #[test]
+    fn it_reports_all_the_checks() {
+        let (trustlist, data) = signed_data();
+        // issued on 2021-06-26 and expiring on 2021-06-28
+        let report = Verifier::new(&trustlist)
+            .with_validation_clock(Utc.with_ymd_and_hms(2021, 6, 27, 0, 0, 0).unwrap())
+            .with_rule(IssuerRule("AT"))
+            .verify(&data);
+
+        assert_eq!(report.status, VerificationStatus::Valid);
+        assert_eq!(
+            outcomes(&report),
+            vec![
+                (Check::Prefix, CheckOutcome::Passed),
+                (Check::Base45Decode, CheckOutcome::Passed),
+                (Check::Decompression, CheckOutcome::Passed),
+                (Check::CwtDecode, CheckOutcome::Passed),
+                (Check::Signature, CheckOutcome::Passed),
+                (Check::TimeValidity, CheckOutcome::Passed),
+                (Check::Rule("issuer is AT".into()), CheckOutcome::Passed),
+            ]
+        );
+        assert_eq!(report.matched_kid, Some(vec![1, 2, 3, 4, 5, 6, 7, 8]));
+        assert_eq!(report.container.unwrap().issuer, "AT");
+    }
+
+    #[test]
+    fn it_warns_about_issuer_country_mismatches() {
+        let (mut trustlist, data) = signed_data();
+        let validation_clock = Utc.with_ymd_and_hms(2021, 6, 27, 0, 0, 0).unwrap();
+        let kid = [1, 2, 3, 4, 5, 6, 7, 8];
+        let issuer_country_outcome = |trustlist: &TrustList| {
+            let report = Verifier::new(trustlist)
+                .with_validation_clock(validation_clock)
+                .with_issuer_country_check()
+                .verify(&data);
+            assert!(report.is_valid());
+            report.get(&Check::IssuerCountry).unwrap().outcome
+        };
+
+        // the country of a raw key is unknown
+        assert_eq!(issuer_country_outcome(&trustlist), CheckOutcome::Skipped);
+
+        let mut entry = trustlist.remove(&kid).pop().unwrap();
+        entry.country = Some(String::from("AT"));
+        trustlist.add_entry(&kid, entry.clone());
+        assert_eq!(issuer_country_outcome(&trustlist), CheckOutcome::Passed);
+
+        entry.country = Some(String::from("DE"));
+        trustlist.add_entry(&kid, entry);
+        assert_eq!(issuer_country_outcome(&trustlist), CheckOutcome::Warning);
+
+        let report = Verifier::new(&trustlist)
+            .with_validation_clock(validation_clock)
+            .with_issuer_country_check()
+            .verify(&data);
+        let warnings: Vec<_> = report.warnings().map(|r| r.check.clone()).collect();
+        assert_eq!(warnings, vec![Check::IssuerCountry]);
+
+        // the check is performed only when enabled
+        let report = Verifier::new(&trustlist)
+            .with_validation_clock(validation_clock)
+            .verify(&data);
+        assert!(report.get(&Check::IssuerCountry).is_none());
+    }
+
+    #[test]
+    fn it_fails_expired_certificates() {
+        let (trustlist, data) = signed_data();
+        let validation_clock = Utc.with_ymd_and_hms(2021, 7, 1, 0, 0, 0).unwrap();
+        let report = Verifier::new(&trustlist)
+            .with_validation_clock(validation_clock)
+            .verify(&data);
+
+        assert_eq!(report.status, VerificationStatus::Invalid);
+        assert_eq!(
+            report.get(&Check::TimeValidity).unwrap().outcome,
+            CheckOutcome::Failed
+        );
+        assert_eq!(report.failures().count(), 1);
+
+        // a big enough clock skew makes it valid again
+        let report = Verifier::new(&trustlist)
+            .with_validation_clock(validation_clock)
+            .with_clock_skew(Duration::days(5))
+            .verify(&data);
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn it_fails_unknown_keys_and_rules() {
+        let trustlist = TrustList::default();
+        let report = Verifier::new(&trustlist)
+            .with_validation_clock(Utc.with_ymd_and_hms(2021, 6, 27, 0, 0, 0).unwrap())
+            .with_rule(IssuerRule("IT"))
+            .verify(DATA);
+
+        assert!(!report.is_valid());
+        let failures: Vec<_> = report.failures().map(|r| r.check.clone()).collect();
+        assert_eq!(
+            failures,
+            vec![Check::Signature, Check::Rule("issuer is IT".into())]
+        );
+        assert!(report
+            .get(&Check::Signature)
+            .unwrap()
+            .reason
+            .contains("was not found in the given trustlist"));
+        assert_eq!(report.matched_kid, None);
+    }
+
+    #[test]
+    fn it_skips_the_checks_after_a_decoding_failure() {
+        let trustlist = TrustList::default();
+        let data = format!("HC1:{}", base45::encode("not zlib data"));
+        let report = Verifier::new(&trustlist)
+            .with_rule(IssuerRule("AT"))
+            .verify(&data);
+
+        assert!(!report.is_valid());
+        assert!(report.container.is_none());
+        assert_eq!(
+            outcomes(&report),
+            vec![
+                (Check::Prefix, CheckOutcome::Passed),
+                (Check::Base45Decode, CheckOutcome::Passed),
+                (Check::Decompression, CheckOutcome::Failed),
+                (Check::CwtDecode, CheckOutcome::Skipped),
+                (Check::Signature, CheckOutcome::Skipped),
+                (Check::TimeValidity, CheckOutcome::Skipped),
+                (Check::Rule("issuer is AT".into()), CheckOutcome::Skipped),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_the_leniencies_as_warnings() {
+        let (trustlist, data) = signed_data();
+        let validation_clock = Utc.with_ymd_and_hms(2021, 6, 27, 0, 0, 0).unwrap();
+        let cose = decompress(decode_base45(&data[4..]).unwrap()).unwrap();
+        let data = format!("HC1:{}\n", base45::encode(cose));
+
+        let report = Verifier::new(&trustlist)
+            .with_validation_clock(validation_clock)
+            .verify(&data);
+        assert!(!report.is_valid());
+
+        let report = Verifier::new(&trustlist)
+            .with_validation_clock(validation_clock)
+            .with_lenient_decoding()
+            .verify(&data);
+        assert!(report.is_valid());
+        assert_eq!(
+            outcomes(&report)[..4],
+            [
+                (Check::Prefix, CheckOutcome::Warning),
+                (Check::Base45Decode, CheckOutcome::Passed),
+                (Check::Decompression, CheckOutcome::Warning),
+                (Check::CwtDecode, CheckOutcome::Passed),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_fails_on_invalid_prefix() {
+        let trustlist = TrustList::default();
+        let report = Verifier::new(&trustlist).verify("HC2:NCFOXN%TS3DH3ZSUZK");
+
+        assert_eq!(outcomes(&report)[0], (Check::Prefix, CheckOutcome::Failed));
+        assert_eq!(report.failures().count(), 1);
+    }
+}
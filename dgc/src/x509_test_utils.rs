@@ -0,0 +1,179 @@
+//! Helpers to build X.509 certificates in tests.
+
+use crate::der::{sequence, tlv};
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_COUNTRY_NAME: &[u8] = &[0x55, 0x04, 0x06];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+
+fn oid(encoded: &[u8]) -> Vec<u8> {
+    tlv(0x06, encoded)
+}
+
+fn bit_string(data: &[u8]) -> Vec<u8> {
+    tlv(0x03, &[&[0], data].concat())
+}
+
+fn name(country: &str, common_name: &str) -> Vec<u8> {
+    sequence(&[
+        tlv(
+            0x31,
+            &sequence(&[oid(OID_COUNTRY_NAME), tlv(0x13, country.as_bytes())]),
+        ),
+        tlv(
+            0x31,
+            &sequence(&[oid(OID_COMMON_NAME), tlv(0x0c, common_name.as_bytes())]),
+        ),
+    ])
+}
+
+/// Encodes an object identifier given in dotted notation.
+pub(crate) fn encode_oid(dotted: &str) -> Vec<u8> {
+    let arcs: Vec<u64> = dotted.split('.').map(|arc| arc.parse().unwrap()).collect();
+    let mut encoded = vec![(arcs[0] * 40 + arcs[1]) as u8];
+    for arc in &arcs[2..] {
+        let mut bytes = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            bytes.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        encoded.extend(bytes.iter().rev());
+    }
+    encoded
+}
+
+/// A P-256 key pair that can sign certificates.
+pub(crate) struct TestKey {
+    pub(crate) pkcs8: Vec<u8>,
+    key_pair: EcdsaKeyPair,
+}
+
+impl TestKey {
+    pub(crate) fn generate() -> Self {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &SystemRandom::new())
+                .unwrap()
+                .as_ref()
+                .to_vec();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8).unwrap();
+        TestKey { pkcs8, key_pair }
+    }
+
+    pub(crate) fn public_key(&self) -> &[u8] {
+        self.key_pair.public_key().as_ref()
+    }
+
+    /// Signs `data` with ECDSA using SHA-256, returning the ASN.1 DER encoded signature.
+    pub(crate) fn sign(&self, data: &[u8]) -> Vec<u8> {
+        self.key_pair
+            .sign(&SystemRandom::new(), data)
+            .unwrap()
+            .as_ref()
+            .to_vec()
+    }
+}
+
+/// The parameters of a test certificate.
+pub(crate) struct CertificateParams<'a> {
+    pub(crate) country: &'a str,
+    pub(crate) subject: &'a str,
+    pub(crate) issuer: &'a str,
+    /// UTCTime (`YYMMDDhhmmssZ`)
+    pub(crate) not_before: &'a str,
+    /// UTCTime (`YYMMDDhhmmssZ`)
+    pub(crate) not_after: &'a str,
+    /// The extended key usages in dotted notation
+    pub(crate) extended_key_usages: &'a [&'a str],
+    /// Adds the basic constraints extension of a CA certificate
+    pub(crate) ca: bool,
+    /// Adds the key usage extension allowing to sign certificates
+    pub(crate) key_cert_sign: bool,
+}
+
+impl<'a> CertificateParams<'a> {
+    pub(crate) fn new(subject: &'a str, issuer: &'a str) -> Self {
+        CertificateParams {
+            country: "IT",
+            subject,
+            issuer,
+            not_before: "210101000000Z",
+            not_after: "301231235959Z",
+            extended_key_usages: &[],
+            ca: false,
+            key_cert_sign: false,
+        }
+    }
+
+    /// The parameters of a self-signed CSCA certificate that can be used as a trust anchor.
+    pub(crate) fn csca(subject: &'a str) -> Self {
+        CertificateParams {
+            ca: true,
+            key_cert_sign: true,
+            ..CertificateParams::new(subject, subject)
+        }
+    }
+
+    /// Creates the DER encoded certificate of `subject_key` signed by `issuer_key`.
+    pub(crate) fn sign(&self, subject_key: &TestKey, issuer_key: &TestKey) -> Vec<u8> {
+        let algorithm = sequence(&[oid(OID_ECDSA_WITH_SHA256)]);
+        let public_key_info = sequence(&[
+            sequence(&[oid(OID_EC_PUBLIC_KEY), oid(OID_PRIME256V1)]),
+            bit_string(subject_key.public_key()),
+        ]);
+        let mut tbs_items = vec![
+            tlv(0xa0, &tlv(0x02, &[2])),
+            tlv(0x02, &[1]),
+            algorithm.clone(),
+            name(self.country, self.issuer),
+            sequence(&[
+                tlv(0x17, self.not_before.as_bytes()),
+                tlv(0x17, self.not_after.as_bytes()),
+            ]),
+            name(self.country, self.subject),
+            public_key_info,
+        ];
+        let mut extensions = Vec::new();
+        if self.ca {
+            let critical = tlv(0x01, &[0xff]);
+            let constraints = sequence(&[tlv(0x01, &[0xff])]);
+            extensions.push(sequence(&[
+                oid(OID_BASIC_CONSTRAINTS),
+                critical,
+                tlv(0x04, &constraints),
+            ]));
+        }
+        if self.key_cert_sign {
+            // keyCertSign and cRLSign, the last bit is unused
+            let usage = tlv(0x03, &[1, 0x06]);
+            extensions.push(sequence(&[oid(OID_KEY_USAGE), tlv(0x04, &usage)]));
+        }
+        if !self.extended_key_usages.is_empty() {
+            let usages: Vec<_> = self
+                .extended_key_usages
+                .iter()
+                .map(|usage| oid(&encode_oid(usage)))
+                .collect();
+            extensions.push(sequence(&[
+                oid(OID_EXTENDED_KEY_USAGE),
+                tlv(0x04, &sequence(&usages)),
+            ]));
+        }
+        if !extensions.is_empty() {
+            tbs_items.push(tlv(0xa3, &sequence(&extensions)));
+        }
+        let tbs_certificate = sequence(&tbs_items);
+        let signature = issuer_key.sign(&tbs_certificate);
+
+        sequence(&[tbs_certificate, algorithm, bit_string(&signature)])
+    }
+}
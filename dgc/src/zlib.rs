@@ -0,0 +1,206 @@
+//! A minimal zlib ([RFC 1950](https://datatracker.ietf.org/doc/html/rfc1950)) compressor.
+//!
+//! Certificates are a few hundred bytes long, so a single deflate block using the
+//! fixed Huffman codes and a greedy LZ77 matcher gets close enough to what
+//! general purpose compressors produce for this kind of payload.
+
+use adler32::RollingAdler32;
+use std::collections::HashMap;
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many previous occurrences of a 3 bytes sequence are tried when looking for a match
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Writes values in the bit order used by deflate (least significant bit first).
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn new(output: Vec<u8>) -> Self {
+        BitWriter {
+            output,
+            buffer: 0,
+            bits: 0,
+        }
+    }
+
+    fn write_bits(&mut self, value: u32, count: u8) {
+        self.buffer |= value << self.bits;
+        self.bits += count;
+        while self.bits >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit
+    fn write_code(&mut self, code: u32, length: u8) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.output.push(self.buffer as u8);
+        }
+        self.output
+    }
+}
+
+/// Writes a literal/length symbol using the fixed Huffman code (RFC 1951, section 3.2.6)
+fn write_literal_or_length(writer: &mut BitWriter, symbol: u16) {
+    let symbol = u32::from(symbol);
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let length = length as u16;
+    let code = LENGTH_BASE
+        .iter()
+        .rposition(|base| *base <= length)
+        .unwrap();
+    write_literal_or_length(writer, 257 + code as u16);
+    writer.write_bits(
+        u32::from(length - LENGTH_BASE[code]),
+        LENGTH_EXTRA_BITS[code],
+    );
+
+    let distance = distance as u16;
+    let code = DISTANCE_BASE
+        .iter()
+        .rposition(|base| *base <= distance)
+        .unwrap();
+    writer.write_code(code as u32, 5);
+    writer.write_bits(
+        u32::from(distance - DISTANCE_BASE[code]),
+        DISTANCE_EXTRA_BITS[code],
+    );
+}
+
+/// Checks if the data starts with a valid zlib header: the deflate compression method and a
+/// check value making the first two bytes a multiple of 31.
+pub(crate) fn has_zlib_header(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+/// Compresses the given data and wraps it in a zlib stream.
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    // CMF = deflate with a 32K window, FLG = default compression level, no dictionary
+    let mut writer = BitWriter::new(vec![0x78, 0x9c]);
+    // single final block compressed with the fixed Huffman codes
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+
+    let mut positions: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        if pos + MIN_MATCH <= data.len() {
+            if let Some(candidates) = positions.get(&data[pos..pos + MIN_MATCH]) {
+                let max_length = MAX_MATCH.min(data.len() - pos);
+                for &candidate in candidates.iter().rev().take(MAX_CHAIN) {
+                    if pos - candidate > WINDOW_SIZE {
+                        break;
+                    }
+                    let length = data[candidate..]
+                        .iter()
+                        .zip(&data[pos..pos + max_length])
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    if length > best_length {
+                        best_length = length;
+                        best_distance = pos - candidate;
+                    }
+                }
+            }
+        }
+
+        let step = if best_length >= MIN_MATCH {
+            write_match(&mut writer, best_length, best_distance);
+            best_length
+        } else {
+            write_literal_or_length(&mut writer, u16::from(data[pos]));
+            1
+        };
+        for index in pos..(pos + step) {
+            if index + MIN_MATCH <= data.len() {
+                positions
+                    .entry(&data[index..index + MIN_MATCH])
+                    .or_default()
+                    .push(index);
+            }
+        }
+        pos += step;
+    }
+    // end of block
+    write_literal_or_length(&mut writer, 256);
+
+    let mut output = writer.finish();
+    // checksum of the uncompressed data
+    let checksum = RollingAdler32::from_buffer(data).hash();
+    output.extend_from_slice(&checksum.to_be_bytes());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_compresses_data_that_can_be_inflated() {
+        let inputs: [&[u8]; 4] = [
+            b"",
+            b"a",
+            b"abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabc",
+            &[0u8; 1000],
+        ];
+
+        for input in inputs {
+            let compressed = compress(input);
+            let inflated = inflate::inflate_bytes_zlib(&compressed).unwrap();
+            assert_eq!(inflated, input);
+        }
+    }
+
+    #[test]
+    fn it_reduces_the_size_of_repetitive_data() {
+        let input = "URN:UVCI:01:IT:0123456789#A ".repeat(20);
+        let compressed = compress(input.as_bytes());
+        assert!(compressed.len() < input.len() / 4);
+        assert_eq!(
+            inflate::inflate_bytes_zlib(&compressed).unwrap(),
+            input.as_bytes()
+        );
+    }
+}
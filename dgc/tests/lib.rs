@@ -6,6 +6,24 @@ use serde_json::Value;
 use std::fs;
 use std::path::PathBuf;
 
+/// Compares two certificates considering equivalent the dates and times that
+/// represent the same instant with a different offset notation (e.g. `Z` and `+00:00`)
+fn assert_same_cert(actual: &Dgc, mut expected: Dgc) {
+    for (actual_test, expected_test) in actual.tests.iter().zip(expected.tests.iter_mut()) {
+        if actual_test.parsed_date_of_collection().is_ok()
+            && actual_test.parsed_date_of_collection() == expected_test.parsed_date_of_collection()
+        {
+            expected_test.date_of_collection = actual_test.date_of_collection.clone();
+        }
+        if actual_test.parsed_date_of_result().is_ok()
+            && actual_test.parsed_date_of_result() == expected_test.parsed_date_of_result()
+        {
+            expected_test.date_of_result = actual_test.date_of_result.clone();
+        }
+    }
+    assert_eq!(*actual, expected);
+}
+
 #[rstest]
 #[case::common_2dcode_raw_b1_json("common/2DCode/raw/B1.json")]
 #[case::common_2dcode_raw_cbo1_json("common/2DCode/raw/CBO1.json")]
@@ -110,11 +128,8 @@ use std::path::PathBuf;
 #[case::es_2dcode_raw_2101_json("ES/2DCode/raw/2101.json")]
 #[case::es_2dcode_raw_2102_json("ES/2DCode/raw/2102.json")]
 #[case::es_2dcode_raw_2103_json("ES/2DCode/raw/2103.json")]
-#[ignore = "Key is P-384. See #34"]
 #[case::es_2dcode_raw_401_json("ES/2DCode/raw/401.json")]
-#[ignore = "Key is P-384. See #34"]
 #[case::es_2dcode_raw_402_json("ES/2DCode/raw/402.json")]
-#[ignore = "Key is P-384. See #34"]
 #[case::es_2dcode_raw_403_json("ES/2DCode/raw/403.json")]
 #[case::es_2dcode_raw_501_json("ES/2DCode/raw/501.json")]
 #[case::es_2dcode_raw_502_json("ES/2DCode/raw/502.json")]
@@ -551,7 +566,6 @@ use std::path::PathBuf;
 #[case::pt_1_3_0_2dcode_raw_1_json("PT/1.3.0/2DCode/raw/1.json")]
 #[case::pt_1_3_0_2dcode_raw_2_json("PT/1.3.0/2DCode/raw/2.json")]
 #[case::pt_1_3_0_2dcode_raw_3_json("PT/1.3.0/2DCode/raw/3.json")]
-#[ignore = "Weird difference in date field (`sc`). Expected '2021-06-30T12:34:56+00:00', found: '2021-06-30T12:34:56Z'."]
 #[case::pt_1_3_0_2dcode_raw_4_json("PT/1.3.0/2DCode/raw/4.json")]
 #[case::pt_1_3_0_2dcode_raw_5_json("PT/1.3.0/2DCode/raw/5.json")]
 #[case::ro_2dcode_raw_1_json("RO/2DCode/raw/1.json")]
@@ -638,7 +652,7 @@ fn test_case(#[case] test_file: &str) {
     {
         // in some files (e.g. "common/2DCode/raw/CBO2.json") EXPECTEDVERIFY = false
         // is used to indicate malformed codes. So we need to exit early if the file is malformed
-        if matches!(cwt, Err(_)) {
+        if cwt.is_err() {
             return;
         }
     }
@@ -662,7 +676,10 @@ fn test_case(#[case] test_file: &str) {
         let cert_content = serde_json::ser::to_string(&test_data["JSON"]).unwrap();
         let expected_cert_payload: Dgc = serde_json::from_str(cert_content.as_str()).unwrap();
         // makes sure that the content of the decoded certificate matches the expectation
-        assert_eq!(*cwt.payload.certs.get(&1).unwrap(), expected_cert_payload);
+        assert_same_cert(
+            cwt.payload.certs.get(&1).unwrap().as_eu_dcc().unwrap(),
+            expected_cert_payload,
+        );
     }
 
     // Validates signature only if the CERTIFICATE field is populated in test data
@@ -677,6 +694,32 @@ fn test_case(#[case] test_file: &str) {
         let expected_verify = test_data["EXPECTEDRESULTS"]["EXPECTEDVERIFY"]
             .as_bool()
             .unwrap_or(true);
-        assert_eq!(signature_validity.is_valid(), expected_verify);
+        let expected_key_usage = test_data["EXPECTEDRESULTS"]["EXPECTEDKEYUSAGE"]
+            .as_bool()
+            .unwrap_or(true);
+        match test_file {
+            // the DSC only has the ICAO extended key usage, so it can sign every type of
+            // certificate even if EXPECTEDKEYUSAGE = false
+            "IS/2DCode/raw/3.json" => assert!(signature_validity.is_valid()),
+            // the DSC can only sign vaccinations, but EXPECTEDKEYUSAGE is missing
+            "LI/2DCode/raw/4.json" => assert!(matches!(
+                signature_validity,
+                SignatureValidity::CertificateTypeNotAllowed(CertificateType::Test)
+            )),
+            // the certificates were issued before the validity period of their DSC
+            "BG/2DCode/raw/4.json"
+            | "PL/1.0.0/2DCode/raw/10.json"
+            | "PL/1.2.1/2DCode/raw/10.json"
+            | "PL/1.3.0/2DCode/raw/10.json" => assert!(matches!(
+                signature_validity,
+                SignatureValidity::KeyNotYetValid { .. }
+            )),
+            // the signature is correct, but the DSC cannot sign this type of certificate
+            _ if expected_verify && !expected_key_usage => assert!(matches!(
+                signature_validity,
+                SignatureValidity::CertificateTypeNotAllowed(_)
+            )),
+            _ => assert_eq!(signature_validity.is_valid(), expected_verify),
+        }
     }
 }